@@ -176,6 +176,20 @@ pub fn main_facade(worker_guard: &mut Option<WorkerGuard>)
     } else {
         None
     };
+    if let Some(pal) = palette.as_mut() {
+        pal.load_libraries(config.palette_libraries.clone());
+        pal.set_active_variant(common.variant.clone())?;
+        pal.set_ignore_locks(common.force);
+        pal.set_history_entry_info(Some(command_text()), common.label.clone());
+        pal.set_history_limits(
+            config.history_max_entries,
+            config.history_max_bytes);
+        pal.set_journal(if config.journal {
+            Some(config.journal_compact_threshold)
+        } else {
+            None
+        });
+    }
     // event!(Level::TRACE, "Palette: {:#?}", palette);
 
     // Dispatch to appropriate commands.
@@ -187,10 +201,10 @@ pub fn main_facade(worker_guard: &mut Option<WorkerGuard>)
         &mut settings,
         Some(&cur_dir))?;
 
-    if let Some(pal) = palette {
+    if let Some(mut pal) = palette {
         if pal.modified() {
             event!(Level::DEBUG, "Palette modified, saving to load path.");
-            pal.write_to_load_path()
+            pal.write_changes_to_load_path()
                 .map(|_| ())
                 .context("Failed to write palette pile")?;
         }
@@ -213,3 +227,23 @@ pub fn main_facade(worker_guard: &mut Option<WorkerGuard>)
 
     Ok(())
 }
+
+
+////////////////////////////////////////////////////////////////////////////////
+// command_text
+////////////////////////////////////////////////////////////////////////////////
+/// Returns the text of the command line used to invoke the application.
+fn command_text() -> String {
+    std::iter::once("atma".to_string())
+        .chain(std::env::args().skip(1).map(|arg| {
+            if arg.is_empty() || arg.contains(|c: char| c.is_whitespace()
+                || c == '"')
+            {
+                format!("{:?}", arg)
+            } else {
+                arg
+            }
+        }))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
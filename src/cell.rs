@@ -35,6 +35,14 @@ pub use selection::*;
 pub use selector::*;
 
 
+////////////////////////////////////////////////////////////////////////////////
+// Constants
+////////////////////////////////////////////////////////////////////////////////
+
+/// The cell metadata key used for cell notes.
+pub const NOTE_KEY: &'static str = "note";
+
+
 ////////////////////////////////////////////////////////////////////////////////
 // Cell
 ////////////////////////////////////////////////////////////////////////////////
@@ -12,9 +12,11 @@
 use crate::cell::REF_ALL_TOKEN;
 use crate::cell::REF_POS_SEP_TOKEN;
 use crate::cell::REF_PREFIX_TOKEN;
+use crate::cell::REF_RELATIVE_TOKEN;
 use crate::parse::AtmaScanner;
 use crate::parse::AtmaToken;
 use crate::parse::position;
+use crate::parse::position_offset;
 use crate::parse::position_selector;
 
 // External library imports.
@@ -178,6 +180,88 @@ impl std::error::Error for PositionParseError {}
 
 
 
+////////////////////////////////////////////////////////////////////////////////
+// PositionOffset
+////////////////////////////////////////////////////////////////////////////////
+/// A signed offset from a `Position`, used for relative cell references.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[derive(Serialize, Deserialize)]
+#[serde(from = "(i32, i32, i32)")]
+#[serde(into = "(i32, i32, i32)")]
+pub struct PositionOffset {
+    /// The page offset.
+    pub page: i32,
+    /// The line offset.
+    pub line: i32,
+    /// The column offset.
+    pub column: i32,
+}
+
+impl PositionOffset {
+    /// Returns the position found by applying the offset to the given
+    /// position, or None if it would lie outside of the valid positions.
+    pub fn apply(&self, position: Position) -> Option<Position> {
+        let offset = |val: u16, off: i32| u16::try_from(i64::from(val)
+            + i64::from(off)).ok();
+
+        Some(Position {
+            page: offset(position.page, self.page)?,
+            line: offset(position.line, self.line)?,
+            column: offset(position.column, self.column)?,
+        })
+    }
+}
+
+// Conversion for simplifying serialization.
+impl From<PositionOffset> for (i32, i32, i32) {
+    fn from(offset: PositionOffset) -> Self {
+        (offset.page, offset.line, offset.column)
+    }
+}
+
+// Conversion for simplifying serialization.
+impl From<(i32, i32, i32)> for PositionOffset {
+    fn from(t: (i32, i32, i32)) -> Self {
+        PositionOffset {
+            page: t.0,
+            line: t.1,
+            column: t.2,
+        }
+    }
+}
+
+impl std::fmt::Display for PositionOffset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}", REF_PREFIX_TOKEN, REF_RELATIVE_TOKEN)?;
+        // Use the shortest form which preserves the offset.
+        if self.page != 0 {
+            write!(f, "{}{}", self.page, REF_POS_SEP_TOKEN)?;
+        }
+        if self.page != 0 || self.line != 0 {
+            write!(f, "{}{}", self.line, REF_POS_SEP_TOKEN)?;
+        }
+        write!(f, "{}", self.column)
+    }
+}
+
+impl std::str::FromStr for PositionOffset {
+    type Err = PositionParseError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        // Setup parser.
+        let scanner = AtmaScanner::new();
+        let mut lexer = Lexer::new(scanner, text, Lf::with_tab_width(4));
+        lexer.set_filter_fn(|tok| *tok != AtmaToken::Whitespace);
+
+        // Perform parse.
+        position_offset(lexer)
+            .finish()
+            .map_err(|_| PositionParseError)
+    }
+}
+
+
+
 ////////////////////////////////////////////////////////////////////////////////
 // PositionSelector
 ////////////////////////////////////////////////////////////////////////////////
@@ -10,6 +10,8 @@
 
 // Local imports.
 use crate::cell::Position;
+use crate::cell::PositionOffset;
+use crate::cell::REF_LIBRARY_TOKEN;
 use crate::cell::REF_PREFIX_TOKEN;
 use crate::parse::AtmaScanner;
 use crate::parse::AtmaToken;
@@ -54,6 +56,19 @@ pub enum CellRef<'name> {
         /// The index of the cell within the group.
         idx: u32,
     },
+
+    /// A reference to a cell based on an offset from the position of the cell
+    /// whose expression contains the reference.
+    Relative(PositionOffset),
+
+    /// A reference to a cell in another palette, identified by a palette
+    /// library alias.
+    External {
+        /// The alias of the palette library.
+        library: Cow<'name, str>,
+        /// The reference to the cell within the library palette.
+        cell_ref: Box<CellRef<'name>>,
+    },
 }
 
 impl<'name> CellRef<'name> {
@@ -63,11 +78,16 @@ impl<'name> CellRef<'name> {
         match self {
             Index(idx) => Index(idx),
             Position(position) => Position(position),
+            Relative(offset) => Relative(offset),
             Name(name) => Name(Cow::from(name.into_owned())),
             Group { group, idx } => Group {
                 group: Cow::from(group.into_owned()),
                 idx,
             },
+            External { library, cell_ref } => External {
+                library: Cow::from(library.into_owned()),
+                cell_ref: Box::new(cell_ref.into_static()),
+            },
         }
     }
 }
@@ -85,8 +105,11 @@ impl<'name> std::fmt::Display for CellRef<'name> {
             Index(idx) => write!(f, "{}{}", REF_PREFIX_TOKEN, idx),
             Name(name) => write!(f, "{}", name),
             Position(position) => write!(f, "{}", position),
+            Relative(offset) => write!(f, "{}", offset),
             Group { group, idx } => write!(f, 
                 "{}{}{}", group, REF_PREFIX_TOKEN, idx),
+            External { library, cell_ref } => write!(f,
+                "{}{}{}", library, REF_LIBRARY_TOKEN, cell_ref),
         }
     }
 }
@@ -50,6 +50,24 @@ pub const REF_RANGE_TOKEN: char = '-';
 /// The CellSelection list separator token.
 pub const REF_SEP_TOKEN: char = ',';
 
+/// The CellRef relative position prefix token.
+pub const REF_RELATIVE_TOKEN: char = '~';
+
+/// The CellRef palette library separator token.
+pub const REF_LIBRARY_TOKEN: &'static str = "::";
+
+/// The group path separator token.
+pub const REF_GROUP_PATH_TOKEN: char = '/';
+
+/// The CellSelector group subtree suffix token.
+pub const REF_GROUP_TREE_TOKEN: &'static str = "/**";
+
+/// The CellSelector metadata prefix token.
+pub const REF_META_TOKEN: char = '@';
+
+/// The CellSelector metadata value separator token.
+pub const REF_META_VALUE_TOKEN: char = '=';
+
 
 ////////////////////////////////////////////////////////////////////////////////
 // CellSelector
@@ -107,8 +125,22 @@ pub enum CellSelector<'name> {
         high: u32,
     },
 
-    /// Select alls cells within the given group.
+    /// Select alls cells within the given group and the groups nested one
+    /// level beneath it. Groups nested more deeply are not selected; use
+    /// `GroupTree` to select the full subtree.
     GroupAll(Cow<'name, str>),
+
+    /// Select all cells within the given group and all groups nested beneath
+    /// it.
+    GroupTree(Cow<'name, str>),
+
+    /// Select all cells with the given metadata key assigned.
+    Metadata {
+        /// The metadata key.
+        key: Cow<'name, str>,
+        /// The required metadata value, or None if any value is selected.
+        value: Option<Cow<'name, str>>,
+    },
 }
 
 impl<'name> CellSelector<'name> {
@@ -146,6 +178,11 @@ impl<'name> CellSelector<'name> {
                 high
             },
             GroupAll(group) => GroupAll(Cow::from(group.into_owned())),
+            GroupTree(group) => GroupTree(Cow::from(group.into_owned())),
+            Metadata { key, value } => Metadata {
+                key: Cow::from(key.into_owned()),
+                value: value.map(|v| Cow::from(v.into_owned())),
+            },
         }
     }
 
@@ -223,6 +260,7 @@ impl<'name> CellSelector<'name> {
         -> CellSelectorIndexIter<'name, 'p>
     {
         let mut pos_selector = PositionSelector::ALL;
+        let mut listed = Vec::new();
         let selector = {
             use CellSelector::*;
             match self {
@@ -250,6 +288,11 @@ impl<'name> CellSelector<'name> {
                     .resolve_group_if_occupied(group, *idx)
                     .map(Index),
                 
+                GroupAll(group) if basic.has_nested_groups(group) => {
+                    listed = basic.resolve_group_tree_if_occupied(group, false);
+                    None
+                },
+
                 GroupAll(group) => match basic.assigned_group_range(group) {
                     Few::Two(low, high) => Some(GroupRange {
                         group: group.clone(),
@@ -261,6 +304,11 @@ impl<'name> CellSelector<'name> {
                         .map(Index),
                     Few::Zero           => None,
                 },
+
+                GroupTree(group) => {
+                    listed = basic.resolve_group_tree_if_occupied(group, true);
+                    None
+                },
                 
                 GroupRange { group, low, high } => match basic
                     .assigned_group_subrange(group, *low, *high)
@@ -289,6 +337,13 @@ impl<'name> CellSelector<'name> {
                     Few::Zero           => None,
                 },
 
+                Metadata { key, value } => {
+                    listed = basic.resolve_metadata_if_occupied(
+                        key,
+                        value.as_ref().map(AsRef::as_ref));
+                    None
+                },
+
                 PositionSelector(position_selector) => {
                     pos_selector = position_selector.clone();
                     let (low, high) = position_selector.bounds();
@@ -309,6 +364,7 @@ impl<'name> CellSelector<'name> {
             basic,
             selector,
             pos_selector,
+            listed: listed.into_iter(),
         }
     }
 }
@@ -333,19 +389,32 @@ impl<'name> std::fmt::Display for CellSelector<'name> {
                 group, REF_PREFIX_TOKEN, high),
             GroupAll(group) => write!(f, 
                 "{}{}{}", group, REF_PREFIX_TOKEN, REF_ALL_TOKEN),
+            GroupTree(group) => write!(f, 
+                "{}{}", group, REF_GROUP_TREE_TOKEN),
+            Metadata { key, value: Some(value) } => write!(f,
+                "{}{}{}{}", REF_META_TOKEN, key, REF_META_VALUE_TOKEN, value),
+            Metadata { key, value: None } => write!(f,
+                "{}{}", REF_META_TOKEN, key),
         }
     }
 }
 
 
-impl<'name> From<CellRef<'name>> for CellSelector<'name> {
-    fn from(cell_ref: CellRef<'name>) -> Self {
+impl<'name> TryFrom<CellRef<'name>> for CellSelector<'name> {
+    type Error = InvalidCellSelector;
+    fn try_from(cell_ref: CellRef<'name>) -> Result<Self, Self::Error> {
         match cell_ref {
-            CellRef::Index(idx) => CellSelector::Index(idx),
+            CellRef::Index(idx) => Ok(CellSelector::Index(idx)),
             CellRef::Position(pos)
-                => CellSelector::PositionSelector(pos.into()),
-            CellRef::Name(name) => CellSelector::Name(name),
-            CellRef::Group { group, idx } => CellSelector::Group { group, idx },
+                => Ok(CellSelector::PositionSelector(pos.into())),
+            CellRef::Name(name) => Ok(CellSelector::Name(name)),
+            CellRef::Group { group, idx }
+                => Ok(CellSelector::Group { group, idx }),
+            // External cells are never part of the local palette, and relative
+            // references have no origin outside of an expression.
+            other @ CellRef::Relative(_)     |
+            other @ CellRef::External { .. }
+                => Err(InvalidCellSelector::unselectable(other)),
         }
     }
 }
@@ -400,6 +469,7 @@ struct CellSelectorIndexIter<'t, 'p> {
     basic: &'p BasicPalette,
     selector: Option<CellSelector<'t>>,
     pos_selector: PositionSelector,
+    listed: std::vec::IntoIter<u32>,
 } 
 
 impl<'t, 'p> std::iter::FusedIterator for CellSelectorIndexIter<'t, 'p> {}
@@ -409,7 +479,8 @@ impl<'t, 'p> Iterator for CellSelectorIndexIter<'t, 'p> {
     fn next(&mut self) -> Option<Self::Item> {
         use CellSelector::*;
         match self.selector.take() {
-            None => None,
+            // Selectors resolved during construction are listed directly.
+            None => self.listed.next(),
 
             Some(Index(idx)) => {
                 self.selector = None;
@@ -505,8 +576,11 @@ impl<'t, 'p> Iterator for CellSelectorIndexIter<'t, 'p> {
             // * All should be handled by IndexRange.
             // * Name should be resolved and handled by Index.
             // * Group should be resolved and handled by Index.
-            // * GroupAll should be handled by GroupRange.
             // * PositionSelector should be handled by PositionRange.
+            // * GroupAll should be handled by GroupRange, or resolved and
+            //   handled by the listed indices if it has nested groups.
+            // * GroupTree and Metadata should be resolved and handled by the
+            //   listed indices.
             Some(_) => unreachable!(),
         }
     }
@@ -534,6 +608,11 @@ pub enum InvalidCellSelector {
         /// The range's upper bound.
         high: Cow<'static, str>,
     },
+    /// A CellRef which can't be used to select cells in the palette.
+    Unselectable {
+        /// The reference.
+        cell_ref: Cow<'static, str>,
+    },
 }
 
 impl InvalidCellSelector {
@@ -557,6 +636,13 @@ impl InvalidCellSelector {
             high: format!("{}", high).into(),
         }
     }
+
+    /// Constructs an `InvalidCellSelector::Unselectable` from a `CellRef`.
+    pub fn unselectable<'name>(cell_ref: CellRef<'name>) -> Self {
+        InvalidCellSelector::Unselectable {
+            cell_ref: format!("{}", cell_ref).into(),
+        }
+    }
 }
 
 
@@ -572,6 +658,9 @@ impl std::fmt::Display for InvalidCellSelector {
             RangeOrder { low, high } => write!(f, "range lower bound '{}'\
                 exceeds range upper bound '{}'",
                 low, high),
+            Unselectable { cell_ref } => write!(f, "reference '{}' does \
+                not select cells in the palette",
+                cell_ref),
         }
     }
 }
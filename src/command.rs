@@ -16,7 +16,12 @@ mod script;
 
 /// Public modules.
 pub mod new;
+pub mod check;
+pub mod convert;
+pub mod diff;
 pub mod export_png;
+pub mod history;
+pub mod import_png;
 pub mod list;
 
 // Exports.
@@ -11,12 +11,16 @@
 // Internal library imports.
 use crate::color::Color;
 use crate::cell::Position;
+use crate::palette::ColorSpace;
 
 // External library imports.
 use colored::Colorize as _;
 use serde::Serialize;
 use serde::Deserialize;
 
+// Standard library imports.
+use std::path::PathBuf;
+
 
 ////////////////////////////////////////////////////////////////////////////////
 // CursorBehavior
@@ -120,6 +124,145 @@ impl std::fmt::Display for InvalidPositioning {
 impl std::error::Error for InvalidPositioning {}
 
 
+////////////////////////////////////////////////////////////////////////////////
+// ShiftMode
+////////////////////////////////////////////////////////////////////////////////
+/// The range of cells shifted to make room for inserted or moved cells.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Serialize, Deserialize)]
+pub enum ShiftMode {
+    /// Shift the following cells on the same line.
+    Line,
+    /// Shift the following cells on the same page, wrapping across lines at
+    /// the palette's maximum column.
+    Page,
+}
+
+impl std::str::FromStr for ShiftMode {
+    type Err = InvalidShiftMode;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "line" => Ok(ShiftMode::Line),
+            "page" => Ok(ShiftMode::Page),
+            _      => Err(InvalidShiftMode),
+        }
+    }
+}
+
+/// Error type for an invalid shift mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct InvalidShiftMode;
+
+impl std::fmt::Display for InvalidShiftMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid shift mode: expected one of 'line' or 'page'")
+    }
+}
+
+impl std::error::Error for InvalidShiftMode {}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// ConflictPolicy
+////////////////////////////////////////////////////////////////////////////////
+/// The resolution of name and position conflicts when merging palettes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Serialize, Deserialize)]
+pub enum ConflictPolicy {
+    /// Keep the existing cell and discard the merged one.
+    Skip,
+    /// Replace the existing cell's expression with the merged one.
+    Overwrite,
+    /// Move the merged cell to a free position and give it a unique name.
+    Rename,
+}
+
+impl std::str::FromStr for ConflictPolicy {
+    type Err = InvalidConflictPolicy;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "skip"      => Ok(ConflictPolicy::Skip),
+            "overwrite" => Ok(ConflictPolicy::Overwrite),
+            "rename"    => Ok(ConflictPolicy::Rename),
+            _           => Err(InvalidConflictPolicy),
+        }
+    }
+}
+
+/// Error type for an invalid conflict policy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct InvalidConflictPolicy;
+
+impl std::fmt::Display for InvalidConflictPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid conflict policy: expected one of 'skip', \
+            'overwrite', or 'rename'")
+    }
+}
+
+impl std::error::Error for InvalidConflictPolicy {}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// PaletteSource
+////////////////////////////////////////////////////////////////////////////////
+/// Option parse result for a palette to compare.
+#[derive(Debug, Clone)]
+#[derive(Serialize, Deserialize)]
+pub enum PaletteSource {
+    /// The active palette, given as '@'.
+    Active,
+    /// The active palette with the given number of history entries undone,
+    /// given as '@~N'.
+    Revision(usize),
+    /// A palette file.
+    Path(PathBuf),
+}
+
+impl PaletteSource {
+    /// Returns true if the source is the active palette or one of its
+    /// revisions.
+    pub fn is_active(&self) -> bool {
+        match self {
+            PaletteSource::Path(_) => false,
+            _                      => true,
+        }
+    }
+}
+
+impl std::str::FromStr for PaletteSource {
+    type Err = InvalidPaletteSource;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "@" => Ok(PaletteSource::Active),
+            _   => match text.strip_prefix("@~") {
+                Some(count) => count
+                    .parse()
+                    .map(PaletteSource::Revision)
+                    .map_err(|_| InvalidPaletteSource),
+                None => Ok(PaletteSource::Path(PathBuf::from(text))),
+            },
+        }
+    }
+}
+
+/// Error type for an invalid palette source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct InvalidPaletteSource;
+
+impl std::fmt::Display for InvalidPaletteSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid palette source: expected '@', '@~N', or a palette \
+            file path")
+    }
+}
+
+impl std::error::Error for InvalidPaletteSource {}
+
+
 ////////////////////////////////////////////////////////////////////////////////
 // HistorySetOption
 ////////////////////////////////////////////////////////////////////////////////
@@ -162,6 +305,47 @@ impl std::fmt::Display for InvalidHistorySetOption {
 impl std::error::Error for InvalidHistorySetOption {}
 
 
+////////////////////////////////////////////////////////////////////////////////
+// EntryRange
+////////////////////////////////////////////////////////////////////////////////
+/// Option parse result for a range of history entries, given as 'FROM..TO'.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Serialize, Deserialize)]
+pub struct EntryRange {
+    /// The id of the first entry in the range.
+    pub from: usize,
+    /// The id of the last entry in the range.
+    pub to: usize,
+}
+
+impl std::str::FromStr for EntryRange {
+    type Err = InvalidEntryRange;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut parts = text.splitn(2, "..");
+        match (parts.next(), parts.next()) {
+            (Some(from), Some(to)) => Ok(EntryRange {
+                from: from.trim().parse().map_err(|_| InvalidEntryRange)?,
+                to: to.trim().parse().map_err(|_| InvalidEntryRange)?,
+            }),
+            _ => Err(InvalidEntryRange),
+        }
+    }
+}
+
+/// Error type for an invalid history entry range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct InvalidEntryRange;
+
+impl std::fmt::Display for InvalidEntryRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid history entry range: expected 'FROM..TO'")
+    }
+}
+
+impl std::error::Error for InvalidEntryRange {}
+
+
 
 ////////////////////////////////////////////////////////////////////////////////
 // ListMode
@@ -176,6 +360,10 @@ pub enum ListMode {
     Lines,
     /// Display colors in a line.
     List,
+    /// Display one color per line, followed by its notes and metadata.
+    Detail,
+    /// Display the group hierarchy with one color per line.
+    Groups,
 }
 
 impl std::str::FromStr for ListMode {
@@ -183,10 +371,12 @@ impl std::str::FromStr for ListMode {
 
     fn from_str(text: &str) -> Result<Self, Self::Err> {
         match text {
-            "grid"  => Ok(ListMode::Grid),
-            "lines" => Ok(ListMode::Lines),
-            "list"  => Ok(ListMode::List),
-            _       => Err(InvalidListMode),
+            "grid"   => Ok(ListMode::Grid),
+            "lines"  => Ok(ListMode::Lines),
+            "list"   => Ok(ListMode::List),
+            "detail" => Ok(ListMode::Detail),
+            "groups" => Ok(ListMode::Groups),
+            _        => Err(InvalidListMode),
         }
     }
 }
@@ -198,7 +388,7 @@ pub struct InvalidListMode;
 impl std::fmt::Display for InvalidListMode {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "invalid list mode: expected one of 'grid', \
-            'lines', or 'list'")
+            'lines', 'list', 'detail', or 'groups'")
     }
 }
 
@@ -423,6 +613,112 @@ impl std::fmt::Display for InvalidGutterStyle {
 impl std::error::Error for InvalidGutterStyle {}
 
 
+////////////////////////////////////////////////////////////////////////////////
+// QuantizeMethod
+////////////////////////////////////////////////////////////////////////////////
+/// Option parse result for color quantization method.
+#[derive(Debug, Clone, Copy)]
+#[derive(Serialize, Deserialize)]
+pub enum QuantizeMethod {
+    /// Recursively split the color space at the median of the widest channel.
+    MedianCut,
+    /// Iteratively refine cluster centers seeded by the median cut.
+    KMeans,
+}
+
+impl std::str::FromStr for QuantizeMethod {
+    type Err = InvalidQuantizeMethod;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "median_cut" => Ok(QuantizeMethod::MedianCut),
+            "k_means"    => Ok(QuantizeMethod::KMeans),
+            _            => Err(InvalidQuantizeMethod),
+        }
+    }
+}
+
+/// Error type for an invalid quantize method.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct InvalidQuantizeMethod;
+
+impl std::fmt::Display for InvalidQuantizeMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid quantize method: expected one of 'median_cut' or \
+            'k_means'")
+    }
+}
+
+impl std::error::Error for InvalidQuantizeMethod {}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// SortKey
+////////////////////////////////////////////////////////////////////////////////
+/// Option parse result for the color property to sort by.
+#[derive(Debug, Clone, Copy)]
+#[derive(Serialize, Deserialize)]
+pub enum SortKey {
+    /// Sort by HSV hue.
+    Hue,
+    /// Sort by HSL lightness.
+    Lightness,
+    /// Sort by relative luminance.
+    Luminance,
+    /// Sort by HSV saturation.
+    Saturation,
+    /// Sort by RGB chroma.
+    Chroma,
+}
+
+impl SortKey {
+    /// Returns the sort key value for the given color.
+    pub fn key(&self, color: Color) -> f32 {
+        let [r, g, b] = color.rgb_ratios();
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+        match self {
+            SortKey::Hue        => color.hsv_components()[0],
+            SortKey::Lightness  => (max + min) / 2.0,
+            SortKey::Luminance  => {
+                let [r, g, b] = ColorSpace::LinearRgb.components(color);
+                0.2126 * r + 0.7152 * g + 0.0722 * b
+            },
+            SortKey::Saturation => color.hsv_components()[1],
+            SortKey::Chroma     => max - min,
+        }
+    }
+}
+
+impl std::str::FromStr for SortKey {
+    type Err = InvalidSortKey;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "hue"        => Ok(SortKey::Hue),
+            "lightness"  => Ok(SortKey::Lightness),
+            "luminance"  => Ok(SortKey::Luminance),
+            "saturation" => Ok(SortKey::Saturation),
+            "chroma"     => Ok(SortKey::Chroma),
+            _            => Err(InvalidSortKey),
+        }
+    }
+}
+
+/// Error type for an invalid sort key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct InvalidSortKey;
+
+impl std::fmt::Display for InvalidSortKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid sort key: expected one of 'hue', 'lightness', \
+            'luminance', 'saturation', or 'chroma'")
+    }
+}
+
+impl std::error::Error for InvalidSortKey {}
+
+
 ////////////////////////////////////////////////////////////////////////////////
 // ColorDisplay
 ////////////////////////////////////////////////////////////////////////////////
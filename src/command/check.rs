@@ -0,0 +1,510 @@
+////////////////////////////////////////////////////////////////////////////////
+// Atma structured color palette
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Module for the `check` command.
+////////////////////////////////////////////////////////////////////////////////
+
+
+// Internal module imports.
+use crate::cell::CellRef;
+use crate::cell::Position;
+use crate::color::Color;
+use crate::command::diff::delta_e;
+use crate::command::diff::lab_components;
+use crate::palette::BasicPalette;
+use crate::palette::Expr;
+use crate::setup::Config;
+
+// External library imports.
+use colored::Colorize as _;
+use serde::Deserialize;
+use serde::Serialize;
+
+// Standard library imports.
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Severity
+////////////////////////////////////////////////////////////////////////////////
+/// The severity of a check diagnostic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Serialize, Deserialize)]
+pub enum Severity {
+    /// The rule is disabled.
+    Allow,
+    /// The diagnostic is reported, but does not fail the check.
+    Warning,
+    /// The diagnostic is reported and fails the check.
+    Error,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Allow   => write!(f, "allow"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error   => write!(f, "error"),
+        }
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Rule
+////////////////////////////////////////////////////////////////////////////////
+/// A palette check rule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Rule {
+    /// The stable code of the rule.
+    pub code: &'static str,
+    /// The name of the rule.
+    pub name: &'static str,
+    /// The default severity of the rule.
+    pub severity: Severity,
+}
+
+/// A reference which does not resolve to a cell.
+pub const DANGLING_REFERENCE: Rule = Rule {
+    code: "A001",
+    name: "dangling_reference",
+    severity: Severity::Error,
+};
+
+/// A cell whose expression refers back to itself.
+pub const REFERENCE_CYCLE: Rule = Rule {
+    code: "A002",
+    name: "reference_cycle",
+    severity: Severity::Error,
+};
+
+/// A cell with an empty expression.
+pub const EMPTY_CELL: Rule = Rule {
+    code: "A003",
+    name: "empty_cell",
+    severity: Severity::Warning,
+};
+
+/// A name assigned to a position selector with no cells.
+pub const UNOCCUPIED_NAME: Rule = Rule {
+    code: "A004",
+    name: "unoccupied_name",
+    severity: Severity::Warning,
+};
+
+/// A group member which is not in the palette.
+pub const GROUP_HOLE: Rule = Rule {
+    code: "A005",
+    name: "group_hole",
+    severity: Severity::Warning,
+};
+
+/// A cell whose color lies outside of the RGB gamut.
+pub const OUT_OF_GAMUT: Rule = Rule {
+    code: "A006",
+    name: "out_of_gamut",
+    severity: Severity::Warning,
+};
+
+/// Cells with identical colors.
+pub const DUPLICATE_COLOR: Rule = Rule {
+    code: "A007",
+    name: "duplicate_color",
+    severity: Severity::Warning,
+};
+
+/// Cells with nearly identical colors.
+pub const NEAR_DUPLICATE_COLOR: Rule = Rule {
+    code: "A008",
+    name: "near_duplicate_color",
+    severity: Severity::Warning,
+};
+
+/// A cell which is neither referenced nor positioned.
+pub const UNUSED_CELL: Rule = Rule {
+    code: "A009",
+    name: "unused_cell",
+    severity: Severity::Warning,
+};
+
+/// All palette check rules.
+pub const RULES: [Rule; 9] = [
+    DANGLING_REFERENCE,
+    REFERENCE_CYCLE,
+    EMPTY_CELL,
+    UNOCCUPIED_NAME,
+    GROUP_HOLE,
+    OUT_OF_GAMUT,
+    DUPLICATE_COLOR,
+    NEAR_DUPLICATE_COLOR,
+    UNUSED_CELL,
+];
+
+impl Rule {
+    /// Returns the severity of the rule, using the given overrides keyed by
+    /// rule code or name.
+    pub fn severity(&self, overrides: &BTreeMap<String, Severity>)
+        -> Severity
+    {
+        overrides.get(self.code)
+            .or_else(|| overrides.get(self.name))
+            .copied()
+            .unwrap_or(self.severity)
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Diagnostic
+////////////////////////////////////////////////////////////////////////////////
+/// A problem found by a palette check.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    /// The rule which produced the diagnostic.
+    pub rule: Rule,
+    /// The severity of the diagnostic.
+    pub severity: Severity,
+    /// A description of the problem.
+    pub message: String,
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let label = format!("{}[{}]", self.severity, self.rule.code);
+        let label = match self.severity {
+            Severity::Error => label.red().bold(),
+            _               => label.yellow().bold(),
+        };
+        write!(f, "{}: {} ({})", label, self.message, self.rule.name)
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// check
+////////////////////////////////////////////////////////////////////////////////
+/// Checks the palette against the rules, returning the diagnostics for the
+/// rules which are not disabled in the config.
+pub fn check(palette: &BasicPalette, config: &Config) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let mut report = |rule: Rule, message: String| {
+        let severity = rule.severity(&config.check_rules);
+        if severity != Severity::Allow {
+            diagnostics.push(Diagnostic { rule, severity, message });
+        }
+    };
+
+    let exprs: BTreeMap<u32, &Expr> = palette.exprs().collect();
+
+    // Collect the references between cells.
+    let mut edges: BTreeMap<u32, BTreeSet<u32>> = BTreeMap::new();
+    let mut referenced: BTreeSet<u32> = BTreeSet::new();
+    for (&idx, expr) in exprs.iter() {
+        let targets = edges.entry(idx).or_default();
+        for cell_ref in local_refs(palette, expr, idx, &mut report) {
+            match palette.resolve_ref_to_index(&cell_ref) {
+                Ok(target) if exprs.contains_key(&target) => {
+                    let _ = targets.insert(target);
+                    let _ = referenced.insert(target);
+                },
+                _ => report(DANGLING_REFERENCE, format!(
+                    "cell :{} refers to undefined cell {}", idx, cell_ref)),
+            }
+        }
+    }
+    for (_, idx, expr) in palette.variant_exprs() {
+        for cell_ref in local_refs(palette, expr, idx, &mut |_, _| ()) {
+            if let Ok(target) = palette.resolve_ref_to_index(&cell_ref) {
+                let _ = referenced.insert(target);
+            }
+        }
+    }
+
+    for members in reference_cycles(&edges) {
+        let message = match &members[..] {
+            [idx] => format!("cell :{} refers to itself", idx),
+            _     => format!("cells {} form a reference cycle",
+                cell_list(&members)),
+        };
+        report(REFERENCE_CYCLE, message);
+    }
+
+    for (&idx, expr) in exprs.iter() {
+        if let Expr::Empty = expr {
+            report(EMPTY_CELL, format!("cell :{} is empty", idx));
+        }
+    }
+
+    let occupied: Vec<Position> = palette
+        .assigned_positions()
+        .filter(|(_, idx)| exprs.contains_key(idx))
+        .map(|(position, _)| position)
+        .collect();
+    for (name, selector) in palette.names() {
+        if !occupied.iter().any(|p| selector.contains(p)) {
+            report(UNOCCUPIED_NAME, format!(
+                "name \"{}\" is assigned to unoccupied position {}",
+                name,
+                selector));
+        }
+    }
+
+    for (group, members) in palette.groups() {
+        for (group_idx, idx) in members.iter().enumerate() {
+            if !exprs.contains_key(idx) {
+                report(GROUP_HOLE, format!(
+                    "group \"{}\" member {} refers to missing cell :{}",
+                    group,
+                    group_idx,
+                    idx));
+            }
+        }
+    }
+
+    let colors: Vec<(u32, Color)> = exprs
+        .keys()
+        .filter_map(|idx| palette
+            .color(&CellRef::Index(*idx))
+            .ok()
+            .flatten()
+            .map(|c| (*idx, c)))
+        .collect();
+    for (idx, color) in colors.iter() {
+        if !in_gamut(color.rgb_ratios()) {
+            report(OUT_OF_GAMUT, format!(
+                "cell :{} has a color outside of the RGB gamut", idx));
+        }
+    }
+
+    let mut duplicates: BTreeMap<[u8; 3], Vec<u32>> = BTreeMap::new();
+    for (idx, color) in colors.iter() {
+        duplicates.entry(color.rgb_octets()).or_default().push(*idx);
+    }
+    for members in duplicates.values().filter(|m| m.len() > 1) {
+        report(DUPLICATE_COLOR, format!(
+            "cells {} have the same color", cell_list(members)));
+    }
+
+    let threshold = config.check_near_duplicate_delta_e;
+    for (a, b) in near_duplicates(&colors, threshold) {
+        let ((idx_a, color_a), (idx_b, color_b)) = (colors[a], colors[b]);
+        report(NEAR_DUPLICATE_COLOR, format!(
+            "cells :{} and :{} have nearly the same color (ΔE {:.2})",
+            idx_a,
+            idx_b,
+            delta_e(color_a, color_b)));
+    }
+
+    for &idx in exprs.keys() {
+        let positioned = palette
+            .assigned_position(&CellRef::Index(idx))
+            .is_some();
+        if !positioned && !referenced.contains(&idx) {
+            report(UNUSED_CELL, format!(
+                "cell :{} is neither referenced nor positioned", idx));
+        }
+    }
+
+    diagnostics
+}
+
+/// Returns the local cell references of the expression of the cell with the
+/// given index, with relative references resolved. Relative references which
+/// cannot be resolved are reported as dangling.
+fn local_refs<F>(
+    palette: &BasicPalette,
+    expr: &Expr,
+    idx: u32,
+    report: &mut F)
+    -> Vec<CellRef<'static>>
+    where F: FnMut(Rule, String)
+{
+    let resolved = palette.resolve_relative_refs(expr, idx);
+    if resolved.is_err() {
+        report(DANGLING_REFERENCE, format!(
+            "cell :{} has a relative reference with no target", idx));
+    }
+
+    let mut expr = resolved.unwrap_or_else(|_| expr.clone());
+    let mut refs = Vec::new();
+    expr.map_cell_refs(|cell_ref| match cell_ref {
+        CellRef::Relative(_)     |
+        CellRef::External { .. } => (),
+        _ => refs.push(cell_ref.clone()),
+    });
+    refs
+}
+
+/// Returns true if the given RGB ratios are finite and within the RGB gamut.
+pub(in crate) fn in_gamut(ratios: [f32; 3]) -> bool {
+    ratios.iter().all(|c| c.is_finite() && *c >= 0.0 && *c <= 1.0)
+}
+
+/// Returns the given cell indices formatted as a comma-separated list.
+fn cell_list(indices: &[u32]) -> String {
+    indices
+        .iter()
+        .map(|idx| format!(":{}", idx))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Returns the reference cycles between cells, each listing its members in
+/// ascending order.
+///
+/// The cycles are the strongly connected components of the reference graph
+/// which have more than one member or a member referring to itself. They are
+/// found in a single pass using Tarjan's algorithm.
+fn reference_cycles(edges: &BTreeMap<u32, BTreeSet<u32>>) -> Vec<Vec<u32>> {
+    let no_targets = BTreeSet::new();
+    let mut order: BTreeMap<u32, usize> = BTreeMap::new();
+    let mut low_link: BTreeMap<u32, usize> = BTreeMap::new();
+    let mut component: Vec<u32> = Vec::new();
+    let mut on_component: BTreeSet<u32> = BTreeSet::new();
+    let mut cycles = Vec::new();
+
+    for &root in edges.keys() {
+        if order.contains_key(&root) { continue; }
+
+        // Each frame holds a visited cell and its unexplored references.
+        let mut frames = Vec::new();
+        let mut next = Some(root);
+        loop {
+            if let Some(idx) = next.take() {
+                let position = order.len();
+                let _ = order.insert(idx, position);
+                let _ = low_link.insert(idx, position);
+                component.push(idx);
+                let _ = on_component.insert(idx);
+                frames.push((
+                    idx,
+                    edges.get(&idx).unwrap_or(&no_targets).iter()));
+            }
+
+            let (idx, target) = match frames.last_mut() {
+                Some((idx, targets)) => (*idx, targets.next().copied()),
+                None                 => break,
+            };
+
+            if let Some(target) = target {
+                match order.get(&target) {
+                    None => next = Some(target),
+                    Some(&target_order) if on_component.contains(&target) => {
+                        lower_link(&mut low_link, idx, target_order);
+                    },
+                    Some(_) => (),
+                }
+                continue;
+            }
+
+            // All references of the cell have been explored.
+            let _ = frames.pop();
+            let idx_link = low_link[&idx];
+            if let Some((parent, _)) = frames.last() {
+                lower_link(&mut low_link, *parent, idx_link);
+            }
+            if idx_link != order[&idx] { continue; }
+
+            let mut members = Vec::new();
+            while let Some(member) = component.pop() {
+                let _ = on_component.remove(&member);
+                members.push(member);
+                if member == idx { break; }
+            }
+            let self_ref = edges
+                .get(&idx)
+                .map_or(false, |targets| targets.contains(&idx));
+            if members.len() > 1 || self_ref {
+                members.sort_unstable();
+                cycles.push(members);
+            }
+        }
+    }
+    cycles
+}
+
+/// Lowers the low link of the given cell to the given value if it is lower.
+fn lower_link(low_link: &mut BTreeMap<u32, usize>, idx: u32, value: usize) {
+    if let Some(link) = low_link.get_mut(&idx) {
+        *link = (*link).min(value);
+    }
+}
+
+/// Returns the index pairs of the given colors which have different RGB
+/// octets and a color difference below the threshold, in ascending order.
+///
+/// The colors are bucketed into a grid of CIELAB cubes with sides of the
+/// threshold length, so that each color is only compared with the colors in
+/// its own and adjacent cubes.
+fn near_duplicates(colors: &[(u32, Color)], threshold: f32)
+    -> Vec<(usize, usize)>
+{
+    let mut pairs = Vec::new();
+    if threshold.is_nan() || threshold <= 0.0 { return pairs; }
+
+    let cube = |lab: [f32; 3]| {
+        let [l, a, b] = lab;
+        [
+            (l / threshold).floor() as i32,
+            (a / threshold).floor() as i32,
+            (b / threshold).floor() as i32,
+        ]
+    };
+
+    let mut grid: HashMap<[i32; 3], Vec<usize>> = HashMap::new();
+    let mut cubes = Vec::with_capacity(colors.len());
+    for (i, (_, color)) in colors.iter().enumerate() {
+        let lab = lab_components(*color);
+        // Colors without finite components have no finite difference.
+        if !lab.iter().all(|c| c.is_finite()) {
+            cubes.push(None);
+            continue;
+        }
+        let key = cube(lab);
+        grid.entry(key).or_default().push(i);
+        cubes.push(Some(key));
+    }
+
+    for (i, key) in cubes.iter().enumerate() {
+        let [l, a, b] = match key {
+            Some(key) => *key,
+            None      => continue,
+        };
+        let mut adjacent = Vec::with_capacity(27);
+        for dl in -1..=1 {
+            for da in -1..=1 {
+                for db in -1..=1 {
+                    adjacent.push([
+                        l.saturating_add(dl),
+                        a.saturating_add(da),
+                        b.saturating_add(db),
+                    ]);
+                }
+            }
+        }
+        // Cubes at the edges of the grid saturate to the same key.
+        adjacent.sort_unstable();
+        adjacent.dedup();
+
+        let (_, color_a) = colors[i];
+        for key in adjacent.iter() {
+            for &j in grid.get(key).into_iter().flatten() {
+                if j <= i { continue; }
+                let (_, color_b) = colors[j];
+                if color_a.rgb_octets() != color_b.rgb_octets()
+                    && delta_e(color_a, color_b) < threshold
+                {
+                    pairs.push((i, j));
+                }
+            }
+        }
+    }
+    pairs.sort_unstable();
+    pairs
+}
@@ -0,0 +1,90 @@
+////////////////////////////////////////////////////////////////////////////////
+// Atma structured color palette
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Module for the `convert` command.
+////////////////////////////////////////////////////////////////////////////////
+
+
+// Internal module imports.
+use crate::format::Format;
+use crate::palette::Journal;
+use crate::palette::Palette;
+
+// External library imports.
+use anyhow::anyhow;
+use anyhow::Context as _;
+
+// Standard library imports.
+use std::fs::OpenOptions;
+use std::io::Write as _;
+use std::path::Path;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// convert_palette
+////////////////////////////////////////////////////////////////////////////////
+/// Converts the palette file at the input path from one format to another,
+/// writing the result to the output path.
+///
+/// The converted data is parsed back and compared with the input palette
+/// before it is written, so that nothing is written if the target format
+/// can't represent the palette exactly.
+///
+/// ### Errors
+///
+/// Returns an error if the palette has a history and the output format is
+/// TOML, which can't represent the nested arrays of history operations.
+pub fn convert_palette(
+    input: &Path,
+    from: Format,
+    output: &Path,
+    to: Format,
+    overwrite: bool)
+    -> Result<(), anyhow::Error>
+{
+    let palette = Palette::read_from_path_with_format(input, from)
+        .with_context(|| format!(
+            "Failed to read palette {}",
+            input.display()))?;
+
+    if to == Format::Toml && palette.history().is_some() {
+        return Err(anyhow!("The palette history can't be represented in \
+            TOML format. Disable it with `atma set history disable` or \
+            convert to another format."));
+    }
+
+    let data = palette.generate(to)?;
+    let converted = Palette::parse(&data[..], to)?;
+    if converted.generate(Format::Ron)? != palette.generate(Format::Ron)? {
+        return Err(anyhow!("The palette can't be represented in {} format \
+            without loss.", to));
+    }
+
+    let mut file = OpenOptions::new()
+        .write(true)
+        .truncate(true)
+        .create(true)
+        .create_new(!overwrite)
+        .open(output)
+        .with_context(|| format!(
+            "Failed to create file {}",
+            output.display()))?;
+    file.write_all(&data[..])
+        .with_context(|| format!(
+            "Failed to write file {}",
+            output.display()))?;
+    // Any journal left by a previous palette at the output path would be
+    // replayed onto the converted palette.
+    Journal::remove_path(&Journal::path_for(output))?;
+
+    println!("Converted {} ({}) to {} ({}).",
+        input.display(),
+        from,
+        output.display(),
+        to);
+    Ok(())
+}
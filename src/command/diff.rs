@@ -0,0 +1,359 @@
+////////////////////////////////////////////////////////////////////////////////
+// Atma structured color palette
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Module for the `diff` command.
+////////////////////////////////////////////////////////////////////////////////
+
+
+// Internal module imports.
+use crate::cell::CellRef;
+use crate::cell::Position;
+use crate::color::Color;
+use crate::palette::BasicPalette;
+use crate::palette::ColorSpace;
+use crate::palette::Expr;
+
+// External library imports.
+use colored::Colorize as _;
+use serde::Serialize;
+
+// Standard library imports.
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// PaletteDiff
+////////////////////////////////////////////////////////////////////////////////
+/// The structural differences between two palettes.
+///
+/// Cells are matched by index, so the palettes are expected to share a
+/// history, such as two revisions of the same palette.
+#[derive(Debug, Clone, Default)]
+#[derive(Serialize)]
+pub struct PaletteDiff {
+    /// The cells which are only in the second palette.
+    pub added: Vec<CellEntry>,
+    /// The cells which are only in the first palette.
+    pub removed: Vec<CellEntry>,
+    /// The cells with different positions.
+    pub moved: Vec<CellMove>,
+    /// The cells with different expressions.
+    pub changed_exprs: Vec<ExprChange>,
+    /// The cells with different evaluated colors.
+    pub changed_colors: Vec<ColorChange>,
+    /// The cells with different names.
+    pub renamed_names: Vec<NameChange>,
+    /// The groups with the same cells under a different name.
+    pub renamed_groups: Vec<GroupRename>,
+}
+
+/// A cell which is only in one of the compared palettes.
+#[derive(Debug, Clone)]
+#[derive(Serialize)]
+pub struct CellEntry {
+    /// The index of the cell.
+    pub index: u32,
+    /// The position of the cell.
+    pub position: Option<Position>,
+    /// The name of the cell.
+    pub name: Option<String>,
+    /// The evaluated color of the cell, as a hex code.
+    pub color: Option<String>,
+}
+
+/// A cell whose position has changed.
+#[derive(Debug, Clone)]
+#[derive(Serialize)]
+pub struct CellMove {
+    /// The index of the cell.
+    pub index: u32,
+    /// The position in the first palette.
+    pub from: Option<Position>,
+    /// The position in the second palette.
+    pub to: Option<Position>,
+}
+
+/// A cell whose expression has changed.
+#[derive(Debug, Clone)]
+#[derive(Serialize)]
+pub struct ExprChange {
+    /// The index of the cell.
+    pub index: u32,
+    /// The expression in the first palette, in RON format.
+    pub from: String,
+    /// The expression in the second palette, in RON format.
+    pub to: String,
+}
+
+/// A cell whose evaluated color has changed.
+#[derive(Debug, Clone)]
+#[derive(Serialize)]
+pub struct ColorChange {
+    /// The index of the cell.
+    pub index: u32,
+    /// The color in the first palette, as a hex code.
+    pub from: Option<String>,
+    /// The color in the second palette, as a hex code.
+    pub to: Option<String>,
+    /// The CIE76 color difference, if both colors are defined.
+    pub delta_e: Option<f32>,
+}
+
+/// A cell whose name has changed.
+#[derive(Debug, Clone)]
+#[derive(Serialize)]
+pub struct NameChange {
+    /// The index of the cell.
+    pub index: u32,
+    /// The name in the first palette.
+    pub from: Option<String>,
+    /// The name in the second palette.
+    pub to: Option<String>,
+}
+
+/// A group which contains the same cells under a different name.
+#[derive(Debug, Clone)]
+#[derive(Serialize)]
+pub struct GroupRename {
+    /// The group name in the first palette.
+    pub from: String,
+    /// The group name in the second palette.
+    pub to: String,
+}
+
+impl PaletteDiff {
+    /// Compares two palettes.
+    pub fn new(a: &BasicPalette, b: &BasicPalette) -> Self {
+        let exprs_a: BTreeMap<u32, &Expr> = a.exprs().collect();
+        let exprs_b: BTreeMap<u32, &Expr> = b.exprs().collect();
+        let mut diff = PaletteDiff::default();
+
+        for idx in exprs_a.keys().filter(|idx| !exprs_b.contains_key(idx)) {
+            diff.removed.push(cell_entry(a, *idx));
+        }
+        for idx in exprs_b.keys().filter(|idx| !exprs_a.contains_key(idx)) {
+            diff.added.push(cell_entry(b, *idx));
+        }
+
+        for (&idx, expr_a) in exprs_a.iter() {
+            let expr_b = match exprs_b.get(&idx) {
+                Some(expr) => expr,
+                None       => continue,
+            };
+            let cell_ref = CellRef::Index(idx);
+
+            let from = a.assigned_position(&cell_ref).copied();
+            let to = b.assigned_position(&cell_ref).copied();
+            if from != to {
+                diff.moved.push(CellMove { index: idx, from, to });
+            }
+
+            if expr_a != expr_b {
+                diff.changed_exprs.push(ExprChange {
+                    index: idx,
+                    from: expr_text(expr_a),
+                    to: expr_text(expr_b),
+                });
+            }
+
+            let from = a.color(&cell_ref).ok().flatten();
+            let to = b.color(&cell_ref).ok().flatten();
+            let (hex_from, hex_to) = (from.map(hex), to.map(hex));
+            if hex_from != hex_to {
+                diff.changed_colors.push(ColorChange {
+                    index: idx,
+                    from: hex_from,
+                    to: hex_to,
+                    delta_e: match (from, to) {
+                        (Some(from), Some(to)) => Some(delta_e(from, to)),
+                        _                      => None,
+                    },
+                });
+            }
+
+            let from = a.assigned_name(&cell_ref).map(|n| n.to_string());
+            let to = b.assigned_name(&cell_ref).map(|n| n.to_string());
+            if from != to {
+                diff.renamed_names.push(NameChange { index: idx, from, to });
+            }
+        }
+
+        let groups_a: BTreeMap<&str, &[u32]> = a.groups().collect();
+        let groups_b: BTreeMap<&str, &[u32]> = b.groups().collect();
+        let mut matched = BTreeSet::new();
+        for (group_a, members_a) in groups_a
+            .iter()
+            .filter(|(g, _)| !groups_b.contains_key(*g))
+        {
+            let renamed = groups_b
+                .iter()
+                .filter(|(g, _)| !groups_a.contains_key(*g))
+                .find(|(g, members_b)| members_a == *members_b &&
+                    !matched.contains(*g));
+            if let Some((group_b, _)) = renamed {
+                let _ = matched.insert(*group_b);
+                diff.renamed_groups.push(GroupRename {
+                    from: group_a.to_string(),
+                    to: group_b.to_string(),
+                });
+            }
+        }
+
+        diff
+    }
+
+    /// Returns true if no differences were found.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() &&
+        self.removed.is_empty() &&
+        self.moved.is_empty() &&
+        self.changed_exprs.is_empty() &&
+        self.changed_colors.is_empty() &&
+        self.renamed_names.is_empty() &&
+        self.renamed_groups.is_empty()
+    }
+
+    /// Prints the differences as a colored report.
+    pub fn print(&self) {
+        if self.is_empty() {
+            println!("No differences.");
+            return;
+        }
+
+        for entry in self.removed.iter() {
+            println!("{} {}",
+                "-".red().bold(),
+                describe_entry(entry).red());
+        }
+        for entry in self.added.iter() {
+            println!("{} {}",
+                "+".green().bold(),
+                describe_entry(entry).green());
+        }
+        for mv in self.moved.iter() {
+            println!("{} :{} moved {} -> {}",
+                "~".yellow().bold(),
+                mv.index,
+                describe_position(mv.from),
+                describe_position(mv.to));
+        }
+        for change in self.changed_exprs.iter() {
+            println!("{} :{} expression changed", "~".yellow().bold(),
+                change.index);
+            println!("    {} {}", "-".red(), change.from.red());
+            println!("    {} {}", "+".green(), change.to.green());
+        }
+        for change in self.changed_colors.iter() {
+            print!("{} :{} color {} -> {}",
+                "~".yellow().bold(),
+                change.index,
+                describe_color(&change.from),
+                describe_color(&change.to));
+            match change.delta_e {
+                Some(delta_e) => println!(" (ΔE {:.2})", delta_e),
+                None          => println!(),
+            }
+        }
+        for change in self.renamed_names.iter() {
+            println!("{} :{} renamed {} -> {}",
+                "~".yellow().bold(),
+                change.index,
+                describe_name(&change.from),
+                describe_name(&change.to));
+        }
+        for rename in self.renamed_groups.iter() {
+            println!("{} group renamed \"{}\" -> \"{}\"",
+                "~".yellow().bold(),
+                rename.from,
+                rename.to);
+        }
+    }
+}
+
+/// Returns the `CellEntry` for the given cell index.
+fn cell_entry(palette: &BasicPalette, index: u32) -> CellEntry {
+    let cell_ref = CellRef::Index(index);
+    CellEntry {
+        index,
+        position: palette.assigned_position(&cell_ref).copied(),
+        name: palette.assigned_name(&cell_ref).map(|n| n.to_string()),
+        color: palette.color(&cell_ref).ok().flatten().map(hex),
+    }
+}
+
+/// Returns the RON text of the given expression.
+fn expr_text(expr: &Expr) -> String {
+    ron::ser::to_string(expr).unwrap_or_else(|_| format!("{:?}", expr))
+}
+
+/// Returns the hex code of the given color.
+fn hex(color: Color) -> String {
+    let [r, g, b] = color.rgb_octets();
+    format!("#{:02X}{:02X}{:02X}", r, g, b)
+}
+
+/// Returns a description of a `CellEntry`.
+fn describe_entry(entry: &CellEntry) -> String {
+    let mut text = format!(":{}", entry.index);
+    if let Some(position) = entry.position {
+        text.push_str(&format!(" {}", position));
+    }
+    if let Some(name) = &entry.name {
+        text.push_str(&format!(" \"{}\"", name));
+    }
+    if let Some(color) = &entry.color {
+        text.push_str(&format!(" {}", color));
+    }
+    text
+}
+
+/// Returns a description of an optional position.
+fn describe_position(position: Option<Position>) -> String {
+    position.map_or_else(|| "(none)".into(), |p| p.to_string())
+}
+
+/// Returns a description of an optional name.
+fn describe_name(name: &Option<String>) -> String {
+    name.as_ref().map_or_else(|| "(none)".into(), |n| format!("\"{}\"", n))
+}
+
+/// Returns a description of an optional hex color.
+fn describe_color(color: &Option<String>) -> String {
+    color.clone().unwrap_or_else(|| "(undefined)".into())
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Color difference
+////////////////////////////////////////////////////////////////////////////////
+
+/// Returns the CIE76 color difference between two colors.
+pub fn delta_e(a: Color, b: Color) -> f32 {
+    let [l_a, a_a, b_a] = lab_components(a);
+    let [l_b, a_b, b_b] = lab_components(b);
+    ((l_a - l_b).powi(2) + (a_a - a_b).powi(2) + (b_a - b_b).powi(2)).sqrt()
+}
+
+/// Returns the CIELAB components of the given color, using a D65 white point.
+pub(in crate) fn lab_components(color: Color) -> [f32; 3] {
+    let [r, g, b] = ColorSpace::LinearRgb.components(color);
+    let x = (0.412_456_4 * r + 0.357_576_1 * g + 0.180_437_5 * b) / 0.950_47;
+    let y = 0.212_672_9 * r + 0.715_152_2 * g + 0.072_175_0 * b;
+    let z = (0.019_333_9 * r + 0.119_192_0 * g + 0.950_304_1 * b) / 1.088_83;
+
+    let f = |t: f32| {
+        const DELTA: f32 = 6.0 / 29.0;
+        if t > DELTA * DELTA * DELTA {
+            t.cbrt()
+        } else {
+            t / (3.0 * DELTA * DELTA) + 4.0 / 29.0
+        }
+    };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
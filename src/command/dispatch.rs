@@ -10,19 +10,39 @@
 
 // Local imports.
 use crate::cell::CellSelector;
+use crate::cell::NOTE_KEY;
+use crate::command::BranchOption;
 use crate::command::ColorDisplay;
 use crate::command::ColorStyle;
 use crate::command::CommandOption;
+use crate::command::check::check;
+use crate::command::check::Severity;
 use crate::command::CommonOptions;
+use crate::command::convert::convert_palette;
+use crate::command::diff::PaletteDiff;
 use crate::command::export_png::write_png;
 use crate::command::ExportOption;
+use crate::command::GetOption;
+use crate::command::history::describe_entry;
+use crate::command::history::print_branches;
+use crate::command::history::print_checkpoints;
+use crate::command::history::print_history;
+use crate::command::HistoryOption;
+use crate::command::import_png::read_png;
+use crate::command::ImportOption;
 use crate::command::list::list;
 use crate::command::new::new_config;
 use crate::command::new::new_palette;
 use crate::command::new::new_settings;
 use crate::command::NewOption;
+use crate::command::PaletteSource;
+use crate::command::RenameOption;
+use crate::command::Script;
 use crate::command::SetOption;
 use crate::command::TextStyle;
+use crate::error::PaletteError;
+use crate::format::Format;
+use crate::palette::InsertExpr;
 use crate::palette::Palette;
 use crate::setup::Config;
 use crate::setup::DEFAULT_CONFIG_PATH;
@@ -196,9 +216,90 @@ pub fn dispatch(
                 settings)
         },
 
+        // Check
+        ////////////////////////////////////////////////////////////////////////
+        Check { strict } => {
+            let pal = palette.ok_or(anyhow!(NO_PALETTE))?;
+            let diagnostics = check(pal.inner(), config);
+            for diagnostic in diagnostics.iter() {
+                println!("{}", diagnostic);
+            }
+
+            let errors = diagnostics
+                .iter()
+                .filter(|d| d.severity == Severity::Error)
+                .count();
+            let warnings = diagnostics.len() - errors;
+            println!("{} error(s), {} warning(s).", errors, warnings);
+            if errors > 0 || (strict && warnings > 0) {
+                return Err(anyhow!("Palette check failed."));
+            }
+            Ok(())
+        },
+
+        // Convert
+        ////////////////////////////////////////////////////////////////////////
+        Convert { input, output, from, to, overwrite } => {
+            let cur_dir = cur_dir.expect("Current directory not determined");
+            let input = cur_dir.join(input);
+            let output = cur_dir.join(output);
+            let from = from.unwrap_or_else(|| Format::for_path(&input));
+            let to = to.unwrap_or_else(|| Format::for_path(&output));
+            convert_palette(&input, from, &output, to, overwrite)
+                .context("convert command failed.")
+        },
+
+        // Diff
+        ////////////////////////////////////////////////////////////////////////
+        Diff { a, b, json } => {
+            let palette = palette.as_deref();
+            let load = |source: PaletteSource| match source {
+                PaletteSource::Path(path) => {
+                    let path = cur_dir
+                        .expect("Current directory not determined")
+                        .join(path);
+                    Palette::read_from_path(&path)
+                        .map(Palette::into_inner)
+                        .with_context(|| format!(
+                            "Failed to read palette {}", path.display()))
+                },
+                PaletteSource::Active => palette
+                    .ok_or(anyhow!(NO_PALETTE))
+                    .map(|pal| pal.inner().clone()),
+                PaletteSource::Revision(count) => {
+                    // Revert any pending changes, as they are not recorded in
+                    // the history.
+                    let mut pal = palette.ok_or(anyhow!(NO_PALETTE))?.clone();
+                    if pal.in_transaction() {
+                        pal.rollback_transaction()?;
+                    }
+                    let mut history = pal
+                        .history()
+                        .cloned()
+                        .ok_or(anyhow!("Palette history is disabled."))?;
+                    let mut inner = pal.inner().clone();
+                    let performed = inner.undo(&mut history, count);
+                    if performed < count {
+                        return Err(anyhow!("Palette history has only {} \
+                            revisions.", performed));
+                    }
+                    Ok(inner)
+                },
+            };
+
+            let diff = PaletteDiff::new(&load(a)?, &load(b)?);
+            if json {
+                println!("{}", serde_json::to_string_pretty(&diff)
+                    .context("Failed to serialize diff")?);
+            } else {
+                diff.print();
+            }
+            Ok(())
+        },
+
         // Insert
         ////////////////////////////////////////////////////////////////////////
-        Insert { exprs, name, at } => {
+        Insert { exprs, name, at, shift } => {
             event!(Level::DEBUG,
                 "\nInsert {{ exprs: {:?}, name: {:?}, at: {:?} }}",
                 exprs, name, at);
@@ -212,7 +313,7 @@ pub fn dispatch(
                 .insert_cursor_behavior
                 .unwrap_or(config.default_insert_cursor_behavior);
 
-            pal.insert_exprs(&exprs[..], name, at, cursor_behavior)
+            pal.insert_exprs(&exprs[..], name, at, cursor_behavior, shift)
                 .context("insert command failed.")?;
 
             pal.set_modified(true);
@@ -241,14 +342,14 @@ pub fn dispatch(
 
         // Move
         ////////////////////////////////////////////////////////////////////////
-        Move { selection, to } => match selection {
+        Move { selection, to, shift } => match selection {
             Some(selection) => {
                 let pal = palette.ok_or(anyhow!(NO_PALETTE))?;
                 let to = to.unwrap_or(config.default_positioning);
                 let cursor_behavior = settings
                     .move_cursor_behavior
                     .unwrap_or(config.default_move_cursor_behavior);
-                pal.move_selection(selection, to, cursor_behavior)?;
+                pal.move_selection(selection, to, cursor_behavior, shift)?;
 
                 pal.set_modified(true);
                 Ok(())
@@ -259,6 +360,112 @@ pub fn dispatch(
             },
         },
 
+        // Duplicate
+        ////////////////////////////////////////////////////////////////////////
+        Duplicate { selection, to, suffix } => {
+            let pal = palette.ok_or(anyhow!(NO_PALETTE))?;
+            let to = to.unwrap_or(config.default_positioning);
+            let cursor_behavior = settings
+                .insert_cursor_behavior
+                .unwrap_or(config.default_insert_cursor_behavior);
+            pal.duplicate_selection(selection, to, &suffix, cursor_behavior)
+                .context("duplicate command failed.")?;
+
+            pal.set_modified(true);
+            Ok(())
+        },
+
+        // Merge
+        ////////////////////////////////////////////////////////////////////////
+        Merge { path, at, prefix, on_conflict } => {
+            let pal = palette.ok_or(anyhow!(NO_PALETTE))?;
+            let path = cur_dir
+                .expect("Current directory not determined")
+                .clone()
+                .join(path);
+            let other = Palette::read_from_path(&path)
+                .context("merge command failed.")?
+                .into_inner();
+            pal.merge_palette(&other, at, prefix.as_deref(), on_conflict)
+                .context("merge command failed.")?;
+
+            pal.set_modified(true);
+            Ok(())
+        },
+
+        // Sort
+        ////////////////////////////////////////////////////////////////////////
+        Sort { selection, by, reverse } => match selection {
+            Some(selection) => {
+                let pal = palette.ok_or(anyhow!(NO_PALETTE))?;
+                pal.sort_selection(selection, by, reverse)
+                    .context("sort command failed.")?;
+
+                pal.set_modified(true);
+                Ok(())
+            },
+            None => {
+                println!("No cell selection; nothing to sort.");
+                Ok(())
+            },
+        },
+
+        // Compact
+        ////////////////////////////////////////////////////////////////////////
+        Compact { selection, positions, indices } => {
+            let pal = palette.ok_or(anyhow!(NO_PALETTE))?;
+            let selection = selection.unwrap_or(CellSelector::All.into());
+            let (positions, indices) = if positions || indices {
+                (positions, indices)
+            } else {
+                (true, true)
+            };
+            pal.compact_selection(selection, positions, indices)
+                .context("compact command failed.")?;
+
+            pal.set_modified(true);
+            Ok(())
+        },
+
+        // Lock
+        ////////////////////////////////////////////////////////////////////////
+        Lock { selection } => {
+            let pal = palette.ok_or(anyhow!(NO_PALETTE))?;
+            pal.set_locked(selection, true)
+                .context("lock command failed.")?;
+
+            pal.set_modified(true);
+            Ok(())
+        },
+
+        // Unlock
+        ////////////////////////////////////////////////////////////////////////
+        Unlock { selection } => {
+            let pal = palette.ok_or(anyhow!(NO_PALETTE))?;
+            pal.set_locked(selection, false)
+                .context("unlock command failed.")?;
+
+            pal.set_modified(true);
+            Ok(())
+        },
+
+        // Rename
+        ////////////////////////////////////////////////////////////////////////
+        Rename { rename_option } => {
+            let pal = palette.ok_or(anyhow!(NO_PALETTE))?;
+            match rename_option {
+                RenameOption::Name { old, new } => pal
+                    .rename_name(&old, new)
+                    .context("rename name command failed.")?,
+                RenameOption::Group { old, new } => pal
+                    .rename_group(&old, new)
+                    .context("rename group command failed.")?,
+            }
+
+            pal.set_modified(true);
+            Ok(())
+        },
+
         // Set
         ////////////////////////////////////////////////////////////////////////
         Set { set_option } => match set_option {
@@ -271,7 +478,25 @@ pub fn dispatch(
                 Ok(())
             },
 
-            SetOption::Group { selection, name, remove } => {
+            SetOption::Group { selection, name, move_group: true, .. } => {
+                let pal = palette.ok_or(anyhow!(NO_PALETTE))?;
+                let name = name.ok_or(anyhow!("No group path provided."))?;
+
+                let selectors: Vec<_> = selection.iter().collect();
+                match &selectors[..] {
+                    [CellSelector::GroupAll(group)]  |
+                    [CellSelector::GroupTree(group)] => {
+                        pal.move_group(group, name)?;
+                    },
+                    _ => return Err(anyhow!(
+                        "Group moves require a single group selector.")),
+                }
+
+                pal.set_modified(true);
+                Ok(())
+            },
+
+            SetOption::Group { selection, name, remove, .. } => {
                 let pal = palette.ok_or(anyhow!(NO_PALETTE))?;
 
                 pal.set_group(name, selection, remove)?;
@@ -280,18 +505,75 @@ pub fn dispatch(
                 Ok(())
             },
 
-            SetOption::Expr { at, expr } => {
+            SetOption::Meta { key, values } => {
                 let pal = palette.ok_or(anyhow!(NO_PALETTE))?;
 
-                let mut exprs = expr.exprs(pal.inner())?;
-                if exprs.len() > 1 {
-                    return Err(anyhow!("The `set` command does not support \
-                        ramp expressions."));
-                }
-                let expr = exprs.pop()
-                    .ok_or(anyhow!("No expression to set."))?;
+                pal.set_metadata(&key, values)?;
+
+                pal.set_modified(true);
+                Ok(())
+            },
+
+            SetOption::Constraint { key, value } => {
+                let pal = palette.ok_or(anyhow!(NO_PALETTE))?;
+
+                pal.set_constraint(&key, value.as_deref())?;
+
+                pal.set_modified(true);
+                Ok(())
+            },
+
+            SetOption::Library { library, path } => {
+                let pal = palette.ok_or(anyhow!(NO_PALETTE))?;
+
+                pal.set_library_path(library, path)?;
+
+                pal.set_modified(true);
+                Ok(())
+            },
+
+            SetOption::Note { selection, note } => {
+                let pal = palette.ok_or(anyhow!(NO_PALETTE))?;
+
+                pal.set_cell_metadata(selection, NOTE_KEY, note)?;
 
-                pal.set_expr(at, expr)?;
+                pal.set_modified(true);
+                Ok(())
+            },
+
+            SetOption::CellMeta { selection, key, value } => {
+                let pal = palette.ok_or(anyhow!(NO_PALETTE))?;
+
+                pal.set_cell_metadata(selection, key, value)?;
+
+                pal.set_modified(true);
+                Ok(())
+            },
+
+            SetOption::Expr { at, expr, variant } => {
+                let pal = palette.ok_or(anyhow!(NO_PALETTE))?;
+
+                let expr = match expr {
+                    Some(expr) => {
+                        let mut exprs = expr.exprs(pal.inner())?;
+                        if exprs.len() > 1 {
+                            return Err(anyhow!("The `set` command does not \
+                                support ramp expressions."));
+                        }
+                        Some(exprs.pop()
+                            .ok_or(anyhow!("No expression to set."))?)
+                    },
+                    None => None,
+                };
+
+                match (variant, expr) {
+                    (Some(variant), expr) => {
+                        pal.set_variant_expr(variant, at, expr)?;
+                    },
+                    (None, Some(expr)) => pal.set_expr(at, expr)?,
+                    (None, None) => return Err(
+                        anyhow!("No expression to set.")),
+                }
                 pal.set_modified(true);
                 Ok(())
             },
@@ -342,6 +624,53 @@ pub fn dispatch(
             },
         },
 
+        // Get
+        ////////////////////////////////////////////////////////////////////////
+        Get { get_option } => match get_option {
+            GetOption::Meta { key } => {
+                let pal = palette.ok_or(anyhow!(NO_PALETTE))?;
+                match key {
+                    Some(key) => match pal.metadata().get(&key) {
+                        Some(value) => println!("{}", value),
+                        None        => println!("Metadata field '{}' is not \
+                            set.", key),
+                    },
+                    None => for (key, value) in pal.metadata().entries() {
+                        println!("{}: {}", key, value);
+                    },
+                }
+                Ok(())
+            },
+
+            GetOption::Constraints { key } => {
+                let pal = palette.ok_or(anyhow!(NO_PALETTE))?;
+                let constraints = pal.inner().constraints();
+                match key {
+                    Some(key) => match constraints.get(&key) {
+                        Some(value) => println!("{}", value),
+                        None        => println!("Constraint '{}' is not \
+                            set.", key),
+                    },
+                    None => for (key, value) in constraints.entries() {
+                        println!("{}: {}", key, value);
+                    },
+                }
+                Ok(())
+            },
+
+            GetOption::Variants => {
+                let pal = palette.ok_or(anyhow!(NO_PALETTE))?;
+                let active = pal.inner().active_variant();
+                for (variant, count) in pal.inner().variants() {
+                    println!("{}{} ({} overrides)",
+                        variant,
+                        if Some(variant) == active { " [active]" } else { "" },
+                        count);
+                }
+                Ok(())
+            },
+        },
+
         // Undo
         ////////////////////////////////////////////////////////////////////////
         Undo { count } => {
@@ -351,6 +680,11 @@ pub fn dispatch(
                 println!("0 undo operations performed.");
                 return Ok(());
             };
+            if pal.in_transaction() {
+                return Err(anyhow!(PaletteError::TransactionPending)
+                    .context("undo command failed."));
+            }
+            let before = pal.history().map_or(0, |h| h.cursor());
             let performed = pal.undo(count);
             match performed {
                 0 => {
@@ -360,7 +694,15 @@ pub fn dispatch(
                 1 => println!("Undo operation completed."),
                 _ => println!("{} undo operations performed.", performed),
             }
-            pal.write_to_load_path()
+            if let Some(history) = pal.history() {
+                let mut id = before;
+                while id != history.cursor() {
+                    println!("Reverted {}",
+                        describe_entry(history.entry_info(id)));
+                    id = history.parent(id).unwrap_or(0);
+                }
+            }
+            pal.write_changes_to_load_path()
                 .map(|_| ())
                 .context("Failed to write palette")
         },
@@ -374,6 +716,11 @@ pub fn dispatch(
                 println!("0 redo operations performed.");
                 return Ok(());
             };
+            if pal.in_transaction() {
+                return Err(anyhow!(PaletteError::TransactionPending)
+                    .context("redo command failed."));
+            }
+            let before = pal.history().map_or(0, |h| h.cursor());
             let performed = pal.redo(count);
             match performed {
                 0 => {
@@ -383,14 +730,213 @@ pub fn dispatch(
                 1 => println!("Redo operation completed."),
                 _ => println!("{} redo operations performed.", performed),
             }
-            pal.write_to_load_path()
+            if let Some(history) = pal.history() {
+                let mut reapplied = Vec::with_capacity(performed);
+                let mut id = history.cursor();
+                while id != before {
+                    reapplied.push(id);
+                    id = history.parent(id).unwrap_or(0);
+                }
+                for id in reapplied.into_iter().rev() {
+                    println!("Reapplied {}",
+                        describe_entry(history.entry_info(id)));
+                }
+            }
+            pal.write_changes_to_load_path()
                 .map(|_| ())
                 .context("Failed to write palette")
         },
 
+        // History
+        ////////////////////////////////////////////////////////////////////////
+        History { history_option } => {
+            let pal = palette.ok_or(anyhow!(NO_PALETTE))?;
+            match history_option.unwrap_or(HistoryOption::List) {
+                HistoryOption::List => {
+                    let history = pal.history().ok_or(anyhow!(
+                        "History is disabled for this palette."))?;
+                    print_history(history);
+                    if let Some(transaction) = pal.transaction() {
+                        println!("Pending transaction: {}",
+                            transaction.commands().join("; "));
+                    }
+                    return Ok(());
+                },
+
+                HistoryOption::Squash { range } => {
+                    let removed = pal.squash_history(range.from, range.to)
+                        .context("history squash command failed.")?;
+                    println!("{} history entries merged.", removed + 1);
+                },
+
+                HistoryOption::Prune => {
+                    let removed = pal.prune_history()
+                        .context("history prune command failed.")?;
+                    println!("{} history entries removed.", removed);
+                },
+            }
+            pal.set_modified(true);
+            Ok(())
+        },
+
+        // Checkpoint
+        ////////////////////////////////////////////////////////////////////////
+        Checkpoint { name } => {
+            let pal = palette.ok_or(anyhow!(NO_PALETTE))?;
+            pal.set_checkpoint(name)
+                .context("checkpoint command failed.")?;
+            pal.set_modified(true);
+            Ok(())
+        },
+
+        // Restore
+        ////////////////////////////////////////////////////////////////////////
+        Restore { name } => {
+            let pal = palette.ok_or(anyhow!(NO_PALETTE))?;
+            pal.restore_checkpoint(&name)
+                .context("restore command failed.")?;
+            println!("Restored checkpoint \"{}\".", name);
+            pal.set_modified(true);
+            Ok(())
+        },
+
+        // Checkpoints
+        ////////////////////////////////////////////////////////////////////////
+        Checkpoints => {
+            let pal = palette.ok_or(anyhow!(NO_PALETTE))?;
+            let history = pal.history()
+                .ok_or(anyhow!("History is disabled for this palette."))?;
+            print_checkpoints(history);
+            Ok(())
+        },
+
+        // Branch
+        ////////////////////////////////////////////////////////////////////////
+        Branch { branch_option } => {
+            let pal = palette.ok_or(anyhow!(NO_PALETTE))?;
+            match branch_option.unwrap_or(BranchOption::List) {
+                BranchOption::List => {
+                    let history = pal.history().ok_or(anyhow!(
+                        "History is disabled for this palette."))?;
+                    print_branches(history);
+                    return Ok(());
+                },
+
+                BranchOption::Switch { branch } => {
+                    pal.switch_branch(branch)
+                        .context("branch switch command failed.")?;
+                    println!("Switched to history branch {}.", branch);
+                },
+
+                BranchOption::Prune { branch, all } => {
+                    let removed = match branch {
+                        Some(branch) if !all => pal.prune_branch(branch),
+                        _ => pal.prune_inactive_branches(),
+                    }.context("branch prune command failed.")?;
+                    println!("{} history entries removed.", removed);
+                },
+            }
+            pal.set_modified(true);
+            Ok(())
+        },
+
+        // Begin
+        ////////////////////////////////////////////////////////////////////////
+        Begin => {
+            let pal = palette.ok_or(anyhow!(NO_PALETTE))?;
+            pal.begin_transaction(common.label.clone())
+                .context("begin command failed.")?;
+            pal.set_modified(true);
+            Ok(())
+        },
+
+        // Commit
+        ////////////////////////////////////////////////////////////////////////
+        Commit => {
+            let pal = palette.ok_or(anyhow!(NO_PALETTE))?;
+            pal.commit_transaction()
+                .context("commit command failed.")?;
+            pal.set_modified(true);
+            Ok(())
+        },
+
+        // Rollback
+        ////////////////////////////////////////////////////////////////////////
+        Rollback => {
+            let pal = palette.ok_or(anyhow!(NO_PALETTE))?;
+            pal.rollback_transaction()
+                .context("rollback command failed.")?;
+            pal.set_modified(true);
+            Ok(())
+        },
+
+        // Run
+        ////////////////////////////////////////////////////////////////////////
+        Run { path } => {
+            let pal = palette.ok_or(anyhow!(NO_PALETTE))?;
+            let path = normalize_path(
+                cur_dir.expect("Current directory not determined"),
+                path);
+            let script = Script::read_from_path(&path)
+                .with_context(|| format!(
+                    "Failed to read script {}", path.display()))?;
+            script.execute(pal, common, config, settings)
+                .context("run command failed.")?;
+            pal.set_modified(true);
+            Ok(())
+        },
+
         // Import
         ////////////////////////////////////////////////////////////////////////
-        Import => unimplemented!(),
+        Import { import_option } => {
+            let pal = palette.ok_or(anyhow!(NO_PALETTE))?;
+            match import_option {
+                ImportOption::Png {
+                    path,
+                    name,
+                    at,
+                    colors,
+                    method,
+                    color_space,
+                } => {
+                    let path = cur_dir
+                        .expect("Current directory not determined")
+                        .clone()
+                        .join(path);
+                    let exprs: Vec<InsertExpr> = read_png(
+                            &path,
+                            colors,
+                            method,
+                            color_space)
+                        .context("Command 'import png' failed")?
+                        .into_iter()
+                        .map(InsertExpr::Color)
+                        .collect();
+                    if exprs.is_empty() {
+                        println!("No colors to import.");
+                        return Ok(());
+                    }
+                    let at = at.unwrap_or(config.default_positioning);
+                    let cursor_behavior = settings
+                        .insert_cursor_behavior
+                        .unwrap_or(config.default_insert_cursor_behavior);
+
+                    pal.insert_exprs(
+                            &exprs[..],
+                            name,
+                            at,
+                            cursor_behavior,
+                            None)
+                        .context("import command failed.")?;
+                    println!("{} colors imported from {}",
+                        exprs.len(),
+                        path.display());
+
+                    pal.set_modified(true);
+                    Ok(())
+                },
+            }
+        },
 
         // Export
         ////////////////////////////////////////////////////////////////////////
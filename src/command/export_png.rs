@@ -56,7 +56,44 @@ pub fn write_png<'a>(
     encoder.set_depth(png::BitDepth::Eight);
     encoder.set_palette(pal_data);
     let mut writer = encoder.write_header()?;
+
+    // Write palette metadata as tEXt chunks.
+    let meta = palette.metadata();
+    let text_entries = [
+        ("Title", meta.get("title")),
+        ("Author", meta.get("authors")),
+        ("Copyright", meta.get("license")),
+        ("Description", meta.get("description")),
+    ];
+    for (keyword, value) in text_entries.iter() {
+        if let Some(value) = value {
+            write_text_chunk(&mut writer, keyword, value)?;
+        }
+    }
+    for (keyword, value) in meta.extra.iter() {
+        write_text_chunk(&mut writer, keyword, value)?;
+    }
+
     writer.write_image_data(&[0])?;
     println!("Palette exported to {}", path.display());
     Ok(())
 }
+
+/// Writes a PNG tEXt chunk with the given keyword and text. Keywords are
+/// truncated to the 79 byte limit imposed by the PNG specification.
+#[cfg(feature = "png")]
+fn write_text_chunk<W>(
+    writer: &mut png::Writer<W>,
+    keyword: &str,
+    text: &str)
+    -> Result<(), anyhow::Error>
+    where W: std::io::Write
+{
+    let keyword = keyword.as_bytes();
+    let mut data = Vec::with_capacity(keyword.len().min(79) + 1 + text.len());
+    data.extend(&keyword[..keyword.len().min(79)]);
+    data.push(0);
+    data.extend(text.as_bytes());
+    writer.write_chunk(*b"tEXt", &data[..])?;
+    Ok(())
+}
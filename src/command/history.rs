@@ -0,0 +1,125 @@
+////////////////////////////////////////////////////////////////////////////////
+// Atma structured color palette
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Module for the `history` command.
+////////////////////////////////////////////////////////////////////////////////
+
+
+// Internal module imports.
+use crate::palette::EntryInfo;
+use crate::palette::History;
+use crate::utility::format_timestamp;
+
+// External library imports.
+use colored::Colorize as _;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// print_history
+////////////////////////////////////////////////////////////////////////////////
+/// Prints the entries of the given history, marking the cursor position.
+/// Entries which are not applied to the palette are dimmed, and entries which
+/// start a new branch show the entry they follow.
+pub fn print_history(history: &History) {
+    if history.is_empty() {
+        println!("No history entries recorded.");
+        return;
+    }
+
+    print_state(0, "(initial state)", history.cursor() == 0, false);
+    for (id, info) in history.entry_infos() {
+        let mut text = describe_entry(info);
+        match history.parent(id) {
+            Some(parent) if parent + 1 != id => {
+                text.push_str(&format!(" (follows {})", parent));
+            },
+            _ => (),
+        }
+        print_state(
+            id,
+            &text,
+            history.cursor() == id,
+            !history.is_applied(id));
+    }
+}
+
+/// Prints a single history state line.
+fn print_state(number: usize, text: &str, current: bool, undone: bool) {
+    let marker = if current { "@".green().bold() } else { " ".normal() };
+    let line = format!("{:>4}  {}", number, text);
+    if undone {
+        println!("{} {}", marker, line.dimmed());
+    } else {
+        println!("{} {}", marker, line);
+    }
+}
+
+/// Returns a description of a history entry.
+pub fn describe_entry(info: &EntryInfo) -> String {
+    let mut text = match info.timestamp {
+        Some(timestamp) => format_timestamp(timestamp),
+        None            => "(unknown time)".into(),
+    };
+    text.push_str("  ");
+    text.push_str(info.command.as_deref().unwrap_or("(unknown command)"));
+    if let Some(label) = &info.label {
+        text.push_str(&format!(" [{}]", label));
+    }
+    text
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// print_checkpoints
+////////////////////////////////////////////////////////////////////////////////
+/// Prints the checkpoints of the given history, along with the entries they
+/// refer to.
+pub fn print_checkpoints(history: &History) {
+    if history.checkpoints().next().is_none() {
+        println!("No checkpoints recorded.");
+        return;
+    }
+
+    for (name, checkpoint) in history.checkpoints() {
+        let current = !checkpoint.discarded &&
+            checkpoint.position == history.cursor();
+        let marker = if current { "@".green().bold() } else { " ".normal() };
+        let text = if checkpoint.discarded {
+            "(discarded)".to_string()
+        } else if checkpoint.position == 0 {
+            "(initial state)".to_string()
+        } else {
+            describe_entry(history.entry_info(checkpoint.position))
+        };
+        let line = format!("{}  {:>4}  {}", name, checkpoint.position, text);
+        if checkpoint.discarded {
+            println!("{} {}", marker, line.dimmed());
+        } else {
+            println!("{} {}", marker, line);
+        }
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// print_branches
+////////////////////////////////////////////////////////////////////////////////
+/// Prints the branches of the given history, identified by their tip entries,
+/// marking the current branch.
+pub fn print_branches(history: &History) {
+    let branches = history.branches();
+    if branches.is_empty() {
+        println!("No history branches recorded.");
+        return;
+    }
+
+    let current = history.current_branch();
+    for branch in branches {
+        let text = describe_entry(history.entry_info(branch));
+        print_state(branch, &text, branch == current, branch != current);
+    }
+}
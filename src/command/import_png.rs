@@ -0,0 +1,259 @@
+////////////////////////////////////////////////////////////////////////////////
+// Atma structured color palette
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Module for the `import png` command.
+////////////////////////////////////////////////////////////////////////////////
+
+// Local imports.
+use crate::color::Color;
+#[cfg(feature = "png")]
+use crate::color::Rgb;
+use crate::command::QuantizeMethod;
+use crate::palette::ColorSpace;
+
+// External library imports.
+use anyhow::anyhow;
+
+// Standard library imports.
+#[cfg(feature = "png")]
+use std::collections::BTreeSet;
+use std::path::Path;
+
+
+/// The maximum number of refinement passes used by k-means quantization.
+#[cfg(any(feature = "png", test))]
+const K_MEANS_MAX_ITERATIONS: usize = 32;
+
+
+/// Reads the palette colors from the PNG file at the given path.
+///
+/// # Errors
+///
+/// This function will always fail because the "png" feature is not available.
+#[cfg(not(feature = "png"))]
+pub fn read_png(
+    _path: &Path,
+    _colors: usize,
+    _method: QuantizeMethod,
+    _color_space: ColorSpace)
+    -> Result<Vec<Color>, anyhow::Error>
+{
+    Err(anyhow!("Import using PNG format is unsupported."))
+}
+
+/// Reads the palette colors from the PNG file at the given path.
+///
+/// Indexed images will return the colors of their PLTE chunk in order.
+/// Truecolor and grayscale images will be reduced to at most `colors` colors
+/// using the given `QuantizeMethod` in the given `ColorSpace`. Fully
+/// transparent pixels are ignored.
+#[cfg(feature = "png")]
+pub fn read_png(
+    path: &Path,
+    colors: usize,
+    method: QuantizeMethod,
+    color_space: ColorSpace)
+    -> Result<Vec<Color>, anyhow::Error>
+{
+    use png::ColorType;
+
+    let file = std::fs::File::open(path)?;
+    let mut decoder = png::Decoder::new(std::io::BufReader::new(file));
+    decoder.set_transformations(
+        png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let (output_info, mut reader) = decoder.read_info()?;
+
+    // Indexed images carry their palette directly.
+    if reader.info().color_type == ColorType::Indexed {
+        let plte = reader.info().palette
+            .as_ref()
+            .ok_or(anyhow!("Indexed PNG file has no PLTE chunk."))?;
+        return Ok(plte
+            .chunks_exact(3)
+            .map(|c| Color::from(Rgb::from([c[0], c[1], c[2]])))
+            .collect());
+    }
+
+    let mut buf = vec![0; reader.output_buffer_size()];
+    reader.next_frame(&mut buf)?;
+
+    let mut octets: Vec<[u8; 3]> = Vec::with_capacity(
+        (output_info.width * output_info.height) as usize);
+    match output_info.color_type {
+        ColorType::Grayscale => for p in buf.chunks_exact(1) {
+            octets.push([p[0], p[0], p[0]]);
+        },
+        ColorType::GrayscaleAlpha => for p in buf.chunks_exact(2) {
+            if p[1] != 0 { octets.push([p[0], p[0], p[0]]); }
+        },
+        ColorType::RGB => for p in buf.chunks_exact(3) {
+            octets.push([p[0], p[1], p[2]]);
+        },
+        ColorType::RGBA => for p in buf.chunks_exact(4) {
+            if p[3] != 0 { octets.push([p[0], p[1], p[2]]); }
+        },
+        ColorType::Indexed => unreachable!(),
+    }
+
+    // Return the exact colors if there are few enough of them.
+    let unique: BTreeSet<[u8; 3]> = octets.iter().cloned().collect();
+    if unique.len() <= colors {
+        return Ok(unique
+            .into_iter()
+            .map(|c| Color::from(Rgb::from(c)))
+            .collect());
+    }
+
+    let points: Vec<[f32; 3]> = octets
+        .into_iter()
+        .map(|c| color_space.components(Rgb::from(c)))
+        .collect();
+
+    let centers = match method {
+        QuantizeMethod::MedianCut => median_cut(&points[..], colors),
+        QuantizeMethod::KMeans    => k_means(&points[..], colors),
+    };
+
+    Ok(centers
+        .into_iter()
+        .map(|c| color_space.color_from_components(c))
+        .collect())
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Quantization
+////////////////////////////////////////////////////////////////////////////////
+
+/// Reduces the given points to at most `count` representative points by
+/// recursively splitting the box with the widest channel range at its median.
+/// Identical representative points are only returned once.
+#[cfg(any(feature = "png", test))]
+pub(in crate) fn median_cut(points: &[[f32; 3]], count: usize)
+    -> Vec<[f32; 3]>
+{
+    if points.is_empty() || count == 0 { return Vec::new(); }
+
+    let mut boxes: Vec<Vec<[f32; 3]>> = vec![points.to_vec()];
+    while boxes.len() < count {
+        // Find the box and channel with the largest range.
+        let widest = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| b.len() > 1)
+            .map(|(i, b)| {
+                let (channel, range) = widest_channel(&b[..]);
+                (i, channel, range)
+            })
+            .max_by(|a, b| a.2.partial_cmp(&b.2)
+                .unwrap_or(std::cmp::Ordering::Equal));
+
+        let (i, channel) = match widest {
+            Some((i, channel, range)) if range > 0.0 => (i, channel),
+            _ => break,
+        };
+
+        let mut b = boxes.swap_remove(i);
+        b.sort_by(|x, y| x[channel].partial_cmp(&y[channel])
+            .unwrap_or(std::cmp::Ordering::Equal));
+        let upper = b.split_off(b.len() / 2);
+        boxes.push(b);
+        boxes.push(upper);
+    }
+
+    let mut centers: Vec<[f32; 3]> = Vec::with_capacity(boxes.len());
+    for center in boxes.iter().map(|b| mean(&b[..])) {
+        if !centers.contains(&center) { centers.push(center); }
+    }
+    centers
+}
+
+/// Reduces the given points to at most `count` representative points using
+/// k-means clustering seeded by the median cut.
+#[cfg(any(feature = "png", test))]
+pub(in crate) fn k_means(points: &[[f32; 3]], count: usize)
+    -> Vec<[f32; 3]>
+{
+    let mut centers = median_cut(points, count);
+    if centers.is_empty() { return centers; }
+
+    let mut assignments = vec![0usize; points.len()];
+    for _ in 0..K_MEANS_MAX_ITERATIONS {
+        // Assign each point to its nearest center.
+        let mut changed = false;
+        for (p, a) in points.iter().zip(assignments.iter_mut()) {
+            let nearest = centers
+                .iter()
+                .enumerate()
+                .map(|(i, c)| (i, distance_squared(p, c)))
+                .min_by(|a, b| a.1.partial_cmp(&b.1)
+                    .unwrap_or(std::cmp::Ordering::Equal))
+                .map(|(i, _)| i)
+                .unwrap_or(0);
+            if *a != nearest {
+                *a = nearest;
+                changed = true;
+            }
+        }
+        if !changed { break; }
+
+        // Move each center to the mean of its assigned points. Centers with no
+        // assigned points are left in place.
+        let mut sums = vec![([0.0f32; 3], 0usize); centers.len()];
+        for (p, a) in points.iter().zip(assignments.iter()) {
+            let (sum, n) = &mut sums[*a];
+            sum[0] += p[0];
+            sum[1] += p[1];
+            sum[2] += p[2];
+            *n += 1;
+        }
+        for (c, (sum, n)) in centers.iter_mut().zip(sums.into_iter()) {
+            if n > 0 {
+                let n = n as f32;
+                *c = [sum[0] / n, sum[1] / n, sum[2] / n];
+            }
+        }
+    }
+
+    centers
+}
+
+/// Returns the index and range of the channel with the largest range.
+#[cfg(any(feature = "png", test))]
+fn widest_channel(points: &[[f32; 3]]) -> (usize, f32) {
+    let mut low = [f32::MAX; 3];
+    let mut high = [f32::MIN; 3];
+    for p in points {
+        for i in 0..3 {
+            low[i] = low[i].min(p[i]);
+            high[i] = high[i].max(p[i]);
+        }
+    }
+    (0..3)
+        .map(|i| (i, high[i] - low[i]))
+        .fold((0, 0.0), |a, b| if b.1 > a.1 { b } else { a })
+}
+
+/// Returns the mean of the given points.
+#[cfg(any(feature = "png", test))]
+fn mean(points: &[[f32; 3]]) -> [f32; 3] {
+    let n = points.len() as f32;
+    let mut sum = [0.0f32; 3];
+    for p in points {
+        sum[0] += p[0];
+        sum[1] += p[1];
+        sum[2] += p[2];
+    }
+    [sum[0] / n, sum[1] / n, sum[2] / n]
+}
+
+/// Returns the squared euclidean distance between the given points.
+#[cfg(any(feature = "png", test))]
+fn distance_squared(a: &[f32; 3], b: &[f32; 3]) -> f32 {
+    let d = [a[0] - b[0], a[1] - b[1], a[2] - b[2]];
+    d[0] * d[0] + d[1] * d[1] + d[2] * d[2]
+}
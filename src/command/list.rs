@@ -22,8 +22,10 @@ use crate::error::PaletteError;
 use crate::cell::CellSelection;
 use crate::cell::CellSelector;
 use crate::cell::CellRef;
+use crate::cell::NOTE_KEY;
 use crate::cell::Position;
 use crate::cell::PositionSelector;
+use crate::cell::REF_GROUP_PATH_TOKEN;
 
 // External module imports.
 use colored::Colorize as _;
@@ -31,6 +33,8 @@ use tracing::Level;
 use tracing::span;
 use tracing::event;
 
+// Standard library imports.
+use std::collections::BTreeSet;
 
 
 /// Executes the `atma list` command.
@@ -62,6 +66,20 @@ pub fn list(
             config,
             settings),
         ListMode::Lines => list_lines(
+            palette,
+            selection,
+            color_display,
+            false,
+            config,
+            settings),
+        ListMode::Detail => list_lines(
+            palette,
+            selection,
+            color_display,
+            true,
+            config,
+            settings),
+        ListMode::Groups => list_groups(
             palette,
             selection,
             color_display,
@@ -74,11 +92,13 @@ pub fn list(
 ////////////////////////////////////////////////////////////////////////////////
 // list_lines
 ////////////////////////////////////////////////////////////////////////////////
-/// Prints palette information.
+/// Prints palette information, one cell per line. If `detail` is true, each
+/// cell's notes and metadata are printed on the following lines.
 fn list_lines<'a>(
     palette: &Palette,
     selection: Option<CellSelection<'a>>,
     color_display: ColorDisplay,
+    detail: bool,
     _config: &Config,
     _settings: &mut Settings)
     -> Result<(), anyhow::Error>
@@ -114,6 +134,9 @@ fn list_lines<'a>(
             {
                 print!(" \"{}\"", group);
             }
+            if palette.inner().is_locked(&CellRef::Index(idx)) {
+                print!(" {}", "locked".dimmed());
+            }
             
         } else {
             print!("{:4X} ", idx);
@@ -121,6 +144,93 @@ fn list_lines<'a>(
 
         }
         println!();
+
+        if detail {
+            if let Some(entries) = palette.inner()
+                .assigned_metadata(&CellRef::Index(idx))
+            {
+                if let Some(note) = entries.get(NOTE_KEY) {
+                    println!("     {}", note.italic());
+                }
+                for (key, value) in entries.iter() {
+                    if key.as_ref() != NOTE_KEY {
+                        println!("     {}={}", key, value);
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// list_groups
+////////////////////////////////////////////////////////////////////////////////
+/// Prints the palette's group hierarchy, with the selected cells of each group
+/// listed beneath it.
+fn list_groups<'a>(
+    palette: &Palette,
+    selection: Option<CellSelection<'a>>,
+    color_display: ColorDisplay,
+    _config: &Config,
+    _settings: &mut Settings)
+    -> Result<(), anyhow::Error>
+{
+    let span = span!(Level::DEBUG, "list_groups");
+    let _enter = span.enter();
+
+    let selection = selection.unwrap_or(CellSelector::All.into());
+    let index_selection: BTreeSet<u32> = selection
+        .resolve(palette.inner())
+        .into_iter()
+        .collect();
+
+    // Sort by path segments so that nested groups follow their parents.
+    let mut groups: Vec<(Vec<&str>, Vec<u32>)> = palette.inner()
+        .groups()
+        .map(|(path, elems)| (
+            path.split(REF_GROUP_PATH_TOKEN).collect(),
+            elems
+                .iter()
+                .copied()
+                .filter(|idx| index_selection.contains(idx))
+                .collect::<Vec<u32>>()))
+        .filter(|(_, elems)| !elems.is_empty())
+        .collect();
+    groups.sort();
+
+    let mut printed: Vec<&str> = Vec::new();
+    for (segments, elems) in groups {
+        // Print any parent groups that haven't been printed yet.
+        let common = printed
+            .iter()
+            .zip(segments.iter())
+            .take_while(|(a, b)| a == b)
+            .count();
+        for (depth, segment) in segments.iter().enumerate().skip(common) {
+            println!("{:width$}{}{}",
+                "",
+                segment.bold(),
+                REF_GROUP_PATH_TOKEN,
+                width=depth * 2);
+        }
+        printed = segments;
+
+        let indent = printed.len() * 2;
+        for idx in elems {
+            print!("{:width$}{:4X} ", "", idx, width=indent);
+            match palette.inner().color(&CellRef::Index(idx)) {
+                Ok(Some(c)) => color_display.print(c),
+                _           => color_display.print_invalid(),
+            }
+            if let Some(name) = palette.inner()
+                .assigned_name(&CellRef::Index(idx))
+            {
+                print!(" \"{}\"", name);
+            }
+            println!();
+        }
     }
     Ok(())
 }
@@ -186,8 +296,15 @@ fn list_grid<'a>(
     event!(Level::DEBUG, "columns: {}", columns);
     event!(Level::DEBUG, "right_gutter_width: {}", right_gutter_width);
 
-    let max_column = corner_position.column.saturating_add(columns - 1);
+    // Clip the grid to the palette's constraints, marking the boundaries
+    // where they are visible.
+    let constraints = palette.inner().constraints();
+    let mut max_column = corner_position.column.saturating_add(columns - 1);
     let mut max_line = corner_position.line.saturating_add(size.1 - 2);
+    let column_bound = match constraints.max_column {
+        Some(bound) if bound <= max_column => { max_column = bound; true },
+        _ => false,
+    };
     const MAX_SKIP: u16 = 20;
     event!(Level::DEBUG, "max_column: {}, max_line: {}", max_column, max_line);
 
@@ -198,6 +315,12 @@ fn list_grid<'a>(
     let page_color = colored::Color::TrueColor { r: 0x11, g: 0xDD, b: 0x22 };
     let rule_color = colored::Color::TrueColor { r: 0x77, g: 0x77, b: 0x77 };
 
+    if !constraints.contains(&corner_position) {
+        println!("{}", format!("{} is outside of the palette constraints.",
+            corner_position).color(rule_color));
+        return Ok(());
+    }
+
     // Print header line.
     if print_column_rule {
         let page_selector = PositionSelector {
@@ -219,6 +342,7 @@ fn list_grid<'a>(
                 print!("{:<width$}", "⭣".color(rule_color), width=w);
             }
         }
+        if column_bound { print!("{}", "│".color(rule_color)); }
         if print_line_names {
             if let Some(name) = palette.inner().get_name(&page_selector) {
                 let w = right_gutter_width as usize - 1;
@@ -233,6 +357,14 @@ fn list_grid<'a>(
     }
 
 
+    let line_bound = match constraints.max_line {
+        Some(bound) if bound < max_line => {
+            max_line = bound.saturating_add(1);
+            true
+        },
+        _ => false,
+    };
+
     while line < max_line {
         let mut print_line = false;
         for column in corner_position.column..=max_column {
@@ -284,6 +416,7 @@ fn list_grid<'a>(
                     Err(_)      => color_display.print_empty(),
                 }
             }
+            if column_bound { print!("{}", "│".color(rule_color)); }
 
             if print_line_names {
                 if let Some(name) = palette.inner().get_name(&line_selector) {
@@ -305,5 +438,13 @@ fn list_grid<'a>(
         }
     }
     if skipped > 0 { println!("\t..."); }
+    if line_bound && line >= max_line {
+        let w = (max_column - corner_position.column + 1) as usize
+            * color_display.width() as usize;
+        println!("{:width$} {}",
+            "",
+            "─".repeat(w).color(rule_color),
+            width=left_gutter_width as usize);
+    }
     Ok(())
 }
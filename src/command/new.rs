@@ -43,6 +43,7 @@ fn create_new_palette(
 
     if !no_history { palette = palette.with_history(); }
     if let Some(name) = name {
+        palette.metadata_mut().title = Some(name.clone());
         let _ = palette.inner_mut().assign_name(name, PositionSelector::ALL)?;
     }
     Ok(palette)
@@ -76,6 +77,9 @@ pub fn new_palette(
 
     if let Some(script_path) = script_path {
         let script = Script::read_from_path(script_path)?;
+        palette.set_history_entry_info(
+            Some(format!("new palette --from-script {}", script.source())),
+            common.label.clone());
         if config.new_from_script_history {
             let _ = script.execute(&mut palette, common, config, settings)?;
         } else {
@@ -15,14 +15,22 @@ use crate::cell::CellSelection;
 use crate::cell::Position;
 use crate::cell::PositionSelector;
 use crate::command::ColorStyle;
+use crate::command::ConflictPolicy;
 use crate::command::CursorBehavior;
+use crate::command::EntryRange;
 use crate::command::GutterStyle;
 use crate::command::HistorySetOption;
 use crate::command::LineStyle;
 use crate::command::ListMode;
+use crate::command::PaletteSource;
 use crate::command::Positioning;
+use crate::command::QuantizeMethod;
 use crate::command::RuleStyle;
+use crate::command::ShiftMode;
+use crate::command::SortKey;
 use crate::command::TextStyle;
+use crate::format::Format;
+use crate::palette::ColorSpace;
 use crate::palette::InsertExpr;
 
 // External library imports.
@@ -84,6 +92,18 @@ pub struct CommonOptions {
     #[structopt(short = "q", long = "quiet", alias = "silent")]
     pub quiet: bool,
 
+    /// Allow changes to locked cells.
+    #[structopt(long = "force")]
+    pub force: bool,
+
+    /// The palette variant to use when evaluating colors.
+    #[structopt(long = "variant")]
+    pub variant: Option<String>,
+
+    /// A label to record with the history entries of the command.
+    #[structopt(long = "label")]
+    pub label: Option<String>,
+
     /// Print trace messages. (Overrides -q if both are provided.)
     #[structopt(long = "ztrace", hidden(true))]
     pub trace: bool,
@@ -152,6 +172,54 @@ pub enum CommandOption {
 
     },
 
+    /// Check a palette for problems, such as dangling references and
+    /// duplicate colors. Fails if any errors are found.
+    Check {
+        /// Fail if any warnings are found.
+        #[structopt(long = "strict")]
+        strict: bool,
+    },
+
+    /// Convert a palette file to another file format. The formats are
+    /// determined by the file extensions: 'ron', 'json', 'toml', or 'bin'.
+    /// Files with other extensions are treated as RON.
+    Convert {
+        /// The palette file to convert.
+        #[structopt(parse(from_os_str))]
+        input: PathBuf,
+
+        /// The path of the converted palette file.
+        #[structopt(parse(from_os_str))]
+        output: PathBuf,
+
+        /// The format of the input file, overriding its extension.
+        #[structopt(long = "from")]
+        from: Option<Format>,
+
+        /// The format of the output file, overriding its extension.
+        #[structopt(long = "to")]
+        to: Option<Format>,
+
+        /// Overwrite the output file if it exists.
+        #[structopt(long = "overwrite")]
+        overwrite: bool,
+    },
+
+    /// Compare two palettes, reporting added, removed, moved, and changed
+    /// cells.
+    Diff {
+        /// The first palette: a palette file, '@' for the active palette, or
+        /// '@~N' for the active palette with N history entries undone.
+        a: PaletteSource,
+
+        /// The second palette, given in the same form as the first.
+        b: PaletteSource,
+
+        /// Print the differences as JSON.
+        #[structopt(long = "json")]
+        json: bool,
+    },
+
     /// Insert colors and ramps into a palette.
     Insert {
         /// The color expression objects to insert.
@@ -164,6 +232,11 @@ pub enum CommandOption {
         /// The start position for the inserted objects.
         #[structopt(long = "at")]
         at: Option<Positioning>,
+
+        /// Shift the following cells on the same 'line' or 'page' forward to
+        /// make room for the inserted objects.
+        #[structopt(long = "shift")]
+        shift: Option<ShiftMode>,
     },
     
     /// Delete colors and ramps from a palette.
@@ -179,7 +252,98 @@ pub enum CommandOption {
 
         /// The position to move the cells to.
         #[structopt(long = "to")]
-        to: Option<Positioning>
+        to: Option<Positioning>,
+
+        /// Close the gaps left by the moved cells and shift the following
+        /// cells on the same 'line' or 'page' forward to make room for them.
+        #[structopt(long = "shift")]
+        shift: Option<ShiftMode>,
+    },
+
+    /// Copy cells along with their names and groups, remapping references
+    /// between the copied cells to the copies.
+    Duplicate {
+        /// The selection of palette cells to copy.
+        selection: CellSelection<'static>,
+
+        /// The position of the copies. The copies keep the layout of the
+        /// selection.
+        #[structopt(long = "to")]
+        to: Option<Positioning>,
+
+        /// The suffix to append to the names of the copies.
+        #[structopt(long = "suffix", default_value = "_copy")]
+        suffix: String,
+    },
+
+    /// Merge the cells of another palette file into the palette, remapping
+    /// references between the merged cells.
+    Merge {
+        /// The palette file to merge.
+        #[structopt(parse(from_os_str))]
+        path: PathBuf,
+
+        /// The position of the merged cells. The merged cells keep their
+        /// layout. If omitted, the merged cells keep their positions.
+        #[structopt(long = "at")]
+        at: Option<Position>,
+
+        /// The prefix to prepend to the names of the merged cells.
+        #[structopt(long = "prefix")]
+        prefix: Option<String>,
+
+        /// How to resolve merged cells whose position or name is already in
+        /// use: 'skip', 'overwrite', or 'rename'.
+        #[structopt(long = "on-conflict", default_value = "rename")]
+        on_conflict: ConflictPolicy,
+    },
+
+    /// Reorder the positions of cells in a palette by a color property.
+    Sort {
+        /// The selection of palette cells to sort.
+        selection: Option<CellSelection<'static>>,
+
+        /// The color property to sort by.
+        #[structopt(long = "by")]
+        by: SortKey,
+
+        /// Sort in descending order.
+        #[structopt(long = "reverse")]
+        reverse: bool,
+    },
+
+    /// Close the gaps between cell positions and indices. If neither
+    /// '--positions' nor '--indices' is given, both are compacted.
+    Compact {
+        /// The selection of palette cells to compact. If omitted, all cells
+        /// are compacted.
+        selection: Option<CellSelection<'static>>,
+
+        /// Compact cell positions, line by line.
+        #[structopt(long = "positions")]
+        positions: bool,
+
+        /// Compact cell indices.
+        #[structopt(long = "indices")]
+        indices: bool,
+    },
+
+    /// Lock cells, preventing changes to them.
+    Lock {
+        /// The selection of palette cells to lock.
+        selection: CellSelection<'static>,
+    },
+
+    /// Unlock cells.
+    Unlock {
+        /// The selection of palette cells to unlock.
+        selection: CellSelection<'static>,
+    },
+
+    /// Rename a name or group, rewriting the references to it.
+    Rename {
+        #[structopt(subcommand)]
+        rename_option: RenameOption,
     },
 
     /// Change settings, or assign color expressions, names, or metadata to
@@ -189,6 +353,12 @@ pub enum CommandOption {
         set_option: SetOption,
     },
     
+    /// Retrieve palette metadata.
+    Get {
+        #[structopt(subcommand)]
+        get_option: GetOption,
+    },
+
     /// Revert previous operations.
     Undo {
         /// The number of operations to revert.
@@ -199,9 +369,58 @@ pub enum CommandOption {
         /// The number of operations to reapply.
         count: Option<usize>,
     },
+
+    /// Display and manage the palette history.
+    History {
+        #[structopt(subcommand)]
+        history_option: Option<HistoryOption>,
+    },
+
+    /// Mark the current history position with a named checkpoint.
+    Checkpoint {
+        /// The name of the checkpoint.
+        name: String,
+    },
+
+    /// Undo or redo history entries to return to a named checkpoint.
+    Restore {
+        /// The name of the checkpoint.
+        name: String,
+    },
+
+    /// List the history checkpoints.
+    Checkpoints,
+
+    /// Display and manage the palette history branches.
+    Branch {
+        #[structopt(subcommand)]
+        branch_option: Option<BranchOption>,
+    },
+
+    /// Begin a transaction. Changes made until the transaction is committed
+    /// are recorded as a single history entry.
+    Begin,
+
+    /// Commit the pending transaction, recording its changes as a single
+    /// history entry.
+    Commit,
+
+    /// Roll back the pending transaction, reverting its changes.
+    Rollback,
+
+    /// Run a script on the palette. The script is applied as a single history
+    /// entry, and its changes are reverted if any statement fails.
+    Run {
+        /// The script file to run.
+        #[structopt(parse(from_os_str))]
+        path: PathBuf,
+    },
     
     /// Import color data into a palette.
-    Import,
+    Import {
+        #[structopt(subcommand)]
+        import_option: ImportOption,
+    },
     /// Export palette data.
     Export {
         #[structopt(subcommand)]
@@ -214,6 +433,8 @@ impl CommandOption {
     pub fn requires_palette(&self) -> bool {
         match self {
             CommandOption::New { .. } => false,
+            CommandOption::Convert { .. } => false,
+            CommandOption::Diff { a, b, .. } => a.is_active() || b.is_active(),
             CommandOption::Set { set_option } => match set_option {
                 SetOption::ActivePalette { .. } |
                 SetOption::DeleteCursorBehavior { .. } |
@@ -236,8 +457,21 @@ impl CommandOption {
 
             CommandOption::New { .. } |
             CommandOption::List { .. } |
+            CommandOption::Check { .. } |
+            CommandOption::Diff { .. } |
+            CommandOption::Convert { .. } |
+            CommandOption::Get { .. } |
             CommandOption::Undo { .. } |
             CommandOption::Redo { .. } |
+            CommandOption::History { .. } |
+            CommandOption::Checkpoint { .. } |
+            CommandOption::Restore { .. } |
+            CommandOption::Checkpoints |
+            CommandOption::Branch { .. } |
+            CommandOption::Begin |
+            CommandOption::Commit |
+            CommandOption::Rollback |
+            CommandOption::Run { .. } |
             CommandOption::Export { .. } |
             CommandOption::Import { .. } => true,
             _ => false,
@@ -332,7 +566,65 @@ pub enum SetOption {
 
         /// Unassign the group from the selected cells.
         #[structopt(long = "remove")]
-        remove: bool
+        remove: bool,
+
+        /// Move the selected group and its nested groups to the given group
+        /// path. The selection must be a single group selector.
+        #[structopt(long = "move", conflicts_with = "remove")]
+        move_group: bool,
+    },
+
+    /// Assign or clear a palette metadata field.
+    Meta {
+        /// The metadata field to set. One of 'title', 'authors', 'license',
+        /// 'description', 'tags', or any other name for a free-form entry.
+        key: String,
+
+        /// The values to assign. If omitted, the field is cleared.
+        values: Vec<String>,
+    },
+
+    /// Assign or clear a palette constraint.
+    Constraint {
+        /// The constraint to set. One of 'max_page', 'max_line', 'max_column',
+        /// 'max_cells', 'max_positions', 'max_name_length', or 'name_rule'.
+        key: String,
+
+        /// The value to assign. If omitted, the constraint is cleared. Name
+        /// rules are one of 'identifier', 'snake_case', or 'kebab_case'.
+        value: Option<String>,
+    },
+
+    /// Assign or unassign a palette library alias for external references.
+    Library {
+        /// The library alias to assign.
+        library: String,
+
+        /// The path of the library palette file, relative to the palette. If
+        /// omitted, the alias is removed.
+        #[structopt(parse(from_os_str))]
+        path: Option<PathBuf>,
+    },
+
+    /// Assign or unassign a note to selected cells.
+    Note {
+        /// The selection to annotate.
+        selection: CellSelection<'static>,
+
+        /// The note to assign. If omitted, the note is removed.
+        note: Option<String>,
+    },
+
+    /// Assign or unassign a metadata entry to selected cells.
+    CellMeta {
+        /// The selection to assign the metadata to.
+        selection: CellSelection<'static>,
+
+        /// The metadata key.
+        key: String,
+
+        /// The metadata value. If omitted, the entry is removed.
+        value: Option<String>,
     },
 
     /// Assign a color expression to a cell.
@@ -340,8 +632,13 @@ pub enum SetOption {
         /// The cell to set the expression for.
         at: CellRef<'static>,
 
-        /// The color expression to set.
-        expr: InsertExpr,
+        /// The color expression to set. If omitted when a variant is given,
+        /// the variant's override is removed.
+        expr: Option<InsertExpr>,
+
+        /// The variant to set the expression override for.
+        #[structopt(long = "variant")]
+        variant: Option<String>,
     },
 
     /// Sets the palette cursor position.
@@ -383,6 +680,144 @@ pub enum SetOption {
 }
 
 
+////////////////////////////////////////////////////////////////////////////////
+// GetOption
+////////////////////////////////////////////////////////////////////////////////
+/// Options for the get command.
+#[derive(Debug, Clone)]
+#[derive(StructOpt)]
+pub enum GetOption {
+    /// Print palette metadata.
+    Meta {
+        /// The metadata field to print. If omitted, all fields are printed.
+        key: Option<String>,
+    },
+
+    /// Print palette constraints.
+    Constraints {
+        /// The constraint to print. If omitted, all constraints are printed.
+        key: Option<String>,
+    },
+
+    /// Print the palette's variants.
+    Variants,
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// RenameOption
+////////////////////////////////////////////////////////////////////////////////
+/// Options for the rename command.
+#[derive(Debug, Clone)]
+#[derive(StructOpt)]
+pub enum RenameOption {
+    /// Rename an assigned name.
+    Name {
+        /// The name to rename.
+        old: String,
+        /// The new name.
+        new: String,
+    },
+
+    /// Rename a group and all groups nested beneath it.
+    Group {
+        /// The group to rename.
+        old: String,
+        /// The new group name.
+        new: String,
+    },
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// HistoryOption
+////////////////////////////////////////////////////////////////////////////////
+/// Options for the history command.
+#[derive(Debug, Clone)]
+#[derive(StructOpt)]
+pub enum HistoryOption {
+    /// List the history entries. This is the default.
+    List,
+
+    /// Merge a range of history entries into a single entry.
+    Squash {
+        /// The range of entry ids to merge, given as 'FROM..TO'.
+        range: EntryRange,
+    },
+
+    /// Remove all history entries, keeping the current palette state.
+    Prune,
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// BranchOption
+////////////////////////////////////////////////////////////////////////////////
+/// Options for the branch command.
+#[derive(Debug, Clone)]
+#[derive(StructOpt)]
+pub enum BranchOption {
+    /// List the history branches by the ids of their tip entries. This is the
+    /// default.
+    List,
+
+    /// Switch to a history branch, undoing and redoing entries as needed.
+    Switch {
+        /// The id of the branch's tip entry.
+        branch: usize,
+    },
+
+    /// Remove the entries of a history branch which are not shared with other
+    /// branches.
+    Prune {
+        /// The id of the branch's tip entry.
+        #[structopt(required_unless = "all")]
+        branch: Option<usize>,
+
+        /// Remove all branches other than the current branch.
+        #[structopt(long = "all")]
+        all: bool,
+    },
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// ImportOption
+////////////////////////////////////////////////////////////////////////////////
+/// Options for the import command.
+#[derive(Debug, Clone)]
+#[derive(StructOpt)]
+pub enum ImportOption {
+    /// Import palette data from a PNG file.
+    Png {
+        /// The input file name.
+        #[structopt(parse(from_os_str))]
+        path: PathBuf,
+
+        /// The name of the insert group.
+        #[structopt(long = "name")]
+        name: Option<String>,
+
+        /// The start position for the imported colors.
+        #[structopt(long = "at")]
+        at: Option<Positioning>,
+
+        /// The maximum number of colors to import from a truecolor image.
+        /// Indexed images always import their full palette.
+        #[structopt(long = "colors", default_value = "16")]
+        colors: usize,
+
+        /// The method to use for reducing truecolor images.
+        #[structopt(long = "method", default_value = "median_cut")]
+        method: QuantizeMethod,
+
+        /// The color space in which to reduce truecolor images.
+        #[structopt(long = "color-space", default_value = "rgb")]
+        color_space: ColorSpace,
+    },
+}
+
+
 ////////////////////////////////////////////////////////////////////////////////
 // ExportOption
 ////////////////////////////////////////////////////////////////////////////////
@@ -49,7 +49,9 @@ use std::path::Path;
 #[derive(Debug)]
 pub struct Script {
     /// The script's abstract syntax tree.
-    stmts: Vec<Stmt>
+    stmts: Vec<Stmt>,
+    /// The source of the script, used to describe its history entry.
+    source: Option<String>,
 }
 
 impl Script {
@@ -68,12 +70,35 @@ impl Script {
         if self.stmts.is_empty() {
             tracing::warn!("Executing empty script.");
         }
+
+        // Run the script in a transaction so that it is applied atomically.
+        // If a transaction is already pending, only the script's changes are
+        // rolled back on failure.
+        let savepoint = palette.transaction_savepoint();
+        if savepoint.is_none() {
+            palette.begin_transaction(common.label.clone())?;
+        }
         for stmt in self.stmts {
-            stmt.execute(palette, common, config, settings)?;
+            if let Err(e) = stmt.execute(palette, common, config, settings) {
+                match savepoint {
+                    Some(savepoint) => palette
+                        .rollback_to_savepoint(savepoint)?,
+                    None => palette.rollback_transaction()?,
+                }
+                return Err(e);
+            }
+        }
+        if savepoint.is_none() {
+            palette.commit_transaction()?;
         }
         Ok(())
     }
 
+    /// Returns a description of the script's source.
+    pub fn source(&self) -> &str {
+        self.source.as_deref().unwrap_or("script")
+    }
+
     /// Constructs a new `Script` by parsing data from the file at the given
     /// path.
     pub fn read_from_path<P>(path: P) -> Result<Self, FileError>
@@ -86,7 +111,9 @@ impl Script {
             .read(true)
             .open(path)
             .with_context(|| format!("Failed to open file {:?}", path))?;
-        Script::read_from_file(&mut file)
+        let mut script = Script::read_from_file(&mut file)?;
+        script.source = Some(path.display().to_string());
+        Ok(script)
     }
 
     /// Constructs a new `Script` by parsing data from the given file.
@@ -115,7 +142,7 @@ impl std::str::FromStr for Script {
 
         let (script, succ) = stmts
             (lexer)
-            .map_value(|stmts| Script { stmts })?
+            .map_value(|stmts| Script { stmts, source: None })?
             .take_value();
 
         // Try to parse end-of-text, and if it fails, return the error from
@@ -221,7 +248,12 @@ impl Stmt {
                 let cursor_behavior = CursorBehavior::MoveAfterEnd;
 
                 palette
-                    .insert_exprs(&[expr], name, positioning, cursor_behavior)
+                    .insert_exprs(
+                        &[expr],
+                        name,
+                        positioning,
+                        cursor_behavior,
+                        None)
                     .context("expr insert failed.")?;
             },
         }
@@ -19,6 +19,7 @@ use tephra::result::FailureOwned;
 
 // Standard library imports.
 use std::borrow::Cow;
+use std::path::PathBuf;
 
 
 ////////////////////////////////////////////////////////////////////////////////
@@ -109,7 +110,23 @@ pub enum FileError {
         msg: Option<String>,
         /// The error source.
         source: FailureOwned<Lf>,
-    }
+    },
+
+    /// A JSON, TOML, or binary serialization error.
+    FormatError {
+        /// The error message.
+        msg: Option<String>,
+        /// The error source.
+        source: Box<dyn std::error::Error + Send + Sync + 'static>,
+    },
+
+    /// A journal replay error.
+    JournalError {
+        /// The error message.
+        msg: Option<String>,
+        /// The error source.
+        source: PaletteError,
+    },
 }
 
 impl FileError {
@@ -127,6 +144,8 @@ impl FileError {
             FileError::IoError { msg, .. } => msg,
             FileError::RonError { msg, .. } => msg,
             FileError::ParseError { msg, .. } => msg,
+            FileError::FormatError { msg, .. } => msg,
+            FileError::JournalError { msg, .. } => msg,
         }
     }
 }
@@ -145,6 +164,14 @@ impl std::fmt::Display for FileError {
             FileError::ParseError { msg, .. } => {
                 if let Some(msg) = msg { write!(f, "{}", msg)?; }
             },
+
+            FileError::FormatError { msg, .. } => {
+                if let Some(msg) = msg { write!(f, "{}", msg)?; }
+            },
+
+            FileError::JournalError { msg, .. } => {
+                if let Some(msg) = msg { write!(f, "{}", msg)?; }
+            },
         }
         Ok(())
     }
@@ -157,6 +184,8 @@ impl std::error::Error for FileError {
             FileError::IoError { source, .. } => Some(source),
             FileError::RonError { source, .. } => Some(source),
             FileError::ParseError { source, .. } => Some(source),
+            FileError::FormatError { source, .. } => Some(&**source),
+            FileError::JournalError { source, .. } => Some(source),
         }
     }
 }
@@ -167,6 +196,36 @@ impl From<ron::error::Error> for FileError {
     }
 }
 
+impl From<serde_json::Error> for FileError {
+    fn from(err: serde_json::Error) -> Self {
+        FileError::FormatError { msg: None, source: Box::new(err) }
+    }
+}
+
+impl From<toml::de::Error> for FileError {
+    fn from(err: toml::de::Error) -> Self {
+        FileError::FormatError { msg: None, source: Box::new(err) }
+    }
+}
+
+impl From<toml::ser::Error> for FileError {
+    fn from(err: toml::ser::Error) -> Self {
+        FileError::FormatError { msg: None, source: Box::new(err) }
+    }
+}
+
+impl From<bincode::Error> for FileError {
+    fn from(err: bincode::Error) -> Self {
+        FileError::FormatError { msg: None, source: err }
+    }
+}
+
+impl From<PaletteError> for FileError {
+    fn from(err: PaletteError) -> Self {
+        FileError::JournalError { msg: None, source: err }
+    }
+}
+
 impl From<std::io::Error> for FileError {
     fn from(err: std::io::Error) -> Self {
         FileError::IoError { msg: None, source: err }
@@ -275,6 +334,85 @@ pub enum PaletteError {
         /// A description of the invalid input.
         msg: Cow<'static, str>,
     },
+
+    /// An operation attempted to modify a locked cell.
+    LockedCell {
+        /// The locked cell.
+        cell_ref: CellRef<'static>,
+    },
+
+    /// An operation would violate a palette constraint.
+    ConstraintViolation {
+        /// The name of the violated constraint.
+        constraint: &'static str,
+        /// A description of the violation.
+        msg: Cow<'static, str>,
+    },
+
+    /// A palette variant is not defined.
+    UndefinedVariant {
+        /// The undefined variant.
+        variant: Cow<'static, str>,
+    },
+
+    /// The palette history is disabled.
+    HistoryDisabled,
+
+    /// A history checkpoint is not defined.
+    UndefinedCheckpoint {
+        /// The undefined checkpoint.
+        name: Cow<'static, str>,
+    },
+
+    /// A history checkpoint refers to entries which were discarded.
+    DiscardedCheckpoint {
+        /// The discarded checkpoint.
+        name: Cow<'static, str>,
+    },
+
+    /// A history branch is not defined.
+    UndefinedBranch {
+        /// The id of the undefined branch's tip entry.
+        branch: usize,
+    },
+
+    /// An operation attempted to remove the current history branch.
+    CurrentBranch {
+        /// The id of the current branch's tip entry.
+        branch: usize,
+    },
+
+    /// A range of history entries could not be squashed.
+    InvalidHistoryRange {
+        /// The first entry of the range.
+        from: usize,
+        /// The last entry of the range.
+        to: usize,
+        /// A description of the problem.
+        msg: Cow<'static, str>,
+    },
+
+    /// An operation requires that no transaction is pending.
+    TransactionPending,
+
+    /// An operation requires a pending transaction.
+    NoTransaction,
+
+    /// A palette library alias is not defined.
+    UndefinedLibrary {
+        /// The undefined alias.
+        library: Cow<'static, str>,
+    },
+
+    /// A palette library could not be loaded.
+    LibraryLoadFailed {
+        /// The alias of the library.
+        library: Cow<'static, str>,
+        /// The path of the library palette.
+        path: PathBuf,
+        /// The error which occurred while loading the palette.
+        source: Box<FileError>,
+    },
 }
 
 impl std::fmt::Display for PaletteError {
@@ -304,13 +442,59 @@ impl std::fmt::Display for PaletteError {
                 "All palette positions are already assigned."),
 
             PaletteError::InvalidInputValue { msg } => write!(f,
-                "Invalid input value: {}", msg)
+                "Invalid input value: {}", msg),
+
+            PaletteError::LockedCell { cell_ref } => write!(f,
+                "cell {} is locked; use --force to modify it", cell_ref),
+
+            PaletteError::ConstraintViolation { constraint, msg } => write!(f,
+                "palette constraint {} violated: {}", constraint, msg),
+
+            PaletteError::UndefinedVariant { variant } => write!(f,
+                "undefined palette variant: {}", variant),
+
+            PaletteError::HistoryDisabled => write!(f,
+                "the palette history is disabled"),
+
+            PaletteError::UndefinedCheckpoint { name } => write!(f,
+                "undefined history checkpoint: {}", name),
+
+            PaletteError::DiscardedCheckpoint { name } => write!(f,
+                "history checkpoint {} cannot be restored: the entries leading \
+                to it were discarded by a later edit", name),
+
+            PaletteError::UndefinedBranch { branch } => write!(f,
+                "undefined history branch: {}", branch),
+
+            PaletteError::CurrentBranch { branch } => write!(f,
+                "history branch {} is the current branch", branch),
+
+            PaletteError::InvalidHistoryRange { from, to, msg } => write!(f,
+                "invalid history range {}..{}: {}", from, to, msg),
+
+            PaletteError::TransactionPending => write!(f,
+                "a transaction is pending; commit or roll it back first"),
+
+            PaletteError::NoTransaction => write!(f,
+                "no transaction is pending"),
+
+            PaletteError::UndefinedLibrary { library } => write!(f,
+                "undefined palette library: {}", library),
+
+            PaletteError::LibraryLoadFailed { library, path, source } => write!(f,
+                "failed to load palette library {} from {}: {}",
+                library,
+                path.display(),
+                source),
         }
     }
 }
 
 impl std::error::Error for PaletteError {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
-        None
+        match self {
+            PaletteError::LibraryLoadFailed { source, .. } => Some(&**source),
+            _ => None,
+        }
     }
 }
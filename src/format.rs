@@ -0,0 +1,226 @@
+////////////////////////////////////////////////////////////////////////////////
+// Atma structured color palette
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! File serialization formats.
+////////////////////////////////////////////////////////////////////////////////
+
+// Local imports.
+use crate::error::FileError;
+use crate::error::FileErrorContext as _;
+
+// External library imports.
+use ron::ser::PrettyConfig;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde::Serialize;
+
+// Standard library imports.
+use std::fs::File;
+use std::io::BufWriter;
+use std::io::Read;
+use std::io::Write;
+use std::path::Path;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Format
+////////////////////////////////////////////////////////////////////////////////
+/// A file serialization format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Serialize, Deserialize)]
+pub enum Format {
+    /// Rusty Object Notation.
+    Ron,
+    /// JavaScript Object Notation.
+    Json,
+    /// Tom's Obvious Minimal Language.
+    Toml,
+    /// A compact binary encoding.
+    Binary,
+}
+
+impl Format {
+    /// Returns the format associated with the extension of the given path, or
+    /// `None` if the extension is not recognized.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "ron"  => Some(Format::Ron),
+            "json" => Some(Format::Json),
+            "toml" => Some(Format::Toml),
+            "bin"  => Some(Format::Binary),
+            _      => None,
+        }
+    }
+
+    /// Returns the format to use for the file at the given path. Files with
+    /// unrecognized extensions use RON.
+    pub fn for_path(path: &Path) -> Self {
+        Format::from_path(path).unwrap_or_default()
+    }
+
+    /// Returns the file extension for the format.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Ron    => "ron",
+            Format::Json   => "json",
+            Format::Toml   => "toml",
+            Format::Binary => "bin",
+        }
+    }
+
+    /// Parses a value from the given data.
+    pub fn parse<T>(&self, buf: &[u8]) -> Result<T, FileError>
+        where T: DeserializeOwned
+    {
+        match self {
+            Format::Ron => {
+                use ron::de::Deserializer;
+                let mut d = Deserializer::from_bytes(buf)
+                    .context("Failed deserializing RON file")?;
+                let value = T::deserialize(&mut d)
+                    .context("Failed parsing RON file")?;
+                d.end()
+                    .context("Failed parsing RON file")?;
+                Ok(value)
+            },
+
+            Format::Json => serde_json::from_slice(buf)
+                .context("Failed parsing JSON file"),
+
+            Format::Toml => {
+                // TOML tables only have string keys, so the data is read
+                // through JSON values, which parse their keys on demand.
+                let value: serde_json::Value = toml::from_slice(buf)
+                    .context("Failed parsing TOML file")?;
+                serde_json::from_value(value)
+                    .context("Failed parsing TOML file")
+            },
+
+            Format::Binary => bincode::deserialize(buf)
+                .context("Failed parsing binary file"),
+        }
+    }
+
+    /// Generates the serialized data for a value. The given `PrettyConfig` is
+    /// used for RON output.
+    pub fn generate<T>(&self, value: &T, pretty: PrettyConfig)
+        -> Result<Vec<u8>, FileError>
+        where T: Serialize
+    {
+        match self {
+            Format::Ron => ron::ser::to_string_pretty(value, pretty)
+                .map(String::into_bytes)
+                .context("Failed to serialize RON file"),
+
+            Format::Json => serde_json::to_vec_pretty(value)
+                .context("Failed to serialize JSON file"),
+
+            Format::Toml => {
+                // TOML tables only have string keys and can't hold empty
+                // values, so the data is written through JSON values with the
+                // empty fields removed.
+                let mut value = serde_json::to_value(value)
+                    .context("Failed to serialize TOML file")?;
+                remove_nulls(&mut value);
+                let value = toml::Value::try_from(value)
+                    .context("Failed to serialize TOML file")?;
+                toml::to_string_pretty(&value)
+                    .map(String::into_bytes)
+                    .context("Failed to serialize TOML file")
+            },
+
+            Format::Binary => bincode::serialize(value)
+                .context("Failed to serialize binary file"),
+        }
+    }
+
+    /// Parses a value from the given file.
+    pub fn read_from_file<T>(&self, file: &mut File) -> Result<T, FileError>
+        where T: DeserializeOwned
+    {
+        let len = file.metadata()
+            .context("Failed to read file metadata")?
+            .len();
+        let mut buf = Vec::with_capacity(len as usize);
+        let _ = file.read_to_end(&mut buf)
+            .context("Failed to read file")?;
+        self.parse(&buf[..])
+    }
+
+    /// Serializes a value into the given file. The given `PrettyConfig` is
+    /// used for RON output.
+    pub fn write_to_file<T>(
+        &self,
+        value: &T,
+        pretty: PrettyConfig,
+        file: &mut File)
+        -> Result<(), FileError>
+        where T: Serialize
+    {
+        let data = self.generate(value, pretty)?;
+        let mut writer = BufWriter::new(file);
+        writer.write_all(&data[..])
+            .context("Failed to write file")?;
+        writer.flush()
+            .context("Failed to flush file buffer")
+    }
+}
+
+impl Default for Format {
+    fn default() -> Self {
+        Format::Ron
+    }
+}
+
+impl std::fmt::Display for Format {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.extension())
+    }
+}
+
+impl std::str::FromStr for Format {
+    type Err = InvalidFormat;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "ron"    => Ok(Format::Ron),
+            "json"   => Ok(Format::Json),
+            "toml"   => Ok(Format::Toml),
+            "bin"    => Ok(Format::Binary),
+            _        => Err(InvalidFormat),
+        }
+    }
+}
+
+/// Error type for an invalid file format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct InvalidFormat;
+
+impl std::fmt::Display for InvalidFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid file format: expected one of 'ron', 'json', \
+            'toml', or 'bin'")
+    }
+}
+
+impl std::error::Error for InvalidFormat {}
+
+/// Removes the null fields of the objects in the given JSON value.
+fn remove_nulls(value: &mut serde_json::Value) {
+    use serde_json::Value;
+    match value {
+        Value::Object(map) => {
+            *map = std::mem::take(map)
+                .into_iter()
+                .filter(|(_, v)| !v.is_null())
+                .collect();
+            map.values_mut().for_each(remove_nulls);
+        },
+        Value::Array(values) => values.iter_mut().for_each(remove_nulls),
+        _ => (),
+    }
+}
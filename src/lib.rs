@@ -48,6 +48,7 @@ pub mod bimap;
 pub mod cell;
 pub mod command;
 pub mod error;
+pub mod format;
 pub mod palette;
 pub mod parse;
 pub mod setup;
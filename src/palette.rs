@@ -11,13 +11,21 @@
 // Internal modules.
 mod full;
 mod basic;
+mod constraints;
 mod expr;
 mod history;
+mod journal;
+mod library;
+mod metadata;
 mod operation;
 
 // Exports.
 pub use full::*;
 pub use basic::*;
+pub use constraints::*;
 pub use expr::*;
 pub use history::*;
+pub use journal::*;
+pub use library::*;
+pub use metadata::*;
 pub use operation::*;
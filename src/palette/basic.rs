@@ -14,34 +14,43 @@ use crate::cell::Cell;
 use crate::cell::CellRef;
 use crate::cell::Position;
 use crate::cell::PositionSelector;
+use crate::cell::REF_GROUP_PATH_TOKEN;
 use crate::color::Color;
 use crate::error::FileError;
 use crate::error::FileErrorContext as _;
 use crate::error::PaletteError;
+use crate::format::Format;
+use crate::palette::Constraints;
 use crate::palette::Expr;
 use crate::palette::History;
+use crate::palette::Library;
 use crate::palette::Operation;
 use crate::utility::Few;
 use crate::utility::split_intersect;
 
 // External library imports.
+use serde::de::MapAccess;
+use serde::de::SeqAccess;
+use serde::de::Unexpected;
+use serde::de::Visitor;
 use serde::Deserialize;
+use serde::Deserializer;
 use serde::Serialize;
+use serde::Serializer;
 use ron::ser::PrettyConfig;
-use ron::ser::to_string_pretty;
 
 // Standard library imports.
 use std::borrow::Cow;
 use std::collections::BTreeMap;
+use std::collections::BTreeSet;
 use std::collections::HashSet;
 use std::convert::TryFrom;
 use std::convert::TryInto;
 use std::fmt::Debug;
 use std::fs::File;
 use std::fs::OpenOptions;
-use std::io::Read;
-use std::io::Write;
 use std::path::Path;
+use std::path::PathBuf;
 
 
 
@@ -62,9 +71,35 @@ pub struct BasicPalette {
     // any position following an assigned one is implicitely assigned to the
     // following index.
     /// A map of assigned positions.
+    #[serde(serialize_with = "serialize_positions")]
+    #[serde(deserialize_with = "deserialize_positions")]
     positions: BiMap<Position, u32>,
     /// A map of names assigned to groups of cells.
     groups: BTreeMap<Cow<'static, str>, Vec<u32>>,
+    /// A map of metadata entries assigned to cells.
+    #[serde(default)]
+    metadata: BTreeMap<u32, BTreeMap<Cow<'static, str>, Cow<'static, str>>>,
+    /// The layout and naming constraints of the palette.
+    #[serde(default)]
+    constraints: Constraints,
+    /// The indices of locked cells.
+    #[serde(default)]
+    locked: BTreeSet<u32>,
+    /// Whether to allow changes to locked cells.
+    #[serde(skip)]
+    ignore_locks: bool,
+    /// A map of variant names to the expressions they override.
+    #[serde(default)]
+    variants: BTreeMap<Cow<'static, str>, BTreeMap<u32, Expr>>,
+    /// The variant to use when evaluating colors.
+    #[serde(skip)]
+    active_variant: Option<Cow<'static, str>>,
+    /// A map of palette library aliases to palette file paths.
+    #[serde(default)]
+    libraries: BTreeMap<Cow<'static, str>, PathBuf>,
+    /// The palette libraries used to resolve external references.
+    #[serde(skip)]
+    library: Library,
     /// The next free cell index.
     next_index: u32,
     // TODO: Undo/redo should track the cursor position.
@@ -86,13 +121,21 @@ impl BasicPalette {
             names: BiMap::new(),
             positions: BiMap::new(),
             groups: BTreeMap::new(),
+            metadata: BTreeMap::new(),
+            constraints: Constraints::default(),
+            locked: BTreeSet::new(),
+            ignore_locks: false,
+            variants: BTreeMap::new(),
+            active_variant: None,
+            libraries: BTreeMap::new(),
+            library: Library::default(),
             next_index: 0,
             position_cursor: Position::ZERO,
         }
     }
 
     /// Constructs a new `BasicPalette` by parsing data from the file at the given
-    /// path.
+    /// path. The file format is determined by the path's extension.
     pub fn read_from_path<P>(path: &P) -> Result<Self, FileError>
         where P: AsRef<Path> + Debug
     {
@@ -100,31 +143,17 @@ impl BasicPalette {
             .read(true)
             .open(path)
             .with_context(|| format!("Failed to open file {:?}", path))?;
-        BasicPalette::read_from_file(&mut file)
+        BasicPalette::read_from_file(
+            &mut file,
+            Format::for_path(path.as_ref()))
     }
 
-    /// Constructs a new `BasicPalette` by parsing data from the given file.
-    pub fn read_from_file(file: &mut File) -> Result<Self, FileError> {
-        BasicPalette::parse_ron_from_file(file)
-    }
-
-    /// Parses a `BasicPalette` from a file using the RON format.
-    fn parse_ron_from_file(file: &mut File) -> Result<Self, FileError> {
-        let len = file.metadata()
-            .context("Failed to read file metadata")?
-            .len();
-        let mut buf = Vec::with_capacity(len as usize);
-        let _ = file.read_to_end(&mut buf)
-            .context("Failed to read palette file")?;
-
-        use ron::de::Deserializer;
-        let mut d = Deserializer::from_bytes(&buf)
-            .context("Failed deserializing RON file")?;
-        let palette = BasicPalette::deserialize(&mut d)
-            .context("Failed parsing RON file")?;
-        d.end()
-            .context("Failed parsing RON file")?;
-        Ok(palette)
+    /// Constructs a new `BasicPalette` by parsing data in the given format
+    /// from the given file.
+    pub fn read_from_file(file: &mut File, format: Format)
+        -> Result<Self, FileError>
+    {
+        format.read_from_file(file)
     }
 
     /// Writes the `BasicPalette` to the file at the given path.
@@ -136,7 +165,7 @@ impl BasicPalette {
             .create(true)
             .open(path)
             .with_context(|| format!("Failed to open file {:?}", path))?;
-        self.write_to_file(&mut file)
+        self.write_to_file(&mut file, Format::for_path(path.as_ref()))
     }
 
     /// Writes the `BasicPalette` to a new file at the given path.
@@ -148,23 +177,17 @@ impl BasicPalette {
             .create_new(true)
             .open(path)
             .with_context(|| format!("Failed to open file {:?}", path))?;
-        self.write_to_file(&mut file)
-    }
-
-    /// Writes the `BasicPalette` to the given file.
-    pub fn write_to_file(&self, file: &mut File) -> Result<(), FileError> {
-        self.generate_ron_into_file(file)
+        self.write_to_file(&mut file, Format::for_path(path.as_ref()))
     }
 
-    /// Generates a RON formatted `BasicPalette` by serializing into the given file.
-    fn generate_ron_into_file(&self, file: &mut File) -> Result<(), FileError> {
+    /// Writes the `BasicPalette` to the given file in the given format.
+    pub fn write_to_file(&self, file: &mut File, format: Format)
+        -> Result<(), FileError>
+    {
         let pretty = PrettyConfig::new()
             .with_depth_limit(2)
             .with_separate_tuple_members(true);
-        let s = to_string_pretty(self, pretty)?;
-
-        file.write_all(s.as_bytes())?;
-        Ok(())
+        format.write_to_file(self, pretty, file)
     }
 
     ////////////////////////////////////////////////////////////////////////////
@@ -195,6 +218,10 @@ impl BasicPalette {
         index_list: &mut HashSet<u32>)
         -> Result<Option<Color>, PaletteError>
     {
+        if let CellRef::External { library, cell_ref } = cell_ref {
+            return self.library.color(library, cell_ref, &self.libraries);
+        }
+
         let idx = BasicPalette::resolve_ref_to_index(&self, cell_ref)?;
         if index_list.contains(&idx) {
             return Err(PaletteError::UndefinedColor {
@@ -204,13 +231,127 @@ impl BasicPalette {
         }
         let _ = index_list.insert(idx);
 
-        self.cells
+        let cell = self.cells
             .get(&idx)
             .ok_or(PaletteError::UndefinedColor { 
                 cell_ref: cell_ref.clone().into_static(),
                 circular: false,
-            })
-            .and_then(|cell| cell.color(self, index_list))
+            })?;
+
+        // Relative references depend on the cell's position, so they are
+        // resolved on each evaluation rather than cached.
+        let expr = self.active_variant_expr(idx);
+        if expr.unwrap_or(cell.expr()).has_relative_refs() {
+            return self
+                .resolve_relative_refs(expr.unwrap_or(cell.expr()), idx)?
+                .color(self, index_list);
+        }
+
+        match expr {
+            Some(expr) => expr.color(self, index_list),
+            None       => cell.color(self, index_list),
+        }
+    }
+
+    /// Returns a copy of the given expression with its relative references
+    /// replaced by the positions they refer to from the cell with the given
+    /// index.
+    pub(in crate) fn resolve_relative_refs(&self, expr: &Expr, idx: u32)
+        -> Result<Expr, PaletteError>
+    {
+        let origin = self.positions.get_right(&idx).cloned();
+        let mut resolved = expr.clone();
+        let mut error = None;
+        resolved.map_cell_refs(|cell_ref| {
+            let offset = match cell_ref {
+                CellRef::Relative(offset) => *offset,
+                _                         => return,
+            };
+            match origin.and_then(|origin| offset.apply(origin)) {
+                Some(position) => *cell_ref = CellRef::Position(position),
+                None => if error.is_none() {
+                    error = Some(PaletteError::UndefinedCellReference {
+                        cell_ref: cell_ref.clone(),
+                    });
+                },
+            }
+        });
+
+        match error {
+            Some(error) => Err(error),
+            None        => Ok(resolved),
+        }
+    }
+
+    /// Returns true if the cell with the given reference is locked.
+    pub fn is_locked<'name>(&self, cell_ref: &CellRef<'name>) -> bool {
+        self.resolve_ref_to_index(cell_ref)
+            .map(|idx| self.locked.contains(&idx))
+            .unwrap_or(false)
+    }
+
+    /// Returns true if changes to locked cells are allowed.
+    pub fn ignore_locks(&self) -> bool {
+        self.ignore_locks
+    }
+
+    /// Sets whether to allow changes to locked cells.
+    pub fn set_ignore_locks(&mut self, ignore_locks: bool) {
+        self.ignore_locks = ignore_locks;
+    }
+
+    /// Returns the active variant's override expression for the given index.
+    fn active_variant_expr(&self, idx: u32) -> Option<&Expr> {
+        self.active_variant
+            .as_ref()
+            .and_then(|variant| self.variants.get(variant))
+            .and_then(|exprs| exprs.get(&idx))
+    }
+
+    /// Returns the name of the variant used when evaluating colors.
+    pub fn active_variant(&self) -> Option<&str> {
+        self.active_variant
+            .as_ref()
+            .map(AsRef::as_ref)
+    }
+
+    /// Sets the variant to use when evaluating colors.
+    ///
+    /// ### Errors
+    ///
+    /// Returns a `PaletteError` if the variant is not defined in the palette.
+    pub fn set_active_variant<T>(&mut self, variant: Option<T>)
+        -> Result<(), PaletteError>
+        where T: Into<Cow<'static, str>>
+    {
+        let variant = variant.map(Into::into);
+        if let Some(variant) = &variant {
+            if !self.variants.contains_key(variant) {
+                return Err(PaletteError::UndefinedVariant {
+                    variant: variant.clone(),
+                });
+            }
+        }
+        self.active_variant = variant;
+        Ok(())
+    }
+
+    /// Returns an iterator over the palette's variant names and the number of
+    /// expressions each overrides.
+    pub fn variants(&self) -> impl Iterator<Item=(&str, usize)> + '_ {
+        self.variants
+            .iter()
+            .map(|(variant, exprs)| (variant.as_ref(), exprs.len()))
+    }
+
+    /// Returns the variant override expression for the given cell reference.
+    pub fn variant_expr<'name>(&self, variant: &str, cell_ref: &CellRef<'name>)
+        -> Option<&Expr>
+    {
+        let idx = self.resolve_ref_to_index(cell_ref).ok()?;
+        self.variants
+            .get(variant)
+            .and_then(|exprs| exprs.get(&idx))
     }
 
     /// Retreives a reference to the `Cell` associated with the given `CellRef`.
@@ -287,6 +428,14 @@ impl BasicPalette {
                 .ok_or(PaletteError::UndefinedCellReference { 
                     cell_ref: cell_ref.clone().into_static(),
                 }),
+
+            // External cells are never part of this palette, and relative
+            // references have no origin outside of an expression.
+            CellRef::Relative(_)     |
+            CellRef::External { .. } => Err(
+                PaletteError::UndefinedCellReference { 
+                    cell_ref: cell_ref.clone().into_static(),
+                }),
         }
     }
 
@@ -303,13 +452,19 @@ impl BasicPalette {
     }
 
     /// Returns the given position if it is unoccupied, or the next unoccupied
-    /// position after it.
+    /// position after it. Positions outside of the palette's constraints are
+    /// skipped.
     pub fn unoccupied_position_or_next(&self, from: Position)
         -> Option<Position>
     {
+        let from = if self.constraints.contains(&from) {
+            from
+        } else {
+            self.constraints.wrapping_succ(from)
+        };
         let mut next = from;
         while self.is_occupied_position(&next) {
-            next = next.wrapping_succ();
+            next = self.constraints.wrapping_succ(next);
             // Check if we've looped all the way around.
             if next == from { return None; }
         }
@@ -367,6 +522,22 @@ impl BasicPalette {
             .get_right(pos_sel)
     }
 
+    /// Returns the position selector assigned to the given name.
+    pub fn get_name_selector(&self, name: &str) -> Option<&PositionSelector> {
+        self.names
+            .get_left(&Cow::Borrowed(name))
+    }
+
+    /// Returns an iterator over the assigned names and their position
+    /// selectors.
+    pub fn names(&self)
+        -> impl Iterator<Item=(&str, &PositionSelector)> + '_
+    {
+        self.names
+            .iter()
+            .map(|(name, selector)| (name.as_ref(), selector))
+    }
+
     /// Returns true if the given name is assigned in the palette.
     pub fn is_assigned_name(&self, name: &str) -> bool {
         self.names
@@ -420,6 +591,112 @@ impl BasicPalette {
             .collect())
     }
 
+    /// Returns the metadata entries assigned to the given cell reference.
+    pub fn assigned_metadata<'name>(&self, cell_ref: &CellRef<'name>)
+        -> Option<&BTreeMap<Cow<'static, str>, Cow<'static, str>>>
+    {
+        let idx = self.resolve_ref_to_index(cell_ref).ok()?;
+        self.metadata
+            .get(&idx)
+    }
+
+    /// Returns the occupied indices of the cells with the given metadata key
+    /// assigned. If a value is provided, only cells whose entry matches the
+    /// value are returned.
+    pub fn resolve_metadata_if_occupied(&self, key: &str, value: Option<&str>)
+        -> Vec<u32>
+    {
+        self.metadata
+            .iter()
+            .filter(|(idx, entries)| self.cells.contains_key(idx) &&
+                match (entries.get(key), value) {
+                    (Some(v), Some(value)) => v == value,
+                    (Some(_), None)        => true,
+                    (None,    _)           => false,
+                })
+            .map(|(idx, _)| *idx)
+            .collect()
+    }
+
+    /// Returns an iterator over the palette's cell indices and expressions.
+    pub fn exprs(&self) -> impl Iterator<Item=(u32, &Expr)> + '_ {
+        self.cells
+            .iter()
+            .map(|(idx, cell)| (*idx, cell.expr()))
+    }
+
+    /// Returns an iterator over the palette's variant override expressions,
+    /// along with their variant names and cell indices.
+    pub fn variant_exprs(&self)
+        -> impl Iterator<Item=(&Cow<'static, str>, u32, &Expr)> + '_
+    {
+        self.variants
+            .iter()
+            .flat_map(|(variant, exprs)| exprs
+                .iter()
+                .map(move |(idx, expr)| (variant, *idx, expr)))
+    }
+
+    /// Returns an iterator over the palette's assigned positions and the
+    /// indices of the cells they are assigned to, in position order.
+    pub fn assigned_positions(&self)
+        -> impl Iterator<Item=(Position, u32)> + '_
+    {
+        self.positions
+            .iter()
+            .map(|(position, idx)| (*position, *idx))
+    }
+
+    /// Returns the palette's `Constraints`.
+    pub fn constraints(&self) -> &Constraints {
+        &self.constraints
+    }
+
+    /// Returns the number of cells in the palette.
+    pub fn cell_count(&self) -> usize {
+        self.cells.len()
+    }
+
+    /// Returns the path assigned to the given palette library alias.
+    pub fn library_path(&self, library: &str) -> Option<&Path> {
+        self.libraries
+            .get(library)
+            .map(PathBuf::as_path)
+    }
+
+    /// Returns an iterator over the palette library aliases and their paths.
+    pub fn libraries(&self) -> impl Iterator<Item=(&str, &Path)> + '_ {
+        self.libraries
+            .iter()
+            .map(|(library, path)| (library.as_ref(), path.as_path()))
+    }
+
+    /// Assigns or unassigns the path of a palette library alias. Relative
+    /// paths are resolved from the palette's directory.
+    pub fn set_library_path<T>(&mut self, library: T, path: Option<PathBuf>)
+        -> Result<Vec<Operation>, PaletteError>
+        where T: Into<Cow<'static, str>>
+    {
+        let library = library.into();
+        let old = match path {
+            Some(path) => self.libraries.insert(library.clone(), path),
+            None       => self.libraries.remove(&library),
+        };
+        match old {
+            Some(path) => Ok(vec![
+                Operation::SetLibraryPath { library, path },
+            ]),
+            None => Ok(vec![
+                Operation::UnsetLibraryPath { library },
+            ]),
+        }
+    }
+
+    /// Sets the `Library` used to resolve external cell references.
+    pub fn set_library(&mut self, library: Library) {
+        self.library = library;
+    }
+
     /// Returns true if the given group index is assigned in the palette.
     pub fn is_assigned_group(&self, group: &str, idx: u32) -> bool {
         self.groups
@@ -471,6 +748,36 @@ impl BasicPalette {
         }
     }
 
+    /// Returns the occupied indices of the cells within the given group and
+    /// its nested groups. If `recursive` is false, only the group's immediate
+    /// child groups are included.
+    pub fn resolve_group_tree_if_occupied(&self, group: &str, recursive: bool)
+        -> Vec<u32>
+    {
+        self.groups
+            .iter()
+            .filter(|(path, _)| is_in_group_tree(path, group, recursive))
+            .flat_map(|(_, elems)| elems.iter())
+            .filter(|idx| self.cells.contains_key(idx))
+            .copied()
+            .collect()
+    }
+
+    /// Returns true if any groups are nested beneath the given group.
+    pub fn has_nested_groups(&self, group: &str) -> bool {
+        self.groups
+            .keys()
+            .any(|path| path != group && is_in_group_tree(path, group, true))
+    }
+
+    /// Returns an iterator over the group paths in the palette and their
+    /// assigned cell indices.
+    pub fn groups(&self) -> impl Iterator<Item=(&str, &[u32])> + '_ {
+        self.groups
+            .iter()
+            .map(|(path, elems)| (path.as_ref(), &elems[..]))
+    }
+
     /// Returns the index associated with the given group if it is occupied.
     pub fn resolve_group_if_occupied(&self, group: &str, idx: u32)
         -> Option<u32>
@@ -558,7 +865,8 @@ impl BasicPalette {
     /// Applies a sequence of `Operation`s to the palette.
     ///
     /// The applied operations' undo ops will be grouped together and inserted
-    /// into the provided `History`.
+    /// into the provided `History`. If any operation fails, the operations
+    /// already applied are reverted.
     ///
     /// ### Parameters
     /// + `op`: The operation to apply.
@@ -569,20 +877,52 @@ impl BasicPalette {
         history: Option<&mut History>)
         -> Result<(), PaletteError>
     {
+        let undo_ops = self.apply_operations_reversibly(ops)?;
         if let Some(history) = history {
-            let mut undo_ops = Vec::with_capacity(ops.len());
-            for op in ops {
-                undo_ops.extend(self.apply_operation(op)?);
-            }
             history.push_undo_ops(undo_ops);
-        } else {
-            for op in ops {
-                let _ = self.apply_operation(op)?;
-            }
         }
         Ok(())
     }
 
+    /// Applies a sequence of `Operation`s to the palette, returning the undo
+    /// ops which revert them. If any operation fails, the operations already
+    /// applied are reverted.
+    pub fn apply_operations_reversibly(&mut self, ops: &[Operation])
+        -> Result<Vec<Operation>, PaletteError>
+    {
+        let mut undo_ops = Vec::with_capacity(ops.len());
+        for op in ops {
+            match self.apply_operation(op) {
+                Ok(undo) => undo_ops.push(undo),
+                Err(e)   => {
+                    let undo_ops: Vec<_> = undo_ops
+                        .into_iter()
+                        .rev()
+                        .flatten()
+                        .collect();
+                    self.revert_operations(&undo_ops);
+                    return Err(e);
+                },
+            }
+        }
+        // Undo operations must be applied in the reverse order.
+        Ok(undo_ops.into_iter().rev().flatten().collect())
+    }
+
+    /// Applies the given undo ops, as returned by
+    /// `apply_operations_reversibly`, ignoring cell locks.
+    ///
+    /// ### Panics
+    ///
+    /// Panics if an undo op cannot be applied to the palette in its current
+    /// state.
+    pub(in crate) fn revert_operations(&mut self, undo_ops: &[Operation]) {
+        for op in undo_ops {
+            let _ = self.apply_unlocked_operation(op)
+                .expect("revert from valid state");
+        }
+    }
+
     /// Unapplies the latest set of operations recorded in the given `History`.
     ///
     /// Returns the number of undo operations successfully performed. This may
@@ -602,11 +942,11 @@ impl BasicPalette {
             history.undo_with(|undo_ops| {
                 let mut redo_ops = Vec::with_capacity(undo_ops.len());
                 for op in undo_ops {
-                    redo_ops.extend(self.apply_operation(op)
+                    redo_ops.push(self.apply_unlocked_operation(op)
                         .expect("undo from valid state"));
                 }
                 real_count += 1;
-                redo_ops
+                redo_ops.into_iter().rev().flatten().collect()
             });
         }
         real_count
@@ -632,11 +972,11 @@ impl BasicPalette {
             history.redo_with(|redo_ops| {
                 let mut undo_ops = Vec::with_capacity(redo_ops.len());
                 for op in redo_ops {
-                    undo_ops.extend(self.apply_operation(op)
+                    undo_ops.push(self.apply_unlocked_operation(op)
                         .expect("redo from valid state"));
                 }
                 real_count += 1;
-                undo_ops
+                undo_ops.into_iter().rev().flatten().collect()
             });
         }
         real_count
@@ -652,8 +992,45 @@ impl BasicPalette {
     ///
     /// ### Parameters
     /// + `op`: The operation to apply.
+    ///
+    /// ### Errors
+    ///
+    /// Returns a `PaletteError::LockedCell` if the operation would change the
+    /// expression, existence, position, or groups of a locked cell, unless
+    /// locks are ignored.
     pub fn apply_operation(&mut self, op: &Operation) 
         -> Result<Vec<Operation>, PaletteError>
+    {
+        use Operation::*;
+        if !self.ignore_locks {
+            match op {
+                RemoveCell { cell_ref }             |
+                AssignPosition { cell_ref, .. }     |
+                UnassignPosition { cell_ref }       |
+                AssignGroup { cell_ref, .. }        |
+                UnassignGroup { cell_ref, .. }      |
+                ClearGroups { cell_ref }            |
+                SetExpr { cell_ref, .. }            |
+                SetVariantExpr { cell_ref, .. }     |
+                UnsetVariantExpr { cell_ref, .. }   => {
+                    if self.is_locked(cell_ref) {
+                        return Err(PaletteError::LockedCell {
+                            cell_ref: cell_ref.clone(),
+                        });
+                    }
+                },
+                _ => (),
+            }
+        }
+
+        self.apply_unlocked_operation(op)
+    }
+
+    /// Applies an `Operation` to the palette without checking for locked
+    /// cells. Undo and redo restore previously applied states, so they use
+    /// this to avoid being blocked by locks.
+    fn apply_unlocked_operation(&mut self, op: &Operation) 
+        -> Result<Vec<Operation>, PaletteError>
     {
         use Operation::*;
         match op {
@@ -679,8 +1056,34 @@ impl BasicPalette {
             ClearGroups { cell_ref } 
                 => self.clear_groups(cell_ref.clone()),
 
+            AssignMetadata { cell_ref, key, value }
+                => self.assign_metadata(
+                    cell_ref.clone(),
+                    key.clone(),
+                    value.clone()),
+            UnassignMetadata { cell_ref, key }
+                => self.unassign_metadata(cell_ref.clone(), key.clone()),
+
             SetExpr { cell_ref, expr }
                 => self.set_expr(cell_ref.clone(), expr.clone()),
+            SetVariantExpr { variant, cell_ref, expr }
+                => self.set_variant_expr(
+                    variant.clone(),
+                    cell_ref.clone(),
+                    expr.clone()),
+            UnsetVariantExpr { variant, cell_ref }
+                => self.unset_variant_expr(variant.clone(), cell_ref.clone()),
+
+            Lock { cell_ref } => self.lock(cell_ref.clone()),
+            Unlock { cell_ref } => self.unlock(cell_ref.clone()),
+
+            SetLibraryPath { library, path }
+                => self.set_library_path(library.clone(), Some(path.clone())),
+            UnsetLibraryPath { library }
+                => self.set_library_path(library.clone(), None),
+
+            SetConstraints { constraints }
+                => self.set_constraints(constraints.clone()),
 
             SetPositionCursor { position }
                 => Ok(vec![SetPositionCursor {
@@ -707,21 +1110,48 @@ impl BasicPalette {
         }
     }
 
-    /// Removes a `Cell` from the palette.
+    /// Removes a `Cell` from the palette, along with its lock, metadata
+    /// entries, and variant override expressions.
     pub fn remove_cell<'name>(&mut self, cell_ref: CellRef<'name>)
         -> Result<Vec<Operation>, PaletteError> 
     {
         let idx = BasicPalette::resolve_ref_to_index(&self, &cell_ref)?;
         
-        match self.cells.remove(&idx) {
-            // Cell was removed.
-            Some(cell) => Ok(vec![
-                Operation::InsertCell { idx, cell },
-            ]),
-
+        let cell = match self.cells.remove(&idx) {
+            Some(cell) => cell,
             // Cell is already missing.
-            None => Ok(Vec::new()),
+            None => return Ok(Vec::new()),
+        };
+
+        // The cell must be restored before anything attached to it.
+        let mut ops = vec![Operation::InsertCell { idx, cell }];
+
+        if let Some(entries) = self.metadata.remove(&idx) {
+            for (key, value) in entries {
+                ops.push(Operation::AssignMetadata {
+                    cell_ref: CellRef::Index(idx),
+                    key,
+                    value,
+                });
+            }
         }
+
+        for (variant, exprs) in self.variants.iter_mut() {
+            if let Some(expr) = exprs.remove(&idx) {
+                ops.push(Operation::SetVariantExpr {
+                    variant: variant.clone(),
+                    cell_ref: CellRef::Index(idx),
+                    expr,
+                });
+            }
+        }
+        self.variants.retain(|_, exprs| !exprs.is_empty());
+
+        if self.locked.remove(&idx) {
+            ops.push(Operation::Lock { cell_ref: CellRef::Index(idx) });
+        }
+
+        Ok(ops)
     }
 
     /// Assigns a name to a position selector.
@@ -733,6 +1163,8 @@ impl BasicPalette {
         where T: Into<Cow<'static, str>>
     {
         let name = name.into();
+        self.constraints.check_name(&name)?;
+        self.constraints.check_selector(&selector)?;
 
         use crate::bimap::Overwritten::*;
         match self.names.insert(name.clone(), selector) {
@@ -791,6 +1223,12 @@ impl BasicPalette {
         -> Result<Vec<Operation>, PaletteError>
     {
         let idx = BasicPalette::resolve_ref_to_index(&self, &cell_ref)?;
+        self.constraints.check_position(&position)?;
+        if !self.positions.contains_right(&idx) &&
+            !self.positions.contains_left(&position)
+        {
+            self.constraints.check_position_count(self.positions.len() + 1)?;
+        }
 
         use crate::bimap::Overwritten::*;
         match self.positions.insert(position, idx) {
@@ -854,6 +1292,7 @@ impl BasicPalette {
         where T: Into<Cow<'static, str>>
     {
         let group = group.into();
+        validate_group_path(&group)?;
         let idx = BasicPalette::resolve_ref_to_index(&self, &cell_ref)?;
 
         let members = self.groups.entry(group.clone()).or_default();
@@ -956,6 +1395,122 @@ impl BasicPalette {
         Ok(ops)
     }
 
+    /// Assigns a metadata entry to a cell.
+    pub fn assign_metadata<'name, K, V>(
+        &mut self,
+        cell_ref: CellRef<'name>,
+        key: K,
+        value: V)
+        -> Result<Vec<Operation>, PaletteError>
+        where
+            K: Into<Cow<'static, str>>,
+            V: Into<Cow<'static, str>>,
+    {
+        let key = key.into();
+        let idx = BasicPalette::resolve_ref_to_index(&self, &cell_ref)?;
+
+        match self.metadata
+            .entry(idx)
+            .or_default()
+            .insert(key.clone(), value.into())
+        {
+            Some(old) => Ok(vec![
+                Operation::AssignMetadata {
+                    cell_ref: CellRef::Index(idx),
+                    key,
+                    value: old,
+                },
+            ]),
+            None => Ok(vec![
+                Operation::UnassignMetadata {
+                    cell_ref: CellRef::Index(idx),
+                    key,
+                },
+            ]),
+        }
+    }
+
+    /// Unassigns a metadata entry for a cell.
+    pub fn unassign_metadata<'name, K>(
+        &mut self,
+        cell_ref: CellRef<'name>,
+        key: K)
+        -> Result<Vec<Operation>, PaletteError>
+        where K: Into<Cow<'static, str>>
+    {
+        let key = key.into();
+        let idx = BasicPalette::resolve_ref_to_index(&self, &cell_ref)?;
+
+        let res = match self.metadata
+            .get_mut(&idx)
+            .and_then(|entries| entries.remove(&key))
+        {
+            Some(old) => Ok(vec![
+                Operation::AssignMetadata {
+                    cell_ref: CellRef::Index(idx),
+                    key,
+                    value: old,
+                },
+            ]),
+            None => Ok(Vec::new()),
+        };
+
+        if self.metadata.get(&idx).map(BTreeMap::is_empty).unwrap_or(false) {
+            let _ = self.metadata.remove(&idx);
+        }
+
+        res
+    }
+
+    /// Locks a cell.
+    pub fn lock<'name>(&mut self, cell_ref: CellRef<'name>)
+        -> Result<Vec<Operation>, PaletteError>
+    {
+        let idx = BasicPalette::resolve_ref_to_index(&self, &cell_ref)?;
+
+        if self.locked.insert(idx) {
+            Ok(vec![Operation::Unlock { cell_ref: CellRef::Index(idx) }])
+        } else {
+            Ok(Vec::new())
+        }
+    }
+
+    /// Unlocks a cell.
+    pub fn unlock<'name>(&mut self, cell_ref: CellRef<'name>)
+        -> Result<Vec<Operation>, PaletteError>
+    {
+        let idx = BasicPalette::resolve_ref_to_index(&self, &cell_ref)?;
+
+        if self.locked.remove(&idx) {
+            Ok(vec![Operation::Lock { cell_ref: CellRef::Index(idx) }])
+        } else {
+            Ok(Vec::new())
+        }
+    }
+
+    /// Sets the palette's `Constraints`.
+    ///
+    /// ### Errors
+    ///
+    /// Returns a `PaletteError::ConstraintViolation` if the palette's current
+    /// cells, positions, or names do not satisfy the new constraints.
+    pub fn set_constraints(&mut self, constraints: Constraints)
+        -> Result<Vec<Operation>, PaletteError>
+    {
+        constraints.check_cell_count(self.cells.len())?;
+        constraints.check_position_count(self.positions.len())?;
+        for position in self.positions.left_values() {
+            constraints.check_position(position)?;
+        }
+        for (name, selector) in self.names.iter() {
+            constraints.check_name(name)?;
+            constraints.check_selector(selector)?;
+        }
+
+        let old = std::mem::replace(&mut self.constraints, constraints);
+        Ok(vec![Operation::SetConstraints { constraints: old }])
+    }
+
     /// Sets the color expression for a `Cell`.
     pub fn set_expr<'name>(&mut self, cell_ref: CellRef<'name>, expr: Expr)
         -> Result<Vec<Operation>, PaletteError>
@@ -974,6 +1529,76 @@ impl BasicPalette {
             }
         ])
     }
+
+    /// Sets the color expression override for a `Cell` in a variant.
+    pub fn set_variant_expr<'name, T>(
+        &mut self,
+        variant: T,
+        cell_ref: CellRef<'name>,
+        expr: Expr)
+        -> Result<Vec<Operation>, PaletteError>
+        where T: Into<Cow<'static, str>>
+    {
+        let variant = variant.into();
+        let idx = BasicPalette::resolve_ref_to_index(&self, &cell_ref)?;
+        if !self.cells.contains_key(&idx) {
+            return Err(PaletteError::UndefinedCellReference {
+                cell_ref: cell_ref.into_static(),
+            });
+        }
+
+        match self.variants
+            .entry(variant.clone())
+            .or_default()
+            .insert(idx, expr)
+        {
+            Some(old) => Ok(vec![
+                Operation::SetVariantExpr {
+                    variant,
+                    cell_ref: CellRef::Index(idx),
+                    expr: old,
+                },
+            ]),
+            None => Ok(vec![
+                Operation::UnsetVariantExpr {
+                    variant,
+                    cell_ref: CellRef::Index(idx),
+                },
+            ]),
+        }
+    }
+
+    /// Removes the color expression override for a `Cell` in a variant.
+    pub fn unset_variant_expr<'name, T>(
+        &mut self,
+        variant: T,
+        cell_ref: CellRef<'name>)
+        -> Result<Vec<Operation>, PaletteError>
+        where T: Into<Cow<'static, str>>
+    {
+        let variant = variant.into();
+        let idx = BasicPalette::resolve_ref_to_index(&self, &cell_ref)?;
+
+        let res = match self.variants
+            .get_mut(&variant)
+            .and_then(|exprs| exprs.remove(&idx))
+        {
+            Some(old) => Ok(vec![
+                Operation::SetVariantExpr {
+                    variant: variant.clone(),
+                    cell_ref: CellRef::Index(idx),
+                    expr: old,
+                },
+            ]),
+            None => Ok(Vec::new()),
+        };
+
+        if self.variants.get(&variant).map(BTreeMap::is_empty).unwrap_or(false) {
+            let _ = self.variants.remove(&variant);
+        }
+
+        res
+    }
 }
 
 impl Default for BasicPalette {
@@ -981,3 +1606,163 @@ impl Default for BasicPalette {
         BasicPalette::new()
     }
 }
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Position serialization
+////////////////////////////////////////////////////////////////////////////////
+/// Serializes the palette's position assignments as a map from cell index to
+/// position. Unlike positions, indices can be used as map keys in formats
+/// which only allow string keys.
+fn serialize_positions<S>(positions: &BiMap<Position, u32>, ser: S)
+    -> Result<S::Ok, S::Error>
+    where S: Serializer
+{
+    ser.collect_map(positions.iter().map(|(position, idx)| (idx, position)))
+}
+
+/// Deserializes the palette's position assignments. Maps from position to
+/// cell index, as written by earlier versions, are also accepted from
+/// self-describing formats.
+fn deserialize_positions<'de, D>(de: D)
+    -> Result<BiMap<Position, u32>, D::Error>
+    where D: Deserializer<'de>
+{
+    if de.is_human_readable() {
+        de.deserialize_map(PositionMapVisitor)
+    } else {
+        Ok(BTreeMap::<u32, Position>::deserialize(de)?
+            .into_iter()
+            .map(|(idx, position)| (position, idx))
+            .collect())
+    }
+}
+
+/// Visitor to construct position assignments from map entries in either
+/// orientation.
+struct PositionMapVisitor;
+
+impl<'de> Visitor<'de> for PositionMapVisitor {
+    type Value = BiMap<Position, u32>;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "a map of cell positions")
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+        where A: MapAccess<'de>
+    {
+        let mut positions = BiMap::new();
+        while let Some(key) = map.next_key::<PositionMapKey>()? {
+            let _ = match key {
+                PositionMapKey::Index(idx) => {
+                    positions.insert(map.next_value()?, idx)
+                },
+                PositionMapKey::Position(position) => {
+                    positions.insert(position, map.next_value()?)
+                },
+            };
+        }
+        Ok(positions)
+    }
+}
+
+/// A key of the serialized position assignments.
+enum PositionMapKey {
+    /// A cell index, mapped to its position.
+    Index(u32),
+    /// A position, mapped to its cell index.
+    Position(Position),
+}
+
+impl<'de> Deserialize<'de> for PositionMapKey {
+    fn deserialize<D>(de: D) -> Result<Self, D::Error>
+        where D: Deserializer<'de>
+    {
+        de.deserialize_any(PositionMapKeyVisitor)
+    }
+}
+
+/// Visitor to construct a `PositionMapKey`.
+struct PositionMapKeyVisitor;
+
+impl<'de> Visitor<'de> for PositionMapKeyVisitor {
+    type Value = PositionMapKey;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "a cell index or position")
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+        where E: serde::de::Error
+    {
+        u32::try_from(v)
+            .map(PositionMapKey::Index)
+            .map_err(|_| E::invalid_value(Unexpected::Unsigned(v), &self))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+        where E: serde::de::Error
+    {
+        u32::try_from(v)
+            .map(PositionMapKey::Index)
+            .map_err(|_| E::invalid_value(Unexpected::Signed(v), &self))
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+        where E: serde::de::Error
+    {
+        // Formats with string keys store indices as strings.
+        v.parse()
+            .map(PositionMapKey::Index)
+            .map_err(|_| E::invalid_value(Unexpected::Str(v), &self))
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+        where A: SeqAccess<'de>
+    {
+        use serde::de::Error as _;
+        let mut next = |i: usize| seq
+            .next_element::<u16>()?
+            .ok_or_else(|| A::Error::invalid_length(i, &self));
+        let page = next(0)?;
+        let line = next(1)?;
+        let column = next(2)?;
+        Ok(PositionMapKey::Position(Position { page, line, column }))
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Group paths
+////////////////////////////////////////////////////////////////////////////////
+
+/// Returns true if the group path is within the group tree rooted at `root`.
+/// If `recursive` is false, only the root and its immediate child groups are
+/// within the tree.
+pub(in crate) fn is_in_group_tree(path: &str, root: &str, recursive: bool)
+    -> bool
+{
+    if path == root { return true; }
+    match path
+        .strip_prefix(root)
+        .and_then(|rest| rest.strip_prefix(REF_GROUP_PATH_TOKEN))
+    {
+        Some(rest) => recursive || !rest.contains(REF_GROUP_PATH_TOKEN),
+        None       => false,
+    }
+}
+
+/// Returns an error if the group path has empty or wildcard segments.
+fn validate_group_path(path: &str) -> Result<(), PaletteError> {
+    if path
+        .split(REF_GROUP_PATH_TOKEN)
+        .any(|segment| segment.is_empty() || segment.contains('*'))
+    {
+        Err(PaletteError::InvalidInputValue {
+            msg: format!("invalid group path '{}'", path).into(),
+        })
+    } else {
+        Ok(())
+    }
+}
@@ -0,0 +1,366 @@
+////////////////////////////////////////////////////////////////////////////////
+// Atma structured color palette
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Palette layout and naming constraints.
+////////////////////////////////////////////////////////////////////////////////
+
+// Local imports.
+use crate::cell::Position;
+use crate::cell::PositionSelector;
+use crate::error::PaletteError;
+
+// External library imports.
+use serde::Deserialize;
+use serde::Serialize;
+
+// Standard library imports.
+use std::str::FromStr;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Constraints
+////////////////////////////////////////////////////////////////////////////////
+/// Limits on the positions, names, and number of cells in a palette.
+///
+/// Page, line, and column limits are inclusive: a palette with 16 columns has
+/// a `max_column` of 15.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Constraints {
+    /// The largest permitted page number.
+    pub max_page: Option<u16>,
+    /// The largest permitted line number.
+    pub max_line: Option<u16>,
+    /// The largest permitted column number.
+    pub max_column: Option<u16>,
+    /// The maximum number of cells in the palette.
+    pub max_cells: Option<u32>,
+    /// The maximum number of assigned positions in the palette.
+    pub max_positions: Option<u32>,
+    /// The maximum length of an assigned name.
+    pub max_name_length: Option<u32>,
+    /// The rule assigned names must follow.
+    pub name_rule: Option<NameRule>,
+}
+
+impl Constraints {
+    /// The names of the constraint fields.
+    pub const FIELDS: [&'static str; 7] = [
+        "max_page",
+        "max_line",
+        "max_column",
+        "max_cells",
+        "max_positions",
+        "max_name_length",
+        "name_rule",
+    ];
+
+    /// Returns true if no constraints are set.
+    pub fn is_empty(&self) -> bool {
+        *self == Constraints::default()
+    }
+
+    /// Returns the value of the constraint with the given name, or `None` if
+    /// it is not set.
+    pub fn get(&self, key: &str) -> Option<String> {
+        match key {
+            "max_page"        => self.max_page.map(|v| v.to_string()),
+            "max_line"        => self.max_line.map(|v| v.to_string()),
+            "max_column"      => self.max_column.map(|v| v.to_string()),
+            "max_cells"       => self.max_cells.map(|v| v.to_string()),
+            "max_positions"   => self.max_positions.map(|v| v.to_string()),
+            "max_name_length" => self.max_name_length.map(|v| v.to_string()),
+            "name_rule"       => self.name_rule.map(|v| v.to_string()),
+            _                 => None,
+        }
+    }
+
+    /// Sets the constraint with the given name. If no value is provided, the
+    /// constraint is removed.
+    ///
+    /// ### Errors
+    ///
+    /// Returns a `PaletteError` if the constraint name or value is invalid.
+    pub fn set(&mut self, key: &str, value: Option<&str>)
+        -> Result<(), PaletteError>
+    {
+        match key {
+            "max_page"        => self.max_page = parse_value(key, value)?,
+            "max_line"        => self.max_line = parse_value(key, value)?,
+            "max_column"      => self.max_column = parse_value(key, value)?,
+            "max_cells"       => self.max_cells = parse_value(key, value)?,
+            "max_positions"   => self.max_positions = parse_value(key, value)?,
+            "max_name_length" => self.max_name_length
+                = parse_value(key, value)?,
+            "name_rule"       => self.name_rule = parse_value(key, value)?,
+            _ => return Err(PaletteError::InvalidInputValue {
+                msg: format!("unknown palette constraint '{}': expected one \
+                    of {}", key, Constraints::FIELDS.join(", ")).into(),
+            }),
+        }
+        Ok(())
+    }
+
+    /// Returns an iterator over the names and values of all set constraints.
+    pub fn entries(&self) -> impl Iterator<Item=(&'static str, String)> + '_ {
+        Constraints::FIELDS
+            .iter()
+            .filter_map(move |k| self.get(k).map(|v| (*k, v)))
+    }
+
+    /// Returns true if the given position lies within the page, line, and
+    /// column limits.
+    pub fn contains(&self, position: &Position) -> bool {
+        self.check_position(position).is_ok()
+    }
+
+    /// Returns the next position after the given one which lies within the
+    /// page, line, and column limits, wrapping to zero if there is none.
+    pub fn wrapping_succ(&self, position: Position) -> Position {
+        let max_page = self.max_page.unwrap_or(u16::MAX);
+        let max_line = self.max_line.unwrap_or(u16::MAX);
+        let max_column = self.max_column.unwrap_or(u16::MAX);
+        let Position { page, line, column } = position;
+
+        let next_page = || if page < max_page {
+            Position { page: page + 1, line: 0, column: 0 }
+        } else {
+            Position::ZERO
+        };
+
+        if page > max_page {
+            Position::ZERO
+        } else if line > max_line {
+            next_page()
+        } else if column < max_column {
+            Position { page, line, column: column + 1 }
+        } else if line < max_line {
+            Position { page, line: line + 1, column: 0 }
+        } else {
+            next_page()
+        }
+    }
+
+    /// Checks that the given position lies within the page, line, and column
+    /// limits.
+    pub fn check_position(&self, position: &Position)
+        -> Result<(), PaletteError>
+    {
+        check_limit("max_page", "page", position.page, self.max_page,
+            || position.to_string())?;
+        check_limit("max_line", "line", position.line, self.max_line,
+            || position.to_string())?;
+        check_limit("max_column", "column", position.column, self.max_column,
+            || position.to_string())
+    }
+
+    /// Checks that the given position selector lies within the page, line, and
+    /// column limits.
+    pub fn check_selector(&self, selector: &PositionSelector)
+        -> Result<(), PaletteError>
+    {
+        if let Some(page) = selector.page {
+            check_limit("max_page", "page", page, self.max_page,
+                || selector.to_string())?;
+        }
+        if let Some(line) = selector.line {
+            check_limit("max_line", "line", line, self.max_line,
+                || selector.to_string())?;
+        }
+        if let Some(column) = selector.column {
+            check_limit("max_column", "column", column, self.max_column,
+                || selector.to_string())?;
+        }
+        Ok(())
+    }
+
+    /// Checks that the given name follows the naming constraints.
+    pub fn check_name(&self, name: &str) -> Result<(), PaletteError> {
+        if let Some(max) = self.max_name_length {
+            let len = name.chars().count();
+            if len > max as usize {
+                return Err(PaletteError::ConstraintViolation {
+                    constraint: "max_name_length",
+                    msg: format!("name \"{}\" has {} characters; at most {} \
+                        are allowed", name, len, max).into(),
+                });
+            }
+        }
+        match self.name_rule {
+            Some(rule) if !rule.matches(name) => Err(
+                PaletteError::ConstraintViolation {
+                    constraint: "name_rule",
+                    msg: format!("name \"{}\" is not {}",
+                        name,
+                        rule.description()).into(),
+                }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks that a palette with the given number of cells does not exceed
+    /// the cell limit.
+    pub fn check_cell_count(&self, count: usize) -> Result<(), PaletteError> {
+        match self.max_cells {
+            Some(max) if count > max as usize => Err(
+                PaletteError::ConstraintViolation {
+                    constraint: "max_cells",
+                    msg: format!("palette would have {} cells; at most {} are \
+                        allowed", count, max).into(),
+                }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks that a palette with the given number of assigned positions does
+    /// not exceed the position limit.
+    pub fn check_position_count(&self, count: usize)
+        -> Result<(), PaletteError>
+    {
+        match self.max_positions {
+            Some(max) if count > max as usize => Err(
+                PaletteError::ConstraintViolation {
+                    constraint: "max_positions",
+                    msg: format!("palette would have {} assigned positions; at \
+                        most {} are allowed", count, max).into(),
+                }),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Parses an optional constraint value.
+fn parse_value<T>(key: &str, value: Option<&str>)
+    -> Result<Option<T>, PaletteError>
+    where T: FromStr
+{
+    match value {
+        Some(value) => value
+            .parse()
+            .map(Some)
+            .map_err(|_| PaletteError::InvalidInputValue {
+                msg: format!("invalid value '{}' for palette constraint '{}'",
+                    value,
+                    key).into(),
+            }),
+        None => Ok(None),
+    }
+}
+
+/// Checks a position component against its limit.
+fn check_limit<F>(
+    constraint: &'static str,
+    component: &str,
+    value: u16,
+    max: Option<u16>,
+    target: F)
+    -> Result<(), PaletteError>
+    where F: FnOnce() -> String
+{
+    match max {
+        Some(max) if value > max => Err(PaletteError::ConstraintViolation {
+            constraint,
+            msg: format!("{} {} of {} exceeds the maximum {} {}",
+                component,
+                value,
+                (target)(),
+                component,
+                max).into(),
+        }),
+        _ => Ok(()),
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// NameRule
+////////////////////////////////////////////////////////////////////////////////
+/// A rule for validating assigned names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Serialize, Deserialize)]
+pub enum NameRule {
+    /// ASCII letters, digits, and underscores, not starting with a digit.
+    Identifier,
+    /// Lowercase ASCII letters, digits, and underscores.
+    SnakeCase,
+    /// Lowercase ASCII letters, digits, and hyphens.
+    KebabCase,
+}
+
+impl NameRule {
+    /// Returns true if the given name follows the rule.
+    pub fn matches(&self, name: &str) -> bool {
+        if name.is_empty() { return false; }
+        match self {
+            NameRule::Identifier => name
+                .chars()
+                .enumerate()
+                .all(|(i, c)| c == '_' ||
+                    c.is_ascii_alphabetic() ||
+                    (i > 0 && c.is_ascii_digit())),
+            NameRule::SnakeCase => name
+                .chars()
+                .all(|c| c == '_' ||
+                    c.is_ascii_lowercase() ||
+                    c.is_ascii_digit()),
+            NameRule::KebabCase => name
+                .chars()
+                .all(|c| c == '-' ||
+                    c.is_ascii_lowercase() ||
+                    c.is_ascii_digit()),
+        }
+    }
+
+    /// Returns a description of the names accepted by the rule.
+    fn description(&self) -> &'static str {
+        match self {
+            NameRule::Identifier => "an identifier (ASCII letters, digits, \
+                and underscores, not starting with a digit)",
+            NameRule::SnakeCase => "snake case (lowercase ASCII letters, \
+                digits, and underscores)",
+            NameRule::KebabCase => "kebab case (lowercase ASCII letters, \
+                digits, and hyphens)",
+        }
+    }
+}
+
+impl std::fmt::Display for NameRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NameRule::Identifier => write!(f, "identifier"),
+            NameRule::SnakeCase  => write!(f, "snake_case"),
+            NameRule::KebabCase  => write!(f, "kebab_case"),
+        }
+    }
+}
+
+impl FromStr for NameRule {
+    type Err = InvalidNameRule;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "identifier" => Ok(NameRule::Identifier),
+            "snake_case" => Ok(NameRule::SnakeCase),
+            "kebab_case" => Ok(NameRule::KebabCase),
+            _            => Err(InvalidNameRule),
+        }
+    }
+}
+
+/// Error type for an invalid name rule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct InvalidNameRule;
+
+impl std::fmt::Display for InvalidNameRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid name rule: expected one of 'identifier', \
+            'snake_case', or 'kebab_case'")
+    }
+}
+
+impl std::error::Error for InvalidNameRule {}
@@ -69,6 +69,46 @@ impl Expr {
             Expr::Blend(blend_expr) => blend_expr.color(basic, index_list),
         }
     }
+
+    /// Returns true if any of the Expr's cell references are relative.
+    pub fn has_relative_refs(&self) -> bool {
+        let is_relative = |cell_ref: &CellRef<'_>| match cell_ref {
+            CellRef::Relative(_) => true,
+            _                    => false,
+        };
+        match self {
+            Expr::Empty    |
+            Expr::Color(_) => false,
+
+            Expr::Reference(cell_ref) => is_relative(cell_ref),
+
+            Expr::Blend(blend_expr) => match &blend_expr.blend_fn {
+                BlendFunction::Unary(unary) => is_relative(&unary.arg),
+                BlendFunction::Binary(binary) => is_relative(&binary.arg_0)
+                    || is_relative(&binary.arg_1),
+            },
+        }
+    }
+
+    /// Calls the given function on each of the Expr's cell references.
+    pub fn map_cell_refs<F>(&mut self, mut f: F)
+        where F: FnMut(&mut CellRef<'static>)
+    {
+        match self {
+            Expr::Empty    |
+            Expr::Color(_) => (),
+
+            Expr::Reference(cell_ref) => (f)(cell_ref),
+
+            Expr::Blend(blend_expr) => match &mut blend_expr.blend_fn {
+                BlendFunction::Unary(unary) => (f)(&mut unary.arg),
+                BlendFunction::Binary(binary) => {
+                    (f)(&mut binary.arg_0);
+                    (f)(&mut binary.arg_1);
+                },
+            },
+        }
+    }
 }
 
 impl Default for Expr {
@@ -686,9 +726,36 @@ impl std::fmt::Display for BinaryBlendMethod {
 pub enum ColorSpace {
     /// RGB color space.
     Rgb,
+    /// Linear-light RGB color space.
+    LinearRgb,
 }
 
 impl ColorSpace {
+    /// Returns the channels of the given color in the color space.
+    pub fn components<C>(&self, color: C) -> [f32; 3]
+        where C: Into<Color> + Sized,
+    {
+        match self {
+            ColorSpace::Rgb => color.into().rgb_ratios(),
+            ColorSpace::LinearRgb => {
+                let [r, g, b] = color.into().rgb_ratios();
+                [srgb_decode(r), srgb_decode(g), srgb_decode(b)]
+            },
+        }
+    }
+
+    /// Returns the color with the given channels in the color space.
+    pub fn color_from_components(&self, components: [f32; 3]) -> Color {
+        match self {
+            ColorSpace::Rgb => Rgb::from(components).into(),
+            ColorSpace::LinearRgb => {
+                let [r, g, b] = components;
+                Rgb::from([srgb_encode(r), srgb_encode(g), srgb_encode(b)])
+                    .into()
+            },
+        }
+    }
+
     /// Applies the given binary closure to the channels of the given colors.
     pub fn map_channels_binary<A, B, F>(&self, a: A, b: B, f: F) -> Color
         where
@@ -696,17 +763,31 @@ impl ColorSpace {
             B: Into<Color> + Sized,
             F: Fn(f32, f32) -> f32,
     {
-        match self {
-            ColorSpace::Rgb => {
-                let [ra, ga, ba] = a.into().rgb_ratios();
-                let [rb, gb, bb] = b.into().rgb_ratios();
-                Rgb::from([
-                    (f)(ra, rb),
-                    (f)(ga, gb),
-                    (f)(ba, bb),
-                ]).into()
-            },
-        }
+        let [ra, ga, ba] = self.components(a);
+        let [rb, gb, bb] = self.components(b);
+        self.color_from_components([
+            (f)(ra, rb),
+            (f)(ga, gb),
+            (f)(ba, bb),
+        ])
+    }
+}
+
+/// Converts a gamma-encoded sRGB channel value into a linear-light value.
+fn srgb_decode(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Converts a linear-light channel value into a gamma-encoded sRGB value.
+fn srgb_encode(c: f32) -> f32 {
+    if c <= 0.003_130_8 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
     }
 }
 
@@ -743,7 +824,8 @@ impl std::str::FromStr for ColorSpace {
 impl std::fmt::Display for ColorSpace {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.write_str(match self {
-            ColorSpace::Rgb => "RGB",
+            ColorSpace::Rgb       => "rgb",
+            ColorSpace::LinearRgb => "linear_rgb",
         })
     }
 }
@@ -943,7 +1025,25 @@ impl InterpolateFunction {
                     *m0,
                     *m1,
                     amount)
-                .into()
+                .into(),
+
+            // Linear-light channels are interpolated as if they were RGB
+            // ratios, then re-encoded.
+            (LinearRgb, Linear) => LinearRgb.color_from_components(
+                Color::from(Color::rgb_linear_interpolate(
+                        Rgb::from(LinearRgb.components(a)).into(),
+                        Rgb::from(LinearRgb.components(b)).into(),
+                        amount))
+                    .rgb_ratios()),
+
+            (LinearRgb, Cubic(m0, m1)) => LinearRgb.color_from_components(
+                Color::from(Color::rgb_cubic_interpolate(
+                        Rgb::from(LinearRgb.components(a)).into(),
+                        Rgb::from(LinearRgb.components(b)).into(),
+                        *m0,
+                        *m1,
+                        amount))
+                    .rgb_ratios()),
         }
     }
 }
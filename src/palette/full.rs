@@ -15,32 +15,48 @@ use crate::cell::CellRef;
 use crate::cell::CellSelection;
 use crate::cell::Position;
 use crate::cell::PositionSelector;
+use crate::command::ConflictPolicy;
 use crate::command::CursorBehavior;
 use crate::command::HistorySetOption;
 use crate::command::Positioning;
+use crate::command::ShiftMode;
+use crate::command::SortKey;
 use crate::error::FileError;
 use crate::error::FileErrorContext as _;
 use crate::error::PaletteError;
+use crate::format::Format;
 use crate::palette::BasicPalette;
+use crate::palette::Constraints;
+use crate::palette::EntryInfo;
 use crate::palette::History;
 use crate::palette::InsertExpr;
+use crate::palette::Journal;
+use crate::palette::JournalRecord;
+use crate::palette::Library;
+use crate::palette::Metadata;
 use crate::palette::Operation;
+use crate::palette::Transaction;
+use crate::palette::is_in_group_tree;
 use crate::setup::LoadStatus;
+use crate::utility::current_timestamp;
 
 // External library imports.
 use serde::Deserialize;
 use serde::Serialize;
 use ron::ser::PrettyConfig;
-use ron::ser::to_string_pretty;
 
 // Standard library imports.
 use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::convert::TryFrom;
+use std::convert::TryInto;
+use std::ffi::OsString;
 use std::fmt::Debug;
 use std::fs::File;
 use std::fs::OpenOptions;
-use std::io::Read;
-use std::io::Write;
 use std::path::Path;
+use std::path::PathBuf;
 
 
 ////////////////////////////////////////////////////////////////////////////////
@@ -57,6 +73,19 @@ pub struct Palette {
     inner: BasicPalette,
     /// The command history for the palette.
     history: Option<History>,
+    /// The pending transaction for the palette.
+    #[serde(default)]
+    transaction: Option<Transaction>,
+    /// The palette metadata.
+    #[serde(default)]
+    metadata: Metadata,
+    /// The sequence number of the last journal record included in the
+    /// palette.
+    #[serde(default)]
+    journal_seq: u64,
+    /// The palette change journal.
+    #[serde(skip)]
+    journal: Journal,
 }
 
 impl Palette {
@@ -66,6 +95,10 @@ impl Palette {
             load_status: LoadStatus::default(),
             inner: BasicPalette::new(),
             history: None,
+            transaction: None,
+            metadata: Metadata::new(),
+            journal_seq: 0,
+            journal: Journal::default(),
         }
     }
 
@@ -100,29 +133,54 @@ impl Palette {
         self.load_status.modified()
     }
 
-    /// Sets the Palette modification flag.
+    /// Sets the Palette modification flag. Setting the flag will also update
+    /// the metadata modification time.
     pub fn set_modified(&mut self, modified: bool) {
+        if modified { self.metadata.touch(); }
         self.load_status.set_modified(modified);
     }
 
     /// Constructs a new `Palette` by parsing data from the file at the given
-    /// path.
+    /// path. The file format is determined by the path's extension. Any
+    /// changes recorded in the palette's journal file are replayed.
     pub fn read_from_path<P>(path: P) -> Result<Self, FileError>
         where P: AsRef<Path> + Debug
+    {
+        let format = Format::for_path(path.as_ref());
+        Palette::read_from_path_with_format(path, format)
+    }
+
+    /// Constructs a new `Palette` by parsing data in the given format from the
+    /// file at the given path. Any changes recorded in the palette's journal
+    /// file are replayed.
+    pub fn read_from_path_with_format<P>(path: P, format: Format)
+        -> Result<Self, FileError>
+        where P: AsRef<Path> + Debug
     {
         let path = path.as_ref();
         let mut file = OpenOptions::new()
             .read(true)
             .open(path)
             .with_context(|| format!("Failed to open file {:?}", path))?;
-        let mut palette = Palette::read_from_file(&mut file)?;
+        let mut palette = Palette::read_from_file(&mut file, format)?;
         palette.load_status.set_load_path(path);
+        palette.replay_journal(path)?;
         Ok(palette)
     }
 
-    /// Writes the `Palette` to the file at the given path.
+    /// Writes the `Palette` to the file at the given path. The file format is
+    /// determined by the path's extension.
     pub fn write_to_path<P>(&self, path: P) -> Result<(), FileError>
         where P: AsRef<Path> + Debug
+    {
+        let format = Format::for_path(path.as_ref());
+        self.write_to_path_with_format(path, format)
+    }
+
+    /// Writes the `Palette` in the given format to the file at the given path.
+    pub fn write_to_path_with_format<P>(&self, path: P, format: Format)
+        -> Result<(), FileError>
+        where P: AsRef<Path> + Debug
     {
         let path = path.as_ref();
         let mut file = OpenOptions::new()
@@ -133,7 +191,7 @@ impl Palette {
             .with_context(|| format!(
                 "Failed to create/open file {:?} for writing",
                 path))?;
-        self.write_to_file(&mut file)
+        self.write_to_file(&mut file, format)
     }
 
     /// Writes the `Palette` to a new file at the given path.
@@ -147,72 +205,355 @@ impl Palette {
             .create_new(true)
             .open(path)
             .with_context(|| format!("Failed to create file {:?}.", path))?;
-        self.write_to_file(&mut file)
+        self.write_to_file(&mut file, Format::for_path(path))
+    }
+
+    /// Replaces the file at the given path with the `Palette`. The palette is
+    /// written and synced to a temporary file in the same directory, which is
+    /// then renamed over the file, so that the file is never left partially
+    /// written.
+    fn replace_path(&self, path: &Path) -> Result<(), FileError> {
+        let mut temp_name = path
+            .file_name()
+            .map(OsString::from)
+            .unwrap_or_default();
+        temp_name.push(".tmp");
+        let temp_path = path.with_file_name(temp_name);
+
+        let res = OpenOptions::new()
+            .write(true)
+            .truncate(true)
+            .create(true)
+            .open(&temp_path)
+            .with_context(|| format!(
+                "Failed to create/open file {:?} for writing",
+                temp_path))
+            .and_then(|mut file| {
+                self.write_to_file(&mut file, Format::for_path(path))?;
+                file.sync_all()
+                    .with_context(|| format!(
+                        "Failed to sync file {:?}",
+                        temp_path))
+            })
+            .and_then(|_| std::fs::rename(&temp_path, path)
+                .with_context(|| format!(
+                    "Failed to replace file {:?}",
+                    path)));
+        if res.is_err() {
+            let _ = std::fs::remove_file(&temp_path);
+        }
+        res
     }
 
-    /// Write the `Palette` into the file is was loaded from. Returns true if
-    /// the data was written.
+    /// Write the `Palette` into the file is was loaded from, removing its
+    /// journal file. Returns true if the data was written.
+    ///
+    /// The file is replaced before the journal is removed, so that an
+    /// interruption leaves either the old file and its journal or the new
+    /// file, whose changes would be skipped if the journal were replayed.
     pub fn write_to_load_path(&self) -> Result<bool, FileError> {
         match self.load_path() {
             Some(path) => {
-                self.write_to_path(path)?;
+                self.replace_path(path)?;
+                Journal::remove_path(&Journal::path_for(path))?;
                 Ok(true)
             },
             None => Ok(false)    
         }
     }
 
-    /// Write the `Palette` into a new file using the load path. Returns true if
-    /// the data was written.
+    /// Write the `Palette` into a new file using the load path, removing any
+    /// leftover journal file. Returns true if the data was written.
     pub fn write_to_load_path_if_new(&self) -> Result<bool, FileError> {
         match self.load_path() {
             Some(path) => {
                 self.write_to_path_if_new(path)?;
+                Journal::remove_path(&Journal::path_for(path))?;
                 Ok(true)
             },
             None => Ok(false)    
         }
     }
 
-    /// Constructs a new `Palette` by parsing data from the given file.
-    pub fn read_from_file(file: &mut File) -> Result<Self, FileError> {
-        Palette::parse_ron_from_file(file)
+    /// Writes the changes made to the `Palette` into the file it was loaded
+    /// from. If journaling is enabled, the changes are appended to the
+    /// palette's journal file, unless they can't be recorded or the journal
+    /// is due for compaction, in which case the palette file is rewritten.
+    /// Returns true if the data was written.
+    pub fn write_changes_to_load_path(&mut self) -> Result<bool, FileError> {
+        let path = match self.load_path() {
+            Some(path) => path.to_owned(),
+            None       => return Ok(false),
+        };
+
+        if self.journal.is_enabled() && path.exists() {
+            self.journal.record(JournalRecord::State {
+                position_cursor: self.inner.position_cursor(),
+                modified: self.metadata.modified,
+            });
+            let journal_path = Journal::path_for(&path);
+            if let Some(seq) = self.journal
+                .append_to_path(&journal_path, self.journal_seq)?
+            {
+                self.journal_seq = seq;
+                return Ok(true);
+            }
+            tracing::debug!("Compacting palette journal.");
+        }
+
+        self.journal.reset();
+        self.write_to_load_path()
+    }
+
+    /// Enables or disables the palette change journal. If enabled, the
+    /// journal file is compacted into the palette file once it would hold more
+    /// than the given number of records.
+    pub fn set_journal(&mut self, compact_threshold: Option<usize>) {
+        self.journal.set_compact_threshold(compact_threshold);
     }
 
-    /// Parses a `Palette` from a file using the RON format.
-    fn parse_ron_from_file(file: &mut File) -> Result<Self, FileError> {
-        let len = file.metadata()
-            .context("Failed to read file metadata")?
-            .len();
-        let mut buf = Vec::with_capacity(len as usize);
-        let _ = file.read_to_end(&mut buf)
-            .context("Failed to read palette file")?;
+    /// Replays the records of the journal file for the palette file at the
+    /// given path which are not yet included in the palette.
+    fn replay_journal(&mut self, path: &Path) -> Result<(), FileError> {
+        let journal_path = Journal::path_for(path);
+        let (journal, entries) = Journal::read_from_path(&journal_path)?;
+        self.journal = journal;
+
+        // The recorded operations were checked against the cell locks when
+        // they were first applied.
+        self.inner.set_ignore_locks(true);
+        let mut res = Ok(());
+        for entry in entries {
+            if entry.seq <= self.journal_seq { continue; }
+            res = self.perform_change(&entry.record).map(|_| ());
+            if res.is_err() { break; }
+            self.journal_seq = entry.seq;
+        }
+        self.inner.set_ignore_locks(false);
 
-        use ron::de::Deserializer;
-        let mut d = Deserializer::from_bytes(&buf)
-            .context("Failed deserializing RON file")?;
-        let palette = Palette::deserialize(&mut d)
-            .context("Failed parsing RON file")?;
-        d.end()
-            .context("Failed parsing RON file")?;
-        Ok(palette)
+        res.with_context(|| format!(
+            "Failed replaying journal file {:?}",
+            journal_path))
     }
-    
-    /// Writes the `Palette` to the given file.
-    pub fn write_to_file(&self, file: &mut File) -> Result<(), FileError> {
-        self.generate_ron_into_file(file)
+
+    /// Makes a change to the palette and records it in the journal. Changes
+    /// to the palette's history, transaction, and metadata are all made
+    /// through here, and operations through `apply_operations`, so that
+    /// replaying the journal reproduces them. Returns the number of history
+    /// entries moved through or removed by the change.
+    fn change(&mut self, record: JournalRecord) -> Result<usize, PaletteError> {
+        let dropped = self.dropped_history_count();
+        let count = self.perform_change(&record)?;
+        self.journal_change(record, dropped);
+        Ok(count)
+    }
+
+    /// Records a change in the journal. The given count is the number of
+    /// history entries dropped due to the history limits before the change
+    /// was made.
+    fn journal_change(&mut self, record: JournalRecord, dropped: usize) {
+        // The history limits aren't applied while the journal is replayed, so
+        // changes which drop history entries can't be replayed.
+        if self.dropped_history_count() != dropped {
+            self.journal.invalidate();
+        }
+        self.journal.record(record);
+    }
+
+    /// Returns the number of history entries dropped due to the history
+    /// limits.
+    fn dropped_history_count(&self) -> usize {
+        self.history.as_ref().map_or(0, History::dropped_count)
+    }
+
+    /// Makes a change to the palette without recording it in the journal.
+    /// Returns the number of history entries moved through or removed by the
+    /// change.
+    fn perform_change(&mut self, record: &JournalRecord)
+        -> Result<usize, PaletteError>
+    {
+        use JournalRecord::*;
+        match record {
+            Apply { ops, command, label, timestamp } => {
+                self.set_history_entry_info(command.clone(), label.clone());
+                self.apply_operations_unjournaled(&ops[..])?;
+                if self.transaction.is_none() {
+                    if let Some(history) = self.history.as_mut() {
+                        let id = history.cursor();
+                        history.set_entry_timestamp(id, *timestamp);
+                    }
+                }
+                Ok(0)
+            },
+
+            Undo { count } => match self.history.as_mut() {
+                Some(history) => Ok(self.inner.undo(history, *count)),
+                None          => Ok(0),
+            },
+
+            Redo { count } => match self.history.as_mut() {
+                Some(history) => Ok(self.inner.redo(history, *count)),
+                None          => Ok(0),
+            },
+
+            SetMetadata { key, values } => {
+                self.metadata.set(key, values.clone())?;
+                Ok(0)
+            },
+
+            SetHistoryOption { setting } => {
+                match setting {
+                    HistorySetOption::Clear => {
+                        self.history = Some(History::new());
+                    },
+
+                    HistorySetOption::Enable => if self.history.is_none() {
+                        self.history = Some(History::new());
+                    },
+
+                    HistorySetOption::Disable => {
+                        self.history = None;
+                    },
+                }
+                Ok(0)
+            },
+
+            SetCheckpoint { name } => {
+                self.history
+                    .as_mut()
+                    .ok_or(PaletteError::HistoryDisabled)?
+                    .set_checkpoint(name.clone());
+                Ok(0)
+            },
+
+            MoveHistory { id } => self.move_history_to(*id),
+
+            PruneBranch { branch } => {
+                let history = self.history
+                    .as_mut()
+                    .ok_or(PaletteError::HistoryDisabled)?;
+                if !history.branches().contains(branch) {
+                    return Err(PaletteError::UndefinedBranch {
+                        branch: *branch,
+                    });
+                }
+                if history.current_branch() == *branch {
+                    return Err(PaletteError::CurrentBranch {
+                        branch: *branch,
+                    });
+                }
+                Ok(history.prune_branch(*branch))
+            },
+
+            PruneInactiveBranches => Ok(self.history
+                .as_mut()
+                .ok_or(PaletteError::HistoryDisabled)?
+                .prune_inactive_branches()),
+
+            SquashHistory { from, to } => {
+                self.check_no_transaction()?;
+                self.history
+                    .as_mut()
+                    .ok_or(PaletteError::HistoryDisabled)?
+                    .squash(*from, *to)
+            },
+
+            PruneHistory => {
+                self.check_no_transaction()?;
+                Ok(self.history
+                    .as_mut()
+                    .ok_or(PaletteError::HistoryDisabled)?
+                    .prune())
+            },
+
+            BeginTransaction { label } => {
+                self.check_no_transaction()?;
+                self.transaction = Some(Transaction::new(label.clone()));
+                Ok(0)
+            },
+
+            CommitTransaction { timestamp } => {
+                let (undo_ops, commands, label) = self.transaction
+                    .take()
+                    .ok_or(PaletteError::NoTransaction)?
+                    .into_parts();
+                if let Some(history) = self.history.as_mut() {
+                    if !undo_ops.is_empty() {
+                        let command = if commands.is_empty() {
+                            None
+                        } else {
+                            Some(commands.join("; "))
+                        };
+                        let label = label.or_else(|| history
+                            .next_entry_info()
+                            .label
+                            .clone());
+                        history.push_entry(undo_ops, command, label);
+                        let id = history.cursor();
+                        history.set_entry_timestamp(id, *timestamp);
+                    }
+                }
+                Ok(0)
+            },
+
+            RollbackTransaction => {
+                let (undo_ops, _, _) = self.transaction
+                    .take()
+                    .ok_or(PaletteError::NoTransaction)?
+                    .into_parts();
+                self.inner.revert_operations(&undo_ops);
+                Ok(0)
+            },
+
+            RollbackToSavepoint { savepoint } => {
+                let undo_ops = self.transaction
+                    .as_mut()
+                    .ok_or(PaletteError::NoTransaction)?
+                    .take_since(*savepoint);
+                self.inner.revert_operations(&undo_ops);
+                Ok(0)
+            },
+
+            State { position_cursor, modified } => {
+                let _ = self.inner.set_position_cursor(*position_cursor);
+                self.metadata.modified = *modified;
+                Ok(0)
+            },
+        }
+    }
+
+    /// Constructs a new `Palette` by parsing data in the given format from the
+    /// given file.
+    pub fn read_from_file(file: &mut File, format: Format)
+        -> Result<Self, FileError>
+    {
+        format.read_from_file(file)
     }
 
-    /// Generates a RON formatted `Palette` by serializing into the given file.
-    fn generate_ron_into_file(&self, file: &mut File) -> Result<(), FileError> {
+    /// Writes the `Palette` to the given file in the given format.
+    pub fn write_to_file(&self, file: &mut File, format: Format)
+        -> Result<(), FileError>
+    {
         tracing::debug!("Serializing & writing Palette file.");
-        let pretty = PrettyConfig::new()
-            .with_depth_limit(3)
-            .with_extensions(ron::extensions::Extensions::IMPLICIT_SOME);
-        let s = to_string_pretty(self, pretty)?;
+        format.write_to_file(self, Palette::ron_pretty_config(), file)
+    }
+
+    /// Constructs a new `Palette` by parsing the given data in the given
+    /// format.
+    pub fn parse(buf: &[u8], format: Format) -> Result<Self, FileError> {
+        format.parse(buf)
+    }
+
+    /// Returns the serialized data for the `Palette` in the given format.
+    pub fn generate(&self, format: Format) -> Result<Vec<u8>, FileError> {
+        format.generate(self, Palette::ron_pretty_config())
+    }
 
-        file.write_all(s.as_bytes())
-            .map_err(FileError::from)
+    /// Returns the RON formatting options for palette files.
+    fn ron_pretty_config() -> PrettyConfig {
+        PrettyConfig::new()
+            .with_depth_limit(3)
+            .with_extensions(ron::extensions::Extensions::IMPLICIT_SOME)
     }
 
     ////////////////////////////////////////////////////////////////////////////
@@ -231,9 +572,21 @@ impl Palette {
     pub fn cell_mut<'name>(&mut self, cell_ref: &CellRef<'name>)
         -> Result<&mut Cell, PaletteError>
     {
+        self.journal.invalidate();
         self.inner.cell_mut(cell_ref)
     }
 
+    /// Returns a reference to the palette `Metadata`.
+    pub fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    /// Returns a mutable reference to the palette `Metadata`.
+    pub fn metadata_mut(&mut self) -> &mut Metadata {
+        self.journal.invalidate();
+        &mut self.metadata
+    }
+
     #[allow(unused)] // TODO: Remove this.
     /// Returns a reference to the inner `BasicPalette`.
     pub(in crate) fn inner(&self) -> &BasicPalette {
@@ -242,41 +595,90 @@ impl Palette {
 
     /// Returns a `mut` reference to the inner `BasicPalette`.
     pub(in crate) fn inner_mut(&mut self) -> &mut BasicPalette {
+        self.journal.invalidate();
         &mut self.inner
     }
 
+    /// Consumes the `Palette`, returning the inner `BasicPalette`.
+    pub(in crate) fn into_inner(self) -> BasicPalette {
+        self.inner
+    }
+
+    /// Sets up the palette libraries used to resolve external references,
+    /// using the given fallback aliases. Library paths assigned in the palette
+    /// are resolved relative to the palette's load path.
+    pub fn load_libraries(&mut self, aliases: BTreeMap<String, PathBuf>) {
+        let base_dir = self.load_path().and_then(Path::parent);
+        let library = Library::new(aliases).with_base_dir(base_dir);
+        self.inner.set_library(library);
+    }
+
     ////////////////////////////////////////////////////////////////////////////
     // Commands
     ////////////////////////////////////////////////////////////////////////////
     
     /// Inserts the given color expression objects into the palette.
+    ///
+    /// If a `ShiftMode` is given, the cells following the insert position are
+    /// shifted forward to make room for the inserted cells. Otherwise, the
+    /// inserted cells skip over any occupied positions.
     pub fn insert_exprs<'name, S>(
         &mut self,
         insert_exprs: &[InsertExpr],
         name: Option<S>,
         positioning: Positioning,
-        cursor_behavior: CursorBehavior)
+        cursor_behavior: CursorBehavior,
+        shift: Option<ShiftMode>)
         -> Result<(), PaletteError>
         where S: ToString
     {
         use Operation::*;
+        let mut ops = Vec::with_capacity(insert_exprs.len() * 2);
         // Get start index.
         let mut idx = self.inner
             .unoccupied_index_or_next(0)
             .expect("no free indices"); // TODO: Handle with an error.
         // Get start position.
         let mut next = match positioning {
-            Positioning::Position(p) => p,
+            Positioning::Position(p) => {
+                self.inner.constraints().check_position(&p)?;
+                p
+            },
             Positioning::Open => Position::ZERO,
             Positioning::Cursor => self.inner.position_cursor(),
             Positioning::None => Position::ZERO,
         };
-        if !positioning.is_none() {
-            next = self.inner
-                .unoccupied_position_or_next(next)
-                .expect("no free positions"); // TODO: Handle with an error.
+        match shift {
+            _ if positioning.is_none() => (),
+
+            Some(mode) => {
+                // Open a gap for the inserted cells.
+                let count = insert_exprs
+                    .iter()
+                    .filter_map(|e| e.exprs(&self.inner).ok())
+                    .map(|exprs| exprs.len())
+                    .sum::<usize>();
+                let mut layout = self.inner.assigned_positions().collect();
+                shift_layout(
+                    &mut layout,
+                    next,
+                    count as i64,
+                    mode,
+                    self.inner.constraints())?;
+                ops.extend(self.layout_ops(&layout)?);
+            },
+
+            None => {
+                next = self.inner
+                    .unoccupied_position_or_next(next)
+                    .ok_or(PaletteError::AllPositionsAssigned)?;
+            },
         }
         let start_position = next.clone();
+        // Track wrapping so that positions assigned by this insert are not
+        // reused. The error is deferred until another position is needed.
+        let mut wrapped = false;
+        let mut next_error = None;
 
         // Convert name into proper format.
         let name: Option<Cow<'static, str>> = name
@@ -284,7 +686,7 @@ impl Palette {
 
 
         let mut name_used = false;
-        let mut ops = Vec::with_capacity(insert_exprs.len() * 2);
+        let mut inserted = 0;
         for insert_expr in insert_exprs {
             for exprs in insert_expr.exprs(&self.inner) {
                 for expr in exprs {
@@ -293,9 +695,11 @@ impl Palette {
                         idx,
                         cell: Cell::new_with_expr(expr),
                     });
+                    inserted += 1;
 
                     // Assign position.
                     if !positioning.is_none() {
+                        if let Some(e) = next_error.take() { return Err(e); }
                         ops.push(AssignPosition {
                             cell_ref: CellRef::Index(idx),
                             position: next.clone(),
@@ -318,14 +722,35 @@ impl Palette {
                         .expect("no free indices"); 
 
                     // Shift to next position.
-                    if !positioning.is_none() {
-                        next = self.inner
-                            .unoccupied_position_or_next(next.wrapping_succ())
-                            .expect("no free positions");
+                    if positioning.is_none() { continue; }
+                    if let Some(mode) = shift {
+                        match shift_position(
+                            next,
+                            1,
+                            mode,
+                            self.inner.constraints())
+                        {
+                            Ok(succ) => next = succ,
+                            Err(e)   => next_error = Some(e),
+                        }
+                    } else {
+                        let succ = self.inner
+                            .unoccupied_position_or_next(self.inner
+                                .constraints()
+                                .wrapping_succ(next))
+                            .ok_or(PaletteError::AllPositionsAssigned)?;
+                        wrapped = wrapped || succ <= next;
+                        if wrapped && succ >= start_position {
+                            next_error = Some(
+                                PaletteError::AllPositionsAssigned);
+                        }
+                        next = succ;
                     }
                 }
             }
         }
+        self.inner.constraints()
+            .check_cell_count(self.inner.cell_count() + inserted)?;
 
         match name {
             Some(name) if !name_used => ops.push(AssignName {
@@ -353,7 +778,13 @@ impl Palette {
                 },
             }
         }
-        self.apply_operations(&ops[..])
+        // Shifted cells have been checked for locks, but the position
+        // references to them may be held by locked cells.
+        if shift.is_some() {
+            self.apply_operations_ignoring_locks(&ops[..])
+        } else {
+            self.apply_operations(&ops[..])
+        }
     }
 
     /// Deletes the selected cells from the palette.
@@ -415,22 +846,566 @@ impl Palette {
         self.apply_operations(&ops[..])
     }
 
+    /// Duplicates the selected cells along with their expressions, names,
+    /// groups, metadata, and variant overrides.
+    ///
+    /// For an explicit or cursor positioning, the copies keep the layout of the
+    /// selection, with the corner of its bounds placed at the given position.
+    /// For open positioning, the copies are placed in the next open positions.
+    /// References to selected cells within the copied expressions are remapped
+    /// to the copies, while references to other cells are unchanged. Copied
+    /// names are made unique by appending the given suffix.
+    pub fn duplicate_selection<'name>(
+        &mut self,
+        selection: CellSelection<'name>,
+        positioning: Positioning,
+        suffix: &str,
+        cursor_behavior: CursorBehavior)
+        -> Result<(), PaletteError>
+    {
+        use Operation::*;
+        let index_selection = selection.resolve(self.inner());
+        if index_selection.is_empty() { return Ok(()); }
+
+        // Allocate indices for the copies.
+        let mut index_map = BTreeMap::new();
+        let mut next_idx = 0;
+        for &idx in index_selection.iter() {
+            let new = self.inner
+                .unoccupied_index_or_next(next_idx)
+                .expect("no free indices");
+            let _ = index_map.insert(idx, new);
+            next_idx = new.wrapping_add(1);
+        }
+
+        // Allocate positions for the copies.
+        let old_positions: Vec<(u32, Position)> = index_selection
+            .iter()
+            .filter_map(|idx| self.inner
+                .assigned_position(&CellRef::Index(*idx))
+                .map(|p| (*idx, *p)))
+            .collect();
+        let mut position_map = BTreeMap::new();
+        let target = match positioning {
+            Positioning::Position(p) => Some(p),
+            Positioning::Cursor      => Some(self.inner.position_cursor()),
+            Positioning::Open        => None,
+            Positioning::None        => None,
+        };
+        if let Some(target) = target {
+            let anchor = Position {
+                page: old_positions.iter().map(|(_, p)| p.page).min()
+                    .unwrap_or(0),
+                line: old_positions.iter().map(|(_, p)| p.line).min()
+                    .unwrap_or(0),
+                column: old_positions.iter().map(|(_, p)| p.column).min()
+                    .unwrap_or(0),
+            };
+            for (idx, p) in old_positions.iter() {
+                let position = match (
+                    target.page.checked_add(p.page - anchor.page),
+                    target.line.checked_add(p.line - anchor.line),
+                    target.column.checked_add(p.column - anchor.column))
+                {
+                    (Some(page), Some(line), Some(column))
+                        => Position { page, line, column },
+                    _ => return Err(PaletteError::AllPositionsAssigned),
+                };
+                self.inner.constraints().check_position(&position)?;
+                if self.inner.is_occupied_position(&position) {
+                    return Err(PaletteError::InvalidInputValue {
+                        msg: format!("position {} is already occupied",
+                            position).into(),
+                    });
+                }
+                let _ = position_map.insert(*idx, position);
+            }
+        } else if !positioning.is_none() {
+            let mut next = Position::ZERO;
+            for (idx, _) in old_positions.iter() {
+                let position = self.inner
+                    .unoccupied_position_or_next(next)
+                    .ok_or(PaletteError::AllPositionsAssigned)?;
+                let _ = position_map.insert(*idx, position);
+                next = self.inner.constraints().wrapping_succ(position);
+            }
+        }
+
+        // Generate unique names for the copies of named cells.
+        let mut name_map: BTreeMap<u32, Cow<'static, str>> = BTreeMap::new();
+        for (idx, _) in position_map.iter() {
+            let old = match self.inner.assigned_name(&CellRef::Index(*idx)) {
+                Some(name) => name,
+                None       => continue,
+            };
+            let mut name = format!("{}{}", old, suffix);
+            let mut n = 2;
+            while self.inner.is_assigned_name(&name) ||
+                name_map.values().any(|v| *v == name)
+            {
+                name = format!("{}{}{}", old, suffix, n);
+                n += 1;
+            }
+            let _ = name_map.insert(*idx, name.into());
+        }
+
+        // Determine the group indices of the copies, which are appended to
+        // the groups of the originals.
+        let mut group_map: BTreeMap<(Cow<'static, str>, u32), u32>
+            = BTreeMap::new();
+        let mut group_lens: BTreeMap<&str, usize> = BTreeMap::new();
+        for &idx in index_selection.iter() {
+            for (group, members) in self.inner.groups() {
+                if !members.contains(&idx) { continue; }
+                let len = group_lens.entry(group).or_insert(members.len());
+                let _ = group_map.insert(
+                    (group.to_owned().into(), idx),
+                    (*len).try_into().expect("convert usize to u32"));
+                *len += 1;
+            }
+        }
+
+        let inner = &self.inner;
+        let remap = |expr: &Expr| {
+            let mut expr = expr.clone();
+            expr.map_cell_refs(|cell_ref| {
+                let idx = match cell_ref {
+                    CellRef::External { .. } => return,
+                    _ => match inner.resolve_ref_to_index(cell_ref) {
+                        Ok(idx) => idx,
+                        Err(_)  => return,
+                    },
+                };
+                let new = match index_map.get(&idx) {
+                    Some(new) => *new,
+                    None      => return,
+                };
+                let new_ref = match cell_ref {
+                    CellRef::Name(_) => name_map
+                        .get(&idx)
+                        .map(|name| CellRef::Name(name.clone())),
+                    CellRef::Position(_) => position_map
+                        .get(&idx)
+                        .map(|position| CellRef::Position(*position)),
+                    CellRef::Group { group, .. } => group_map
+                        .get(&(group.clone(), idx))
+                        .map(|group_idx| CellRef::Group {
+                            group: group.clone(),
+                            idx: *group_idx,
+                        }),
+                    _ => None,
+                };
+                *cell_ref = new_ref.unwrap_or(CellRef::Index(new));
+            });
+            expr
+        };
+
+        let mut ops = Vec::new();
+        for &idx in index_selection.iter() {
+            let cell_ref = CellRef::Index(idx);
+            let new = index_map[&idx];
+            let new_ref = CellRef::Index(new);
+            ops.push(InsertCell {
+                idx: new,
+                cell: Cell::new_with_expr(remap(inner.cell(&cell_ref)?.expr())),
+            });
+
+            if let Some(position) = position_map.get(&idx) {
+                ops.push(AssignPosition {
+                    cell_ref: new_ref.clone(),
+                    position: *position,
+                });
+            }
+            if let (Some(name), Some(position))
+                = (name_map.get(&idx), position_map.get(&idx))
+            {
+                ops.push(AssignName {
+                    selector: PositionSelector::from(*position),
+                    name: name.clone(),
+                });
+            }
+            for (group, members) in inner.groups() {
+                if members.contains(&idx) {
+                    ops.push(AssignGroup {
+                        cell_ref: new_ref.clone(),
+                        group: group.to_owned().into(),
+                        idx: None,
+                    });
+                }
+            }
+            if let Some(entries) = inner.assigned_metadata(&cell_ref) {
+                for (key, value) in entries.iter() {
+                    ops.push(AssignMetadata {
+                        cell_ref: new_ref.clone(),
+                        key: key.clone(),
+                        value: value.clone(),
+                    });
+                }
+            }
+            for (variant, _, expr) in inner
+                .variant_exprs()
+                .filter(|(_, i, _)| *i == idx)
+            {
+                ops.push(SetVariantExpr {
+                    variant: variant.clone(),
+                    cell_ref: new_ref.clone(),
+                    expr: remap(expr),
+                });
+            }
+        }
+
+        let start = position_map.values().min().copied();
+        let end = position_map.values().max().copied();
+        match (cursor_behavior, start, end) {
+            (CursorBehavior::MoveToStart, Some(position), _)
+                => ops.push(SetPositionCursor { position }),
+
+            (CursorBehavior::MoveAfterEnd, _, Some(position))
+                => ops.push(SetPositionCursor {
+                    position: inner.constraints().wrapping_succ(position),
+                }),
+
+            (CursorBehavior::MoveToOpen, Some(_), _) => {
+                let mut next = Position::ZERO;
+                while let Some(position) = inner
+                    .unoccupied_position_or_next(next)
+                {
+                    if !position_map.values().any(|p| *p == position) {
+                        ops.push(SetPositionCursor { position });
+                        break;
+                    }
+                    next = inner.constraints().wrapping_succ(position);
+                    if next == Position::ZERO { break; }
+                }
+            },
+            _ => (),
+        }
+        self.apply_operations(&ops[..])
+    }
+
+    /// Merges the cells of another palette into the palette along with their
+    /// expressions, names, groups, metadata, variant overrides, and locks.
+    ///
+    /// The merged cells are given fresh indices, and references between them
+    /// are rewritten to match. If a position is given, the merged layout is
+    /// placed with the corner of its bounds at that position. Merged names are
+    /// prefixed with the given prefix. Merged cells whose position or name is
+    /// already in use are resolved according to the given `ConflictPolicy`.
+    pub fn merge_palette(
+        &mut self,
+        other: &BasicPalette,
+        at: Option<Position>,
+        prefix: Option<&str>,
+        on_conflict: ConflictPolicy)
+        -> Result<(), PaletteError>
+    {
+        use Operation::*;
+        let sources: Vec<u32> = other.exprs().map(|(idx, _)| idx).collect();
+        if sources.is_empty() { return Ok(()); }
+
+        // Determine the requested positions and names of the merged cells.
+        let old_positions: Vec<(u32, Position)> = sources
+            .iter()
+            .filter_map(|idx| other
+                .assigned_position(&CellRef::Index(*idx))
+                .map(|p| (*idx, *p)))
+            .collect();
+        let anchor = Position {
+            page: old_positions.iter().map(|(_, p)| p.page).min()
+                .unwrap_or(0),
+            line: old_positions.iter().map(|(_, p)| p.line).min()
+                .unwrap_or(0),
+            column: old_positions.iter().map(|(_, p)| p.column).min()
+                .unwrap_or(0),
+        };
+        let mut position_map: BTreeMap<u32, Position> = BTreeMap::new();
+        for (idx, p) in old_positions.iter() {
+            let position = match at {
+                Some(target) => match (
+                    target.page.checked_add(p.page - anchor.page),
+                    target.line.checked_add(p.line - anchor.line),
+                    target.column.checked_add(p.column - anchor.column))
+                {
+                    (Some(page), Some(line), Some(column))
+                        => Position { page, line, column },
+                    _ => return Err(PaletteError::AllPositionsAssigned),
+                },
+                None => *p,
+            };
+            let _ = position_map.insert(*idx, position);
+        }
+        let mut name_map: BTreeMap<u32, Cow<'static, str>> = BTreeMap::new();
+        for idx in position_map.keys() {
+            if let Some(name) = other.assigned_name(&CellRef::Index(*idx)) {
+                let _ = name_map.insert(*idx,
+                    format!("{}{}", prefix.unwrap_or(""), name).into());
+            }
+        }
+
+        // Resolve conflicts with the existing cells. Merged cells which are
+        // mapped to an existing cell are not inserted.
+        let mut target_map: BTreeMap<u32, u32> = BTreeMap::new();
+        let mut relocated: Vec<u32> = Vec::new();
+        for &idx in sources.iter() {
+            let position_conflict = position_map
+                .get(&idx)
+                .and_then(|p| self.inner.resolve_position_if_occupied(p));
+            let name_conflict = name_map
+                .get(&idx)
+                .map_or(false, |name| self.inner.is_assigned_name(name));
+            if position_conflict.is_none() && !name_conflict { continue; }
+
+            let target = position_conflict.or_else(|| name_map
+                .get(&idx)
+                .and_then(|name| self.inner.resolve_name_if_occupied(name)));
+            match (on_conflict, target) {
+                (ConflictPolicy::Rename, _) => {
+                    if position_conflict.is_some() { relocated.push(idx); }
+                    if name_conflict {
+                        let name = name_map[&idx].clone();
+                        let mut unique = format!("{}_2", name);
+                        let mut n = 3;
+                        while self.inner.is_assigned_name(&unique) ||
+                            name_map.values().any(|v| *v == unique)
+                        {
+                            unique = format!("{}_{}", name, n);
+                            n += 1;
+                        }
+                        let _ = name_map.insert(idx, unique.into());
+                    }
+                },
+                (_, Some(target)) => {
+                    let _ = target_map.insert(idx, target);
+                    let _ = position_map.remove(&idx);
+                    // An overwriting cell's name is moved to the position of
+                    // the cell it replaces.
+                    if on_conflict == ConflictPolicy::Skip ||
+                        position_conflict.is_none()
+                    {
+                        let _ = name_map.remove(&idx);
+                    }
+                },
+                // The name is assigned to an unoccupied position.
+                (ConflictPolicy::Skip, None) => {
+                    let _ = name_map.remove(&idx);
+                },
+                (ConflictPolicy::Overwrite, None) => (),
+            }
+        }
+
+        // Move relocated cells to the next free positions.
+        let mut next = Position::ZERO;
+        for idx in relocated {
+            let _ = position_map.remove(&idx);
+            let position = loop {
+                let position = self.inner
+                    .unoccupied_position_or_next(next)
+                    .ok_or(PaletteError::AllPositionsAssigned)?;
+                next = self.inner.constraints().wrapping_succ(position);
+                if !position_map.values().any(|p| *p == position) {
+                    break position;
+                }
+                if next == Position::ZERO {
+                    return Err(PaletteError::AllPositionsAssigned);
+                }
+            };
+            let _ = position_map.insert(idx, position);
+        }
+
+        // Allocate indices for the inserted cells.
+        let inserted: Vec<u32> = sources
+            .iter()
+            .copied()
+            .filter(|idx| !target_map.contains_key(idx))
+            .collect();
+        self.inner.constraints()
+            .check_cell_count(self.inner.cell_count() + inserted.len())?;
+        let mut index_map = target_map.clone();
+        let mut next_idx = 0;
+        for &idx in inserted.iter() {
+            let new = self.inner
+                .unoccupied_index_or_next(next_idx)
+                .expect("no free indices");
+            let _ = index_map.insert(idx, new);
+            next_idx = new.wrapping_add(1);
+        }
+
+        // Determine the group indices of the merged cells, which are appended
+        // to any existing groups of the same name.
+        let mut group_map: BTreeMap<(Cow<'static, str>, u32), u32>
+            = BTreeMap::new();
+        let mut group_lens: BTreeMap<Cow<'static, str>, usize> = self.inner
+            .groups()
+            .map(|(group, members)| (group.to_owned().into(), members.len()))
+            .collect();
+        for &idx in sources.iter() {
+            if on_conflict == ConflictPolicy::Skip &&
+                target_map.contains_key(&idx) { continue; }
+            let new = index_map[&idx];
+            for (group, members) in other.groups() {
+                if !members.contains(&idx) { continue; }
+                let group: Cow<'static, str> = group.to_owned().into();
+                let is_member = self.inner
+                    .groups()
+                    .any(|(g, m)| *g == *group && m.contains(&new));
+                if is_member { continue; }
+                let len = group_lens.entry(group.clone()).or_insert(0);
+                let _ = group_map.insert(
+                    (group, idx),
+                    (*len).try_into().expect("convert usize to u32"));
+                *len += 1;
+            }
+        }
+
+        let remap = |expr: &Expr| {
+            let mut expr = expr.clone();
+            expr.map_cell_refs(|cell_ref| {
+                let idx = match cell_ref {
+                    CellRef::External { .. } => return,
+                    _ => match other.resolve_ref_to_index(cell_ref) {
+                        Ok(idx) => idx,
+                        Err(_)  => return,
+                    },
+                };
+                let new = match index_map.get(&idx) {
+                    Some(new) => *new,
+                    None      => return,
+                };
+                let new_ref = match cell_ref {
+                    CellRef::Name(_) => name_map
+                        .get(&idx)
+                        .map(|name| CellRef::Name(name.clone())),
+                    CellRef::Position(_) => position_map
+                        .get(&idx)
+                        .map(|position| CellRef::Position(*position)),
+                    CellRef::Group { group, .. } => group_map
+                        .get(&(group.clone(), idx))
+                        .map(|group_idx| CellRef::Group {
+                            group: group.clone(),
+                            idx: *group_idx,
+                        }),
+                    _ => None,
+                };
+                *cell_ref = new_ref.unwrap_or(CellRef::Index(new));
+            });
+            expr
+        };
+
+        let mut ops = Vec::new();
+        for (idx, expr) in other.exprs() {
+            let cell_ref = CellRef::Index(idx);
+            let new_ref = CellRef::Index(index_map[&idx]);
+            match target_map.get(&idx) {
+                Some(_) if on_conflict == ConflictPolicy::Skip => continue,
+                Some(target) => {
+                    ops.push(SetExpr {
+                        cell_ref: new_ref.clone(),
+                        expr: remap(expr),
+                    });
+                    let position = self.inner
+                        .assigned_position(&CellRef::Index(*target));
+                    if let (Some(name), Some(position))
+                        = (name_map.get(&idx), position)
+                    {
+                        ops.push(AssignName {
+                            selector: PositionSelector::from(*position),
+                            name: name.clone(),
+                        });
+                    }
+                },
+                None => {
+                    ops.push(InsertCell {
+                        idx: index_map[&idx],
+                        cell: Cell::new_with_expr(remap(expr)),
+                    });
+                    if let Some(position) = position_map.get(&idx) {
+                        ops.push(AssignPosition {
+                            cell_ref: new_ref.clone(),
+                            position: *position,
+                        });
+                        if let Some(name) = name_map.get(&idx) {
+                            ops.push(AssignName {
+                                selector: PositionSelector::from(*position),
+                                name: name.clone(),
+                            });
+                        }
+                    }
+                },
+            }
+
+            for (group, _) in other.groups() {
+                let group: Cow<'static, str> = group.to_owned().into();
+                if let Some(group_idx) = group_map.get(&(group.clone(), idx)) {
+                    ops.push(AssignGroup {
+                        cell_ref: new_ref.clone(),
+                        group,
+                        idx: Some(*group_idx),
+                    });
+                }
+            }
+            if let Some(entries) = other.assigned_metadata(&cell_ref) {
+                for (key, value) in entries.iter() {
+                    ops.push(AssignMetadata {
+                        cell_ref: new_ref.clone(),
+                        key: key.clone(),
+                        value: value.clone(),
+                    });
+                }
+            }
+            for (variant, _, expr) in other
+                .variant_exprs()
+                .filter(|(_, i, _)| *i == idx)
+            {
+                ops.push(SetVariantExpr {
+                    variant: variant.clone(),
+                    cell_ref: new_ref.clone(),
+                    expr: remap(expr),
+                });
+            }
+        }
+
+        // Lock the inserted cells last so that the operations above are not
+        // rejected.
+        for idx in inserted {
+            if other.is_locked(&CellRef::Index(idx)) {
+                ops.push(Lock { cell_ref: CellRef::Index(index_map[&idx]) });
+            }
+        }
+
+        self.apply_operations(&ops[..])
+    }
+
     /// Moves the selected cells within the palette.
+    ///
+    /// If a `ShiftMode` is given, the gaps left by the moved cells are closed
+    /// and the cells following the destination are shifted forward to make
+    /// room for them.
     pub fn move_selection<'name>(
         &mut self,
         selection: CellSelection<'name>,
         positioning: Positioning,
-        cursor_behavior: CursorBehavior)
+        cursor_behavior: CursorBehavior,
+        shift: Option<ShiftMode>)
         -> Result<(), PaletteError>
     {
         use Operation::*;
+        if let Some(mode) = shift {
+            return self.shift_move_selection(
+                selection,
+                positioning,
+                cursor_behavior,
+                mode);
+        }
         // TODO: Fix this method so it doesn't misbehave for overlapping moves.
         // Maybe apply operations directly and build the undo ourselves?
 
         let index_selection = selection.resolve(self.inner());    
         let mut ops = Vec::new();
         let mut next = match positioning {
-            Positioning::Position(p) => p,
+            Positioning::Position(p) => {
+                self.inner.constraints().check_position(&p)?;
+                p
+            },
             Positioning::Open => Position::ZERO,
             Positioning::Cursor => self.inner.position_cursor(),
             Positioning::None => Position::ZERO,
@@ -453,7 +1428,7 @@ impl Palette {
                         cell_ref: CellRef::Index(idx),
                         position: pos,
                     });
-                    next = pos.succ();
+                    next = self.inner.constraints().wrapping_succ(pos);
                 },
                 None => return Err(PaletteError::AllPositionsAssigned),
             }
@@ -480,30 +1455,569 @@ impl Palette {
         self.apply_operations(&ops[..])
     }
 
-    /// Changes the palette's history setting.
-    pub fn set_history_option(&mut self, setting: HistorySetOption) {
-        match setting {
-            HistorySetOption::Clear => {
-                self.history = Some(History::new());
-            },
+    /// Moves the selected cells within the palette, closing the gaps they
+    /// leave behind and shifting the cells following the destination to make
+    /// room for them.
+    fn shift_move_selection<'name>(
+        &mut self,
+        selection: CellSelection<'name>,
+        positioning: Positioning,
+        cursor_behavior: CursorBehavior,
+        mode: ShiftMode)
+        -> Result<(), PaletteError>
+    {
+        use Operation::*;
+        let constraints = self.inner.constraints().clone();
+        let index_selection = selection.resolve(self.inner());
+        let mut layout: BTreeMap<Position, u32> = self.inner
+            .assigned_positions()
+            .collect();
+
+        // Remove the selected cells, closing the gaps from last to first so
+        // that the remaining gap positions are unaffected.
+        let mut vacated: Vec<Position> = index_selection
+            .iter()
+            .filter_map(|idx| self.inner
+                .assigned_position(&CellRef::Index(*idx))
+                .copied())
+            .collect();
+        vacated.sort();
+        for position in vacated.into_iter().rev() {
+            let _ = layout.remove(&position);
+            shift_layout(&mut layout, position, -1, mode, &constraints)?;
+        }
 
-            HistorySetOption::Enable => if self.history.is_none() {
-                self.history = Some(History::new());
+        let start = match positioning {
+            Positioning::Position(p) => {
+                constraints.check_position(&p)?;
+                p
             },
+            Positioning::Cursor => self.inner.position_cursor(),
+            Positioning::Open => {
+                let mut next = Position::ZERO;
+                while layout.contains_key(&next) {
+                    next = constraints.wrapping_succ(next);
+                    if next == Position::ZERO {
+                        return Err(PaletteError::AllPositionsAssigned);
+                    }
+                }
+                next
+            },
+            Positioning::None => {
+                let ops = self.layout_ops(&layout)?;
+                return self.apply_operations_ignoring_locks(&ops[..]);
+            },
+        };
+
+        // Open a gap at the destination and move the selected cells into it.
+        let count = index_selection.len() as i64;
+        shift_layout(&mut layout, start, count, mode, &constraints)?;
+        let mut next = start;
+        for (i, idx) in index_selection.iter().enumerate() {
+            if i > 0 { next = shift_position(next, 1, mode, &constraints)?; }
+            let _ = layout.insert(next, *idx);
+        }
+        let mut ops = self.layout_ops(&layout)?;
 
-            HistorySetOption::Disable => {
-                self.history = None;
+        match cursor_behavior {
+            CursorBehavior::RemainInPlace => (),
+
+            CursorBehavior::MoveToStart
+                => ops.push(SetPositionCursor { position: start }),
+
+            CursorBehavior::MoveAfterEnd => ops.push(SetPositionCursor {
+                position: shift_position(next, 1, mode, &constraints)
+                    .unwrap_or(next),
+            }),
+
+            CursorBehavior::MoveToOpen => {
+                let mut position = Position::ZERO;
+                while layout.contains_key(&position) {
+                    position = constraints.wrapping_succ(position);
+                    if position == Position::ZERO { break; }
+                }
+                ops.push(SetPositionCursor { position })
             },
         }
+        self.apply_operations_ignoring_locks(&ops[..])
+    }
+
+    /// Returns the operations which reassign the palette's positions to match
+    /// the given layout. Names and position references within expressions are
+    /// moved along with the cells.
+    ///
+    /// ### Errors
+    ///
+    /// Returns a `PaletteError::LockedCell` if the position of a locked cell
+    /// would be changed, unless locks are ignored.
+    fn layout_ops(&self, layout: &BTreeMap<Position, u32>)
+        -> Result<Vec<Operation>, PaletteError>
+    {
+        use Operation::*;
+        let assigned: BTreeSet<u32> = layout.values().copied().collect();
+        let check_lock = |cell_ref: &CellRef<'static>| {
+            if !self.inner.ignore_locks() && self.inner.is_locked(cell_ref) {
+                Err(PaletteError::LockedCell { cell_ref: cell_ref.clone() })
+            } else {
+                Ok(())
+            }
+        };
+
+        let mut ops = Vec::new();
+        for (_, idx) in self.inner.assigned_positions() {
+            if !assigned.contains(&idx) {
+                let cell_ref = CellRef::Index(idx);
+                check_lock(&cell_ref)?;
+                ops.push(UnassignPosition { cell_ref });
+            }
+        }
+        // Assign positions from last to first, so that cells shifted forward
+        // don't displace each other.
+        let mut moved = BTreeMap::new();
+        for (position, idx) in layout.iter().rev() {
+            let cell_ref = CellRef::Index(*idx);
+            match self.inner.assigned_position(&cell_ref) {
+                Some(old) if old == position => continue,
+                Some(old) => { let _ = moved.insert(*old, *position); },
+                None      => (),
+            }
+            check_lock(&cell_ref)?;
+            ops.push(AssignPosition { cell_ref, position: *position });
+        }
+
+        ops.extend(self.move_position_ops(&moved));
+        Ok(ops)
+    }
+
+    /// Sorts the selected cells by the given color property.
+    ///
+    /// The cells are reassigned to the positions already occupied by the
+    /// selection, so their indices (and any references to them by index) are
+    /// unchanged. Names and position references within expressions are moved
+    /// along with the cells. Cells without an assigned position are ignored,
+    /// and cells without a color are placed after all other cells.
+    ///
+    /// ### Errors
+    ///
+    /// Returns a `PaletteError::LockedCell` if a locked cell would be moved.
+    pub fn sort_selection<'name>(
+        &mut self,
+        selection: CellSelection<'name>,
+        sort_key: SortKey,
+        reverse: bool)
+        -> Result<(), PaletteError>
+    {
+        use Operation::*;
+
+        let index_selection = selection.resolve(self.inner());
+        let mut positions = Vec::new();
+        let mut keyed = Vec::new();
+        for idx in index_selection {
+            let cell_ref = CellRef::Index(idx);
+            if let Some(position) = self.inner.assigned_position(&cell_ref) {
+                positions.push(*position);
+                let key = self.inner
+                    .color(&cell_ref)?
+                    .map(|c| sort_key.key(c));
+                keyed.push((idx, key));
+            }
+        }
+
+        positions.sort();
+        keyed.sort_by(|(_, a), (_, b)| {
+            use std::cmp::Ordering;
+            match (a, b) {
+                (Some(a), Some(b)) => {
+                    let ord = a.partial_cmp(b).unwrap_or(Ordering::Equal);
+                    if reverse { ord.reverse() } else { ord }
+                },
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None)    => Ordering::Equal,
+            }
+        });
+
+        let mut moved = BTreeMap::new();
+        let mut ops = Vec::new();
+        for ((idx, _), position) in keyed.into_iter().zip(positions) {
+            let cell_ref = CellRef::Index(idx);
+            let old = match self.inner.assigned_position(&cell_ref) {
+                Some(old) if *old != position => *old,
+                _ => continue,
+            };
+            if self.inner.is_locked(&cell_ref) {
+                return Err(PaletteError::LockedCell { cell_ref });
+            }
+            let _ = moved.insert(old, position);
+            ops.push(AssignPosition { cell_ref, position });
+        }
+
+        ops.extend(self.move_position_ops(&moved));
+        self.apply_operations_ignoring_locks(&ops[..])
+    }
+
+    /// Closes the gaps between the positions and/or indices of the selected
+    /// cells.
+    ///
+    /// Positions are compacted line by line: the selected cells of each line
+    /// are moved into the lowest columns not occupied by unselected cells, and
+    /// lines holding only selected cells are moved into the lowest free lines
+    /// of their page. Indices are compacted into the lowest indices not
+    /// occupied by unselected cells. Index and position references within
+    /// expressions, and the names of moved positions, are rewritten to follow
+    /// the renumbered cells. Locked cells may be renumbered.
+    ///
+    /// ### Errors
+    ///
+    /// Returns a `PaletteError::LockedCell` if the position of a locked cell
+    /// would be moved.
+    pub fn compact_selection<'name>(
+        &mut self,
+        selection: CellSelection<'name>,
+        positions: bool,
+        indices: bool)
+        -> Result<(), PaletteError>
+    {
+        use Operation::*;
+
+        let selected: BTreeSet<u32> = selection
+            .resolve(self.inner())
+            .into_iter()
+            .collect();
+        let index_map = if indices {
+            compact_index_map(&self.inner, &selected)
+        } else {
+            BTreeMap::new()
+        };
+        let position_map = if positions {
+            compact_position_map(&self.inner, &selected)
+        } else {
+            BTreeMap::new()
+        };
+
+        // Renumbering a locked cell preserves it, but moving its position
+        // doesn't.
+        for (old, (idx, new)) in position_map.iter() {
+            let cell_ref = CellRef::Index(*idx);
+            if old != new && self.inner.is_locked(&cell_ref) {
+                return Err(PaletteError::LockedCell { cell_ref });
+            }
+        }
+
+        let remap = |expr: &Expr| {
+            let mut expr = expr.clone();
+            expr.map_cell_refs(|cell_ref| match cell_ref {
+                CellRef::Index(idx) => {
+                    if let Some(new) = index_map.get(&*idx) {
+                        *idx = *new;
+                    }
+                },
+                CellRef::Position(position) => {
+                    if let Some((_, new)) = position_map.get(&*position) {
+                        *position = *new;
+                    }
+                },
+                _ => (),
+            });
+            expr
+        };
+
+        let mut ops = Vec::new();
+        // Move the renumbered cells along with their positions, groups,
+        // metadata, variant overrides, and locks.
+        for (&idx, &new) in index_map.iter() {
+            let cell_ref = CellRef::Index(idx);
+            let new_ref = CellRef::Index(new);
+            let mut cell = self.inner.cell(&cell_ref)?.clone();
+            *cell.expr_mut() = remap(cell.expr());
+            ops.push(InsertCell { idx: new, cell });
+
+            if let Some(position) = self.inner.assigned_position(&cell_ref) {
+                ops.push(AssignPosition {
+                    cell_ref: new_ref.clone(),
+                    position: *position,
+                });
+            }
+            for (group, members) in self.inner.groups() {
+                let group_idx = members.iter().position(|m| *m == idx);
+                if let Some(group_idx) = group_idx {
+                    let group: Cow<'static, str> = group.to_owned().into();
+                    ops.push(AssignGroup {
+                        cell_ref: new_ref.clone(),
+                        group: group.clone(),
+                        idx: Some(group_idx.try_into()
+                            .expect("convert usize to u32")),
+                    });
+                    ops.push(UnassignGroup {
+                        cell_ref: cell_ref.clone(),
+                        group,
+                    });
+                }
+            }
+            if let Some(entries) = self.inner.assigned_metadata(&cell_ref) {
+                for (key, value) in entries.iter() {
+                    ops.push(AssignMetadata {
+                        cell_ref: new_ref.clone(),
+                        key: key.clone(),
+                        value: value.clone(),
+                    });
+                    ops.push(UnassignMetadata {
+                        cell_ref: cell_ref.clone(),
+                        key: key.clone(),
+                    });
+                }
+            }
+            for (variant, _, expr) in self.inner
+                .variant_exprs()
+                .filter(|(_, i, _)| *i == idx)
+            {
+                ops.push(SetVariantExpr {
+                    variant: variant.clone(),
+                    cell_ref: new_ref.clone(),
+                    expr: remap(expr),
+                });
+                ops.push(UnsetVariantExpr {
+                    variant: variant.clone(),
+                    cell_ref: cell_ref.clone(),
+                });
+            }
+            ops.push(RemoveCell { cell_ref: cell_ref.clone() });
+            if self.inner.is_locked(&cell_ref) {
+                ops.push(Lock { cell_ref: new_ref });
+                ops.push(Unlock { cell_ref });
+            }
+        }
+
+        // Rewrite the references of the cells which weren't renumbered.
+        for (idx, expr) in self.inner.exprs() {
+            let new_expr = remap(expr);
+            if !index_map.contains_key(&idx) && new_expr != *expr {
+                ops.push(SetExpr {
+                    cell_ref: CellRef::Index(idx),
+                    expr: new_expr,
+                });
+            }
+        }
+        for (variant, idx, expr) in self.inner.variant_exprs() {
+            let new_expr = remap(expr);
+            if !index_map.contains_key(&idx) && new_expr != *expr {
+                ops.push(SetVariantExpr {
+                    variant: variant.clone(),
+                    cell_ref: CellRef::Index(idx),
+                    expr: new_expr,
+                });
+            }
+        }
+
+        // Move the cells into their compacted positions.
+        for (idx, position) in position_map.values() {
+            ops.push(AssignPosition {
+                cell_ref: CellRef::Index(*index_map.get(idx).unwrap_or(idx)),
+                position: *position,
+            });
+        }
+
+        // Move the names of the moved positions.
+        let moved: BTreeMap<Position, Position> = position_map
+            .iter()
+            .filter(|(old, (_, new))| old != &new)
+            .map(|(old, (_, new))| (*old, *new))
+            .collect();
+        ops.extend(self.move_name_ops(&moved));
+
+        if ops.is_empty() { return Ok(()); }
+        self.apply_operations_ignoring_locks(&ops[..])
+    }
+
+    /// Sets the variant to use when evaluating colors.
+    pub fn set_active_variant<T>(&mut self, variant: Option<T>)
+        -> Result<(), PaletteError>
+        where T: Into<Cow<'static, str>>
+    {
+        self.inner.set_active_variant(variant)
+    }
+
+    /// Assigns or unassigns the path of a palette library alias.
+    pub fn set_library_path<T>(&mut self, library: T, path: Option<PathBuf>)
+        -> Result<(), PaletteError>
+        where T: Into<Cow<'static, str>>
+    {
+        let library = library.into();
+        let op = match path {
+            Some(path) => Operation::SetLibraryPath { library, path },
+            None       => Operation::UnsetLibraryPath { library },
+        };
+        self.apply_operations(&[op])
+    }
+
+    /// Sets or clears the metadata field with the given name.
+    pub fn set_metadata(&mut self, key: &str, values: Vec<String>)
+        -> Result<(), PaletteError>
+    {
+        self.change(JournalRecord::SetMetadata {
+            key: key.to_owned(),
+            values,
+        }).map(|_| ())
+    }
+
+    /// Sets or clears the palette constraint with the given name.
+    ///
+    /// ### Errors
+    ///
+    /// Returns a `PaletteError` if the constraint is invalid or if the palette
+    /// does not satisfy the new constraints.
+    pub fn set_constraint(&mut self, key: &str, value: Option<&str>)
+        -> Result<(), PaletteError>
+    {
+        let mut constraints = self.inner.constraints().clone();
+        constraints.set(key, value)?;
+
+        self.apply_operations(&[Operation::SetConstraints { constraints }])
+    }
+
+    /// Changes the palette's history setting.
+    pub fn set_history_option(&mut self, setting: HistorySetOption) {
+        let _ = self.change(JournalRecord::SetHistoryOption { setting });
+    }
+
+    /// Returns the palette history.
+    pub fn history(&self) -> Option<&History> {
+        self.history.as_ref()
+    }
+
+    /// Sets a history checkpoint with the given name at the current history
+    /// position.
+    pub fn set_checkpoint(&mut self, name: String) -> Result<(), PaletteError> {
+        self.change(JournalRecord::SetCheckpoint { name }).map(|_| ())
+    }
+
+    /// Undoes or redoes history entries to return to the history checkpoint
+    /// with the given name.
+    ///
+    /// ### Errors
+    ///
+    /// Returns a `PaletteError` if the history is disabled, the checkpoint is
+    /// undefined, or the entries leading to the checkpoint were discarded.
+    pub fn restore_checkpoint(&mut self, name: &str)
+        -> Result<(), PaletteError>
+    {
+        let history = self.history
+            .as_ref()
+            .ok_or(PaletteError::HistoryDisabled)?;
+        let checkpoint = history
+            .checkpoint(name)
+            .copied()
+            .ok_or_else(|| PaletteError::UndefinedCheckpoint {
+                name: name.to_owned().into(),
+            })?;
+        if checkpoint.discarded || checkpoint.position > history.len() {
+            return Err(PaletteError::DiscardedCheckpoint {
+                name: name.to_owned().into(),
+            });
+        }
+
+        self.change(JournalRecord::MoveHistory { id: checkpoint.position })
+            .map(|_| ())
+    }
+
+    /// Undoes and redoes history entries to switch to the history branch with
+    /// the given tip entry.
+    pub fn switch_branch(&mut self, branch: usize) -> Result<(), PaletteError> {
+        let history = self.history
+            .as_ref()
+            .ok_or(PaletteError::HistoryDisabled)?;
+        if !history.branches().contains(&branch) {
+            return Err(PaletteError::UndefinedBranch { branch });
+        }
+        self.change(JournalRecord::MoveHistory { id: branch }).map(|_| ())
+    }
+
+    /// Removes the history entries of the branch with the given tip entry
+    /// which are not shared with other branches. Returns the number of entries
+    /// removed.
+    ///
+    /// ### Errors
+    ///
+    /// Returns a `PaletteError` if the history is disabled, the branch is
+    /// undefined, or the branch is the current branch.
+    pub fn prune_branch(&mut self, branch: usize)
+        -> Result<usize, PaletteError>
+    {
+        self.change(JournalRecord::PruneBranch { branch })
+    }
+
+    /// Removes the history entries which are not on the current branch.
+    /// Returns the number of entries removed.
+    pub fn prune_inactive_branches(&mut self) -> Result<usize, PaletteError> {
+        self.change(JournalRecord::PruneInactiveBranches)
+    }
+
+    /// Merges the given range of history entries into a single entry.
+    /// Returns the number of entries removed.
+    pub fn squash_history(&mut self, from: usize, to: usize)
+        -> Result<usize, PaletteError>
+    {
+        self.change(JournalRecord::SquashHistory { from, to })
+    }
+
+    /// Removes all history entries, keeping the current palette state.
+    /// Returns the number of entries removed.
+    pub fn prune_history(&mut self) -> Result<usize, PaletteError> {
+        self.change(JournalRecord::PruneHistory)
+    }
+
+    /// Sets the maximum number of history entries and the maximum serialized
+    /// size of the history, as measured in the palette's file format. The
+    /// oldest entries are dropped when new entries exceed the limits.
+    pub fn set_history_limits(
+        &mut self,
+        max_entries: Option<usize>,
+        max_bytes: Option<usize>)
+    {
+        let format = self.load_path()
+            .map(Format::for_path)
+            .unwrap_or_default();
+        if let Some(history) = self.history.as_mut() {
+            history.set_limits(max_entries, max_bytes, format);
+        }
+    }
+
+    /// Undoes and redoes history entries to move the history cursor to the
+    /// entry with the given id. Returns the number of entries undone and
+    /// redone.
+    fn move_history_to(&mut self, id: usize) -> Result<usize, PaletteError> {
+        self.check_no_transaction()?;
+        let history = self.history
+            .as_mut()
+            .ok_or(PaletteError::HistoryDisabled)?;
+        let (undo, redo) = history
+            .path_to(id)
+            .ok_or(PaletteError::UndefinedBranch { branch: id })?;
+        let undone = self.inner.undo(history, undo);
+        history.select_path(id);
+        Ok(undone + self.inner.redo(history, redo))
+    }
+
+    /// Sets the command and label to record for new history entries.
+    pub fn set_history_entry_info(
+        &mut self,
+        command: Option<String>,
+        label: Option<String>)
+    {
+        if let Some(history) = self.history.as_mut() {
+            history.set_next_entry_info(command, label);
+        }
     }
 
     /// Sets the palette history state.
     pub fn set_history(&mut self, history: Option<History>) -> Option<History> {
+        self.journal.invalidate();
         std::mem::replace(&mut self.history, history)
     }
 
     /// Removes and returns the palette history.
     pub fn take_history(&mut self) -> Option<History> {
+        self.journal.invalidate();
         std::mem::replace(&mut self.history, None)
     }
 
@@ -538,6 +2052,28 @@ impl Palette {
         self.apply_operations(&[op])
     }
 
+    /// Locks or unlocks the cells in a selection.
+    pub fn set_locked<'a>(&mut self, selection: CellSelection<'a>, locked: bool)
+        -> Result<(), PaletteError>
+    {
+        use Operation::*;
+        let ops: Vec<Operation> = selection
+            .resolve(self.inner())
+            .into_iter()
+            .map(|idx| {
+                let cell_ref = CellRef::Index(idx);
+                if locked { Lock { cell_ref } } else { Unlock { cell_ref } }
+            })
+            .collect();
+
+        self.apply_operations(&ops[..])
+    }
+
+    /// Sets whether to allow changes to locked cells.
+    pub fn set_ignore_locks(&mut self, ignore_locks: bool) {
+        self.inner.set_ignore_locks(ignore_locks)
+    }
+
     /// Assigns or unassigns a group to a selection.
     pub fn set_group<'a, T>(
         &mut self,
@@ -570,6 +2106,233 @@ impl Palette {
         self.apply_operations(&ops[..])
     }
 
+    /// Moves a group and all groups nested beneath it to a new group path.
+    /// The order of the cells within each group is preserved.
+    pub fn move_group<T>(&mut self, from: &str, to: T)
+        -> Result<(), PaletteError>
+        where T: Into<Cow<'static, str>>
+    {
+        let to = to.into();
+        if from == to { return Ok(()); }
+
+        let ops = self.move_group_ops(from, &to)?;
+        self.apply_operations(&ops[..])
+    }
+
+    /// Returns the operations which move a group and all groups nested beneath
+    /// it to a new group path.
+    fn move_group_ops(&self, from: &str, to: &Cow<'static, str>)
+        -> Result<Vec<Operation>, PaletteError>
+    {
+        use Operation::*;
+        if is_in_group_tree(to, from, true) {
+            return Err(PaletteError::InvalidInputValue {
+                msg: format!("cannot move group '{}' into itself", from)
+                    .into(),
+            });
+        }
+
+        let mut ops: Vec<Operation> = Vec::new();
+        for (path, elems) in self.inner()
+            .groups()
+            .filter(|(path, _)| is_in_group_tree(path, from, true))
+        {
+            let group: Cow<'static, str> = Cow::from(path.to_owned());
+            let dest: Cow<'static, str> = Cow::from(
+                format!("{}{}", to, &path[from.len()..]));
+            for idx in elems {
+                let cell_ref = CellRef::Index(*idx);
+                ops.push(UnassignGroup {
+                    cell_ref: cell_ref.clone(),
+                    group: group.clone(),
+                });
+                ops.push(AssignGroup {
+                    cell_ref,
+                    group: dest.clone(),
+                    idx: None,
+                });
+            }
+        }
+
+        Ok(ops)
+    }
+
+    /// Renames an assigned name, rewriting the references to it.
+    pub fn rename_name<T>(&mut self, old: &str, new: T)
+        -> Result<(), PaletteError>
+        where T: Into<Cow<'static, str>>
+    {
+        use Operation::*;
+        let new = new.into();
+        if old == new { return Ok(()); }
+
+        let selector = *self.inner
+            .get_name_selector(old)
+            .ok_or_else(|| PaletteError::InvalidInputValue {
+                msg: format!("name '{}' is not assigned", old).into(),
+            })?;
+        if self.inner.is_assigned_name(&new) {
+            return Err(PaletteError::InvalidInputValue {
+                msg: format!("name '{}' is already assigned", new).into(),
+            });
+        }
+
+        // Assigning the new name to the selector replaces the old name.
+        let mut ops = vec![AssignName { selector, name: new.clone() }];
+        ops.extend(self.rewrite_ref_ops(|cell_ref| match cell_ref {
+            CellRef::Name(name) if *name == old => *name = new.clone(),
+            _ => (),
+        }));
+
+        self.apply_operations(&ops[..])
+    }
+
+    /// Renames a group and all groups nested beneath it, rewriting the
+    /// references to them. The order of the cells within each group is
+    /// preserved.
+    pub fn rename_group<T>(&mut self, old: &str, new: T)
+        -> Result<(), PaletteError>
+        where T: Into<Cow<'static, str>>
+    {
+        let new = new.into();
+        if old == new { return Ok(()); }
+
+        let group_exists = |inner: &BasicPalette, root: &str| inner
+            .groups()
+            .any(|(path, _)| is_in_group_tree(path, root, true));
+
+        if !group_exists(&self.inner, old) {
+            return Err(PaletteError::InvalidInputValue {
+                msg: format!("group '{}' is not assigned", old).into(),
+            });
+        }
+        // Merging into an existing group would renumber its cells and
+        // invalidate the rewritten references.
+        if group_exists(&self.inner, &new) {
+            return Err(PaletteError::InvalidInputValue {
+                msg: format!("group '{}' already exists", new).into(),
+            });
+        }
+
+        let mut ops = self.move_group_ops(old, &new)?;
+        ops.extend(self.rewrite_ref_ops(|cell_ref| {
+            if let CellRef::Group { group, .. } = cell_ref {
+                if is_in_group_tree(group, old, true) {
+                    *group = Cow::from(
+                        format!("{}{}", new, &group[old.len()..]));
+                }
+            }
+        }));
+
+        self.apply_operations(&ops[..])
+    }
+
+    /// Returns the operations which rewrite the cell references of every cell
+    /// expression and variant override using the given function.
+    fn rewrite_ref_ops<F>(&self, mut f: F) -> Vec<Operation>
+        where F: FnMut(&mut CellRef<'static>)
+    {
+        use Operation::*;
+        let mut ops = Vec::new();
+        for (idx, expr) in self.inner.exprs() {
+            let mut rewritten = expr.clone();
+            rewritten.map_cell_refs(&mut f);
+            if rewritten != *expr {
+                ops.push(SetExpr {
+                    cell_ref: CellRef::Index(idx),
+                    expr: rewritten,
+                });
+            }
+        }
+        for (variant, idx, expr) in self.inner.variant_exprs() {
+            let mut rewritten = expr.clone();
+            rewritten.map_cell_refs(&mut f);
+            if rewritten != *expr {
+                ops.push(SetVariantExpr {
+                    variant: variant.clone(),
+                    cell_ref: CellRef::Index(idx),
+                    expr: rewritten,
+                });
+            }
+        }
+        ops
+    }
+
+    /// Returns the operations which move the names assigned to the given old
+    /// positions to their new positions.
+    fn move_name_ops(&self, moved: &BTreeMap<Position, Position>)
+        -> Vec<Operation>
+    {
+        use Operation::*;
+        // Names are bound to positions, so they are moved with their cells.
+        // All of the moved names are unassigned first, so that they don't
+        // displace each other.
+        let mut ops = Vec::new();
+        let mut names = Vec::new();
+        for (old, new) in moved.iter() {
+            let old = PositionSelector::from(*old);
+            if let Some(name) = self.inner.get_name(&old) {
+                names.push((name.clone(), PositionSelector::from(*new)));
+                ops.push(UnassignName { selector: old });
+            }
+        }
+        ops.extend(names
+            .into_iter()
+            .map(|(name, selector)| AssignName { selector, name }));
+        ops
+    }
+
+    /// Returns the operations which move the names and position references of
+    /// the given old positions to their new positions.
+    fn move_position_ops(&self, moved: &BTreeMap<Position, Position>)
+        -> Vec<Operation>
+    {
+        let mut ops = self.move_name_ops(moved);
+        ops.extend(self.rewrite_ref_ops(|cell_ref| {
+            if let CellRef::Position(position) = cell_ref {
+                if let Some(new) = moved.get(&*position) {
+                    *position = *new;
+                }
+            }
+        }));
+        ops
+    }
+
+
+    /// Assigns or unassigns a metadata entry for the cells in a selection.
+    pub fn set_cell_metadata<'a, K, V>(
+        &mut self,
+        selection: CellSelection<'a>,
+        key: K,
+        value: Option<V>)
+        -> Result<(), PaletteError>
+        where
+            K: Into<Cow<'static, str>>,
+            V: Into<Cow<'static, str>>,
+    {
+        use Operation::*;
+        let index_selection = selection.resolve(self.inner());
+        let key = key.into();
+        let value = value.map(Into::into);
+        let mut ops: Vec<Operation> = Vec::new();
+
+        for idx in index_selection {
+            let cell_ref = CellRef::Index(idx);
+            match value.as_ref() {
+                Some(v) => ops.push(AssignMetadata {
+                    cell_ref,
+                    key: key.clone(),
+                    value: v.clone(),
+                }),
+                None => ops.push(UnassignMetadata {
+                    cell_ref,
+                    key: key.clone(),
+                }),
+            }
+        }
+
+        self.apply_operations(&ops[..])
+    }
 
     /// Sets the Expr for the given CellRef.
     pub fn set_expr<'name>(
@@ -585,6 +2348,25 @@ impl Palette {
         }])
     }
 
+    /// Sets or removes the variant override Expr for the given CellRef.
+    pub fn set_variant_expr<'name, T>(
+        &mut self,
+        variant: T,
+        cell_ref: CellRef<'name>,
+        expr: Option<Expr>)
+        -> Result<(), PaletteError>
+        where T: Into<Cow<'static, str>>
+    {
+        use Operation::*;
+        let variant = variant.into();
+        let cell_ref = cell_ref.into_static();
+        let op = match expr {
+            Some(expr) => SetVariantExpr { variant, cell_ref, expr },
+            None       => UnsetVariantExpr { variant, cell_ref },
+        };
+        self.apply_operations(&[op])
+    }
+
     ////////////////////////////////////////////////////////////////////////////
     // Operations
     ////////////////////////////////////////////////////////////////////////////
@@ -596,7 +2378,115 @@ impl Palette {
     pub fn apply_operations(&mut self, ops: &[Operation])
         -> Result<(), PaletteError>
     {
-        self.inner.apply_operations(ops, self.history.as_mut())
+        let dropped = self.dropped_history_count();
+        self.apply_operations_unjournaled(ops)?;
+        if !self.journal.is_enabled() { return Ok(()); }
+
+        // Operations applied within a transaction are recorded with the info
+        // for the transaction's entry, which is created when it is committed.
+        let info = match (&self.transaction, &self.history) {
+            (None,    Some(history)) => history
+                .entry_info(history.cursor())
+                .clone(),
+            (Some(_), Some(history)) => history.next_entry_info().clone(),
+            (_,       None)          => EntryInfo::default(),
+        };
+        self.journal_change(JournalRecord::Apply {
+            ops: ops.to_vec(),
+            command: info.command,
+            label: info.label,
+            timestamp: info.timestamp,
+        }, dropped);
+        Ok(())
+    }
+
+    /// Applies a sequence of `Operation`s to the palette without recording
+    /// them in the journal.
+    fn apply_operations_unjournaled(&mut self, ops: &[Operation])
+        -> Result<(), PaletteError>
+    {
+        match self.transaction.as_mut() {
+            Some(transaction) => {
+                let undo_ops = self.inner.apply_operations_reversibly(ops)?;
+                let command = self.history
+                    .as_ref()
+                    .and_then(|h| h.next_entry_info().command.as_deref());
+                transaction.record(undo_ops, command);
+                Ok(())
+            },
+            None => self.inner.apply_operations(ops, self.history.as_mut()),
+        }
+    }
+
+    /// Applies a sequence of `Operation`s to the palette without checking for
+    /// locked cells. This is used for operations which renumber cells or
+    /// rewrite references without changing any cell's color.
+    fn apply_operations_ignoring_locks(&mut self, ops: &[Operation])
+        -> Result<(), PaletteError>
+    {
+        let ignore_locks = self.inner.ignore_locks();
+        self.inner.set_ignore_locks(true);
+        let res = self.apply_operations(ops);
+        self.inner.set_ignore_locks(ignore_locks);
+        res
+    }
+
+    ////////////////////////////////////////////////////////////////////////////
+    // Transactions
+    ////////////////////////////////////////////////////////////////////////////
+
+    /// Returns the pending transaction.
+    pub fn transaction(&self) -> Option<&Transaction> {
+        self.transaction.as_ref()
+    }
+
+    /// Returns true if a transaction is pending.
+    pub fn in_transaction(&self) -> bool {
+        self.transaction.is_some()
+    }
+
+    /// Begins a transaction. Changes made until the transaction is committed
+    /// are recorded as a single history entry.
+    pub fn begin_transaction(&mut self, label: Option<String>)
+        -> Result<(), PaletteError>
+    {
+        self.change(JournalRecord::BeginTransaction { label }).map(|_| ())
+    }
+
+    /// Commits the pending transaction, recording its changes as a single
+    /// history entry.
+    pub fn commit_transaction(&mut self) -> Result<(), PaletteError> {
+        self.change(JournalRecord::CommitTransaction {
+            timestamp: current_timestamp(),
+        }).map(|_| ())
+    }
+
+    /// Rolls back the pending transaction, reverting its changes.
+    pub fn rollback_transaction(&mut self) -> Result<(), PaletteError> {
+        self.change(JournalRecord::RollbackTransaction).map(|_| ())
+    }
+
+    /// Returns a savepoint for the pending transaction, marking the changes
+    /// made so far.
+    pub fn transaction_savepoint(&self) -> Option<usize> {
+        self.transaction.as_ref().map(Transaction::savepoint)
+    }
+
+    /// Reverts the changes of the pending transaction which were made after
+    /// the given savepoint.
+    pub fn rollback_to_savepoint(&mut self, savepoint: usize)
+        -> Result<(), PaletteError>
+    {
+        self.change(JournalRecord::RollbackToSavepoint { savepoint })
+            .map(|_| ())
+    }
+
+    /// Returns an error if a transaction is pending.
+    fn check_no_transaction(&self) -> Result<(), PaletteError> {
+        match self.transaction {
+            Some(_) => Err(PaletteError::TransactionPending),
+            None    => Ok(()),
+        }
     }
 
     /// Unapplies the latest set of applied operations.
@@ -605,10 +2495,7 @@ impl Palette {
     /// be fewer than the number provided if there are fewer undo operations
     /// recorded than requested.
     pub fn undo(&mut self, count: usize) -> usize {
-        match self.history.as_mut() {
-            Some(history) => self.inner.undo(history, count),
-            _             => 0,
-        }
+        self.change(JournalRecord::Undo { count }).unwrap_or(0)
     }
 
     /// Reapplies the latest set of undone operations.
@@ -617,10 +2504,7 @@ impl Palette {
     /// be fewer than the number provided if there are fewer redo operations
     /// recorded than requested.
     pub fn redo(&mut self, count: usize) -> usize {
-        match self.history.as_mut() {
-            Some(history) => self.inner.redo(history, count),
-            _             => 0,
-        }
+        self.change(JournalRecord::Redo { count }).unwrap_or(0)
     }
 }
 
@@ -637,3 +2521,152 @@ impl PartialEq for Palette {
         // NOTE: This comparison ignores the command history.
     }
 }
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Compaction
+////////////////////////////////////////////////////////////////////////////////
+/// Returns a map from the selected indices to their compacted indices. Indices
+/// which are unchanged are omitted.
+fn compact_index_map(basic: &BasicPalette, selected: &BTreeSet<u32>)
+    -> BTreeMap<u32, u32>
+{
+    let mut map = BTreeMap::new();
+    let mut next: u32 = 0;
+    for &idx in selected.iter() {
+        while basic.is_occupied_index(&next) && !selected.contains(&next) {
+            next += 1;
+        }
+        if next != idx {
+            let _ = map.insert(idx, next);
+        }
+        next += 1;
+    }
+    map
+}
+
+/// Returns a map from the positions of the selected cells to their indices and
+/// compacted positions, in position order. Positions which are unchanged are
+/// omitted.
+fn compact_position_map(basic: &BasicPalette, selected: &BTreeSet<u32>)
+    -> BTreeMap<Position, (u32, Position)>
+{
+    // Group the assigned cells by page and line.
+    let mut lines: BTreeMap<(u16, u16), Vec<(u16, u32)>> = BTreeMap::new();
+    for (position, idx) in basic.assigned_positions() {
+        lines
+            .entry((position.page, position.line))
+            .or_default()
+            .push((position.column, idx));
+    }
+    // Lines holding unselected cells keep their line numbers.
+    let fixed_lines: BTreeSet<(u16, u16)> = lines
+        .iter()
+        .filter(|(_, cells)| cells
+            .iter()
+            .any(|(_, idx)| !selected.contains(idx)))
+        .map(|(line, _)| *line)
+        .collect();
+
+    let mut map = BTreeMap::new();
+    let mut next_lines: BTreeMap<u16, u16> = BTreeMap::new();
+    for (&(page, line), cells) in lines.iter() {
+        let new_line = if fixed_lines.contains(&(page, line)) {
+            line
+        } else {
+            let next = next_lines.entry(page).or_insert(0);
+            while fixed_lines.contains(&(page, *next)) {
+                *next = next.saturating_add(1);
+            }
+            let new_line = *next;
+            *next = next.saturating_add(1);
+            new_line
+        };
+
+        let fixed_columns: BTreeSet<u16> = cells
+            .iter()
+            .filter(|(_, idx)| !selected.contains(idx))
+            .map(|(column, _)| *column)
+            .collect();
+        let mut next_column: u16 = 0;
+        for &(column, idx) in cells.iter() {
+            if !selected.contains(&idx) { continue; }
+            while fixed_columns.contains(&next_column) {
+                next_column = next_column.saturating_add(1);
+            }
+            let old = Position { page, line, column };
+            let new = Position { page, line: new_line, column: next_column };
+            if new != old {
+                let _ = map.insert(old, (idx, new));
+            }
+            next_column = next_column.saturating_add(1);
+        }
+    }
+    map
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Shifting
+////////////////////////////////////////////////////////////////////////////////
+/// Returns the position `offset` positions after the given one within the line
+/// or page shifted by the given `ShiftMode`. Pages wrap across lines at the
+/// maximum column of the given `Constraints`.
+fn shift_position(
+    position: Position,
+    offset: i64,
+    mode: ShiftMode,
+    constraints: &Constraints)
+    -> Result<Position, PaletteError>
+{
+    let width = i64::from(constraints.max_column.unwrap_or(u16::MAX)) + 1;
+    let (line, column) = match mode {
+        ShiftMode::Line => (
+            i64::from(position.line),
+            i64::from(position.column) + offset),
+        ShiftMode::Page => {
+            let linear = i64::from(position.line) * width
+                + i64::from(position.column)
+                + offset;
+            (linear.div_euclid(width), linear.rem_euclid(width))
+        },
+    };
+
+    let shifted = match (u16::try_from(line), u16::try_from(column)) {
+        (Ok(line), Ok(column)) => Position {
+            page: position.page,
+            line,
+            column,
+        },
+        _ => return Err(PaletteError::AllPositionsAssigned),
+    };
+    constraints.check_position(&shifted)?;
+    Ok(shifted)
+}
+
+/// Shifts the cells at or after the given position within its line or page by
+/// `offset` positions.
+fn shift_layout(
+    layout: &mut BTreeMap<Position, u32>,
+    start: Position,
+    offset: i64,
+    mode: ShiftMode,
+    constraints: &Constraints)
+    -> Result<(), PaletteError>
+{
+    let shifted: Vec<(Position, u32)> = layout
+        .range(start..)
+        .filter(|(p, _)| p.page == start.page &&
+            (mode == ShiftMode::Page || p.line == start.line))
+        .map(|(p, idx)| (*p, *idx))
+        .collect();
+
+    for (position, _) in shifted.iter() {
+        let _ = layout.remove(position);
+    }
+    for (position, idx) in shifted {
+        let position = shift_position(position, offset, mode, constraints)?;
+        let _ = layout.insert(position, idx);
+    }
+    Ok(())
+}
@@ -9,12 +9,20 @@
 ////////////////////////////////////////////////////////////////////////////////
 
 // Local imports.
+use crate::error::PaletteError;
+use crate::format::Format;
 use crate::palette::Operation;
+use crate::utility::current_timestamp;
 
 // External library imports.
+use ron::ser::PrettyConfig;
 use serde::Serialize;
 use serde::Deserialize;
 
+// Standard library imports.
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+
 
 ////////////////////////////////////////////////////////////////////////////////
 // CursorState
@@ -24,8 +32,10 @@ use serde::Deserialize;
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum CursorState {
     Valid,
-    AwaitingSetRedo,
-    AwaitingSetUndo,
+    /// Awaiting the redo ops for the entry with the given id.
+    AwaitingSetRedo(usize),
+    /// Awaiting the undo ops for the entry with the given id.
+    AwaitingSetUndo(usize),
 }
 
 impl Default for CursorState {
@@ -35,20 +45,100 @@ impl Default for CursorState {
 }
 
 
+////////////////////////////////////////////////////////////////////////////////
+// EntryInfo
+////////////////////////////////////////////////////////////////////////////////
+/// Descriptive information about a history entry.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct EntryInfo {
+    /// The command line or script statement which created the entry.
+    pub command: Option<String>,
+    /// The time the entry was created, in seconds since the unix epoch.
+    pub timestamp: Option<u64>,
+    /// A user-provided label for the entry.
+    pub label: Option<String>,
+}
+
+/// The info for entries recorded without any.
+static EMPTY_ENTRY_INFO: EntryInfo = EntryInfo {
+    command: None,
+    timestamp: None,
+    label: None,
+};
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Checkpoint
+////////////////////////////////////////////////////////////////////////////////
+/// A named history position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// The id of the checkpoint's history entry.
+    pub position: usize,
+    /// Whether the checkpoint's history entry was discarded.
+    #[serde(default)]
+    pub discarded: bool,
+}
+
+
 ////////////////////////////////////////////////////////////////////////////////
 // History
 ////////////////////////////////////////////////////////////////////////////////
 /// Structure for supporting undo/redo operations.
+///
+/// The history is a tree of entries. Each entry is identified by its id,
+/// starting from 1, and id 0 refers to the initial state of the palette. An
+/// entry holds undo ops while it is applied to the palette, and redo ops
+/// otherwise. Making an edit after an undo starts a new branch, leaving the
+/// undone entries in place.
 #[derive(Debug, Clone)]
 #[derive(Serialize, Deserialize)]
 pub struct History {
-    /// The undo/redo list of history operations.
+    /// The undo/redo ops of each entry.
     ops: Vec<Vec<Operation>>,
-    /// The cursor position, separating undo ops from redo ops.
+    /// The info for each entry in `ops`. Histories recorded before entry info
+    /// was introduced may have fewer infos than entries.
+    #[serde(default)]
+    info: Vec<EntryInfo>,
+    /// The parent id of each entry in `ops`. Linear histories recorded before
+    /// branching was introduced have no parents, in which case each entry's
+    /// parent is the entry before it.
+    #[serde(default)]
+    parents: Vec<usize>,
+    /// The child to redo for entries whose redo child is not their latest.
+    #[serde(default)]
+    selected: BTreeMap<usize, usize>,
+    /// The id of the current entry.
     cursor: usize,
+    /// The named checkpoints.
+    #[serde(default)]
+    checkpoints: BTreeMap<String, Checkpoint>,
     /// The state of the cursor.
     #[serde(skip)]
     cursor_state: CursorState,
+    /// The info to record for new entries. The timestamp is set when the
+    /// entry is pushed.
+    #[serde(skip)]
+    next_info: EntryInfo,
+    /// The maximum number of entries.
+    #[serde(skip)]
+    max_entries: Option<usize>,
+    /// The maximum serialized size of the history, in bytes.
+    #[serde(skip)]
+    max_bytes: Option<usize>,
+    /// The format used to measure the serialized size of the history.
+    #[serde(skip)]
+    size_format: Format,
+    /// The serialized size of the history's entries, or `None` if it has not
+    /// been measured.
+    #[serde(skip)]
+    size: Option<usize>,
+    /// The number of entries dropped due to the limits.
+    #[serde(skip)]
+    dropped: usize,
 }
 
 
@@ -58,36 +148,542 @@ impl History {
     pub fn new() -> Self {
         History {
             ops: Vec::with_capacity(8),
+            info: Vec::with_capacity(8),
+            parents: Vec::with_capacity(8),
+            selected: BTreeMap::new(),
             cursor: 0,
+            checkpoints: BTreeMap::new(),
             cursor_state: CursorState::default(),
+            next_info: EntryInfo::default(),
+            max_entries: None,
+            max_bytes: None,
+            size_format: Format::default(),
+            size: None,
+            dropped: 0,
+        }
+    }
+
+    /// Returns the number of entries in the history.
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    /// Returns true if the history has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    /// Returns the id of the current entry, or 0 if no entries are applied.
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    /// Returns the parent of the entry with the given id.
+    pub fn parent(&self, id: usize) -> Option<usize> {
+        if id == 0 || id > self.ops.len() { return None; }
+        Some(self.parents.get(id - 1).copied().unwrap_or(id - 1))
+    }
+
+    /// Returns an iterator over the children of the entry with the given id,
+    /// in the order they were created.
+    pub fn children(&self, id: usize) -> impl Iterator<Item=usize> + '_ {
+        (id + 1..=self.ops.len())
+            .filter(move |child| self.parent(*child) == Some(id))
+    }
+
+    /// Returns the child of the entry with the given id which will be redone
+    /// from it.
+    pub fn redo_child(&self, id: usize) -> Option<usize> {
+        self.selected
+            .get(&id)
+            .copied()
+            .or_else(|| self.children(id).last())
+    }
+
+    /// Returns true if the entry with the given id is applied to the palette.
+    pub fn is_applied(&self, id: usize) -> bool {
+        self.ancestors(self.cursor).any(|ancestor| ancestor == id)
+    }
+
+    /// Returns an iterator over the entry with the given id and its
+    /// ancestors, ending with the initial state.
+    fn ancestors(&self, id: usize) -> impl Iterator<Item=usize> + '_ {
+        std::iter::successors(Some(id), move |id| self.parent(*id))
+    }
+
+    /// Returns the info for the entry with the given id.
+    pub fn entry_info(&self, id: usize) -> &EntryInfo {
+        match id {
+            0 => &EMPTY_ENTRY_INFO,
+            _ => self.info.get(id - 1).unwrap_or(&EMPTY_ENTRY_INFO),
+        }
+    }
+
+    /// Sets the creation time of the entry with the given id.
+    pub fn set_entry_timestamp(&mut self, id: usize, timestamp: Option<u64>) {
+        if id == 0 || id > self.ops.len() { return; }
+        self.normalize();
+        self.update_entry_size(id, |history| {
+            history.info[id - 1].timestamp = timestamp;
+        });
+    }
+
+    /// Returns an iterator over the ids and info of the entries in the
+    /// history.
+    pub fn entry_infos(&self) -> impl Iterator<Item=(usize, &EntryInfo)> + '_ {
+        (1..=self.ops.len()).map(move |id| (id, self.entry_info(id)))
+    }
+
+    /// Returns the ids of the entries at the tips of the history branches.
+    pub fn branches(&self) -> Vec<usize> {
+        let mut branches: Vec<usize> = (1..=self.ops.len()).collect();
+        for id in 1..=self.ops.len() {
+            if let Some(parent) = self.parent(id) {
+                branches.retain(|branch| *branch != parent);
+            }
+        }
+        branches
+    }
+
+    /// Returns the id of the tip of the current branch, which is reached by
+    /// redoing from the current entry.
+    pub fn current_branch(&self) -> usize {
+        std::iter::successors(Some(self.cursor), |id| self.redo_child(*id))
+            .last()
+            .unwrap_or(self.cursor)
+    }
+
+    /// Returns the number of undos and redos needed to move the cursor to the
+    /// entry with the given id, or `None` if there is no such entry. The redos
+    /// must be preceeded by a call to `select_path`.
+    pub fn path_to(&self, id: usize) -> Option<(usize, usize)> {
+        if id > self.ops.len() { return None; }
+        let common = self.ancestors(id)
+            .find(|ancestor| self.is_applied(*ancestor))
+            .unwrap_or(0);
+        let depth = |id: usize| self.ancestors(id)
+            .take_while(|ancestor| *ancestor != common)
+            .count();
+        Some((depth(self.cursor), depth(id)))
+    }
+
+    /// Selects the redo children along the path to the entry with the given
+    /// id, so that redoing from any of its ancestors leads to it.
+    pub fn select_path(&mut self, id: usize) {
+        let mut child = id;
+        while let Some(parent) = self.parent(child) {
+            self.select_child(parent, child);
+            child = parent;
+        }
+    }
+
+    /// Selects the child to redo from the given parent entry.
+    fn select_child(&mut self, parent: usize, child: usize) {
+        if self.children(parent).last() == Some(child) {
+            let _ = self.selected.remove(&parent);
+        } else {
+            let _ = self.selected.insert(parent, child);
+        }
+    }
+
+    /// Removes the entries of the branch with the given tip which are not
+    /// shared with any other branch. Returns the number of entries removed,
+    /// which is 0 if the given id is not the tip of a branch or its entries
+    /// are applied to the palette.
+    pub fn prune_branch(&mut self, branch: usize) -> usize {
+        if !self.branches().contains(&branch) { return 0; }
+
+        let mut removed = BTreeSet::new();
+        for id in self.ancestors(branch) {
+            let shared = self.children(id)
+                .any(|child| !removed.contains(&child));
+            if id == 0 || self.is_applied(id) || shared { break; }
+            let _ = removed.insert(id);
+        }
+        self.remove_entries(&removed, &BTreeMap::new())
+    }
+
+    /// Removes all entries which are neither applied to the palette nor on
+    /// the current branch. Returns the number of entries removed.
+    pub fn prune_inactive_branches(&mut self) -> usize {
+        let mut kept: BTreeSet<usize> = self.ancestors(self.cursor).collect();
+        kept.extend(std::iter::successors(
+            Some(self.cursor),
+            |id| self.redo_child(*id)));
+        let removed = (1..=self.ops.len())
+            .filter(|id| !kept.contains(id))
+            .collect();
+        self.remove_entries(&removed, &BTreeMap::new())
+    }
+
+    /// Removes the entries with the given ids, renumbering the remaining
+    /// entries. Checkpoints on removed entries are marked as discarded.
+    ///
+    /// The merged entries are also removed, but they represent the same
+    /// palette state as the entries they map to, so references to them are
+    /// redirected. The cursor must not be on a removed entry, and removed
+    /// entries must not be the parents of any remaining entries.
+    ///
+    /// Returns the number of entries removed.
+    fn remove_entries(
+        &mut self,
+        removed: &BTreeSet<usize>,
+        merged: &BTreeMap<usize, usize>)
+        -> usize
+    {
+        if removed.is_empty() && merged.is_empty() { return 0; }
+        self.normalize();
+        if let Some(size) = self.size {
+            let removed_size: usize = removed
+                .iter()
+                .chain(merged.keys())
+                .map(|id| self.entry_size(*id))
+                .sum();
+            self.size = Some(size.saturating_sub(removed_size));
+        }
+
+        let mut map = BTreeMap::new();
+        let _ = map.insert(0, 0);
+        for id in 1..=self.ops.len() {
+            if removed.contains(&id) || merged.contains_key(&id) { continue; }
+            let next = map.len();
+            let _ = map.insert(id, next);
+        }
+        for (id, target) in merged.iter() {
+            let target = map[target];
+            let _ = map.insert(*id, target);
+        }
+
+        let entries = std::mem::take(&mut self.ops)
+            .into_iter()
+            .zip(std::mem::take(&mut self.info))
+            .zip(std::mem::take(&mut self.parents))
+            .enumerate();
+        for (idx, ((ops, info), parent)) in entries {
+            let id = idx + 1;
+            if removed.contains(&id) || merged.contains_key(&id) { continue; }
+            self.ops.push(ops);
+            self.info.push(info);
+            self.parents.push(map[&parent]);
+        }
+
+        self.cursor = map[&self.cursor];
+        self.selected = std::mem::take(&mut self.selected)
+            .into_iter()
+            .filter_map(|(parent, child)| Some((
+                *map.get(&parent)?,
+                *map.get(&child)?)))
+            .filter(|(parent, child)| parent != child)
+            .collect();
+        for checkpoint in self.checkpoints.values_mut() {
+            if checkpoint.discarded { continue; }
+            match map.get(&checkpoint.position) {
+                Some(position) => checkpoint.position = *position,
+                None           => checkpoint.discarded = true,
+            }
+        }
+        removed.len() + merged.len()
+    }
+
+    /// Returns the ids of the entry with the given id and its descendants.
+    fn descendants(&self, id: usize) -> BTreeSet<usize> {
+        let mut descendants = BTreeSet::new();
+        let _ = descendants.insert(id);
+        for child in id + 1..=self.ops.len() {
+            if self.parent(child).map_or(false, |p| descendants.contains(&p)) {
+                let _ = descendants.insert(child);
+            }
+        }
+        descendants
+    }
+
+    /// Drops the oldest applied entry, making the state after it the new
+    /// initial state. Any branches which do not include the entry are also
+    /// dropped. Returns the number of entries removed.
+    pub fn drop_oldest(&mut self) -> usize {
+        let oldest = match self.ancestors(self.cursor)
+            .take_while(|id| *id != 0)
+            .last()
+        {
+            Some(oldest) => oldest,
+            None         => return 0,
+        };
+
+        let mut removed = BTreeSet::new();
+        for child in self.children(0).filter(|child| *child != oldest) {
+            removed.extend(self.descendants(child));
         }
+        let mut merged = BTreeMap::new();
+        let _ = merged.insert(oldest, 0);
+        self.remove_entries(&removed, &merged)
+    }
+
+    /// Removes all entries, making the current state the new initial state.
+    /// Returns the number of entries removed.
+    pub fn prune(&mut self) -> usize {
+        let removed = (1..=self.ops.len())
+            .filter(|id| *id != self.cursor)
+            .collect();
+        let mut merged = BTreeMap::new();
+        if self.cursor != 0 {
+            let _ = merged.insert(self.cursor, 0);
+        }
+        self.remove_entries(&removed, &merged)
+    }
+
+    /// Merges the entries from `from` to `to` into a single entry. The entries
+    /// must form a chain without branches, and must either all be applied or
+    /// all be unapplied.
+    ///
+    /// ### Errors
+    ///
+    /// Returns a `PaletteError` if the entries cannot be squashed.
+    pub fn squash(&mut self, from: usize, to: usize)
+        -> Result<usize, PaletteError>
+    {
+        let invalid = |msg: &'static str| PaletteError::InvalidHistoryRange {
+            from,
+            to,
+            msg: msg.into(),
+        };
+        if from == 0 || to > self.ops.len() || from >= to {
+            return Err(invalid("no such range of entries"));
+        }
+
+        // Collect the chain of entries, ordered from `from` to `to`.
+        let mut chain: Vec<usize> = self.ancestors(to)
+            .take_while(|id| *id >= from)
+            .collect();
+        chain.reverse();
+        if chain.first() != Some(&from) {
+            return Err(invalid("entries are not on the same branch"));
+        }
+        if chain[..chain.len() - 1]
+            .iter()
+            .any(|id| self.children(*id).count() > 1)
+        {
+            return Err(invalid("entries include a branch point"));
+        }
+        let applied = self.is_applied(from);
+        if applied != self.is_applied(to) {
+            return Err(invalid("entries include the current entry"));
+        }
+
+        // Undo ops are applied from the latest entry to the earliest, and redo
+        // ops from the earliest to the latest.
+        self.normalize();
+        self.size = None;
+        let mut ops = Vec::new();
+        let mut commands = Vec::new();
+        for &id in chain.iter() {
+            let entry_ops = std::mem::take(&mut self.ops[id - 1]);
+            if applied {
+                let _ = ops.splice(0..0, entry_ops);
+            } else {
+                ops.extend(entry_ops);
+            }
+            if let Some(command) = &self.info[id - 1].command {
+                commands.push(command.clone());
+            }
+        }
+        let info = EntryInfo {
+            command: if commands.is_empty() {
+                None
+            } else {
+                Some(commands.join("; "))
+            },
+            timestamp: self.info[to - 1].timestamp,
+            label: self.info[from - 1].label.clone()
+                .or_else(|| self.info[to - 1].label.clone()),
+        };
+        self.ops[from - 1] = ops;
+        self.info[from - 1] = info;
+        for checkpoint in self.checkpoints.values_mut() {
+            if checkpoint.position == from {
+                checkpoint.discarded = true;
+            }
+        }
+
+        let removed = chain[1..chain.len() - 1].iter().copied().collect();
+        let mut merged = BTreeMap::new();
+        let _ = merged.insert(to, from);
+        Ok(self.remove_entries(&removed, &merged))
+    }
+
+    /// Sets the maximum number of entries and the maximum serialized size of
+    /// the history, as measured in the given format. The limits are applied
+    /// when entries are pushed.
+    pub fn set_limits(
+        &mut self,
+        max_entries: Option<usize>,
+        max_bytes: Option<usize>,
+        size_format: Format)
+    {
+        self.max_entries = max_entries;
+        self.max_bytes = max_bytes;
+        if self.size_format != size_format {
+            self.size_format = size_format;
+            self.size = None;
+        }
+    }
+
+    /// Returns the number of entries which have been dropped due to the
+    /// limits.
+    pub fn dropped_count(&self) -> usize {
+        self.dropped
+    }
+
+    /// Drops the oldest entries until the history is within its limits.
+    fn enforce_limits(&mut self) {
+        if let Some(max_entries) = self.max_entries {
+            while self.ops.len() > max_entries {
+                match self.drop_oldest() {
+                    0     => break,
+                    count => self.dropped += count,
+                }
+            }
+        }
+        if let Some(max_bytes) = self.max_bytes {
+            while self.serialized_size() > max_bytes {
+                match self.drop_oldest() {
+                    0     => break,
+                    count => self.dropped += count,
+                }
+            }
+        }
+    }
+
+    /// Returns the serialized size of the history's entries, in bytes, as
+    /// measured in the history's size format.
+    ///
+    /// The entries are measured once, and the size is updated as entries are
+    /// added, changed, or removed.
+    pub fn serialized_size(&mut self) -> usize {
+        match self.size {
+            Some(size) => size,
+            None       => {
+                let size = (1..=self.ops.len())
+                    .map(|id| self.entry_size(id))
+                    .sum();
+                self.size = Some(size);
+                size
+            },
+        }
+    }
+
+    /// Returns the serialized size of the entry with the given id, in bytes.
+    fn entry_size(&self, id: usize) -> usize {
+        let entry = MeasuredEntry {
+            ops: &self.ops[id - 1][..],
+            info: self.entry_info(id),
+        };
+        self.size_format
+            .generate(&entry, PrettyConfig::new())
+            .map_or(0, |data| data.len())
+    }
+
+    /// Applies the given change to the entry with the given id, updating the
+    /// measured size of the history.
+    fn update_entry_size<F>(&mut self, id: usize, f: F)
+        where F: FnOnce(&mut Self)
+    {
+        let size = self.size
+            .map(|size| size.saturating_sub(self.entry_size(id)));
+        (f)(self);
+        self.size = size.map(|size| size + self.entry_size(id));
+    }
+
+    /// Fills in the entry info and parents of entries recorded without them.
+    fn normalize(&mut self) {
+        self.info.resize_with(self.ops.len(), EntryInfo::default);
+        while self.parents.len() < self.ops.len() {
+            self.parents.push(self.parents.len());
+        }
+    }
+
+    /// Sets a checkpoint with the given name at the cursor position, replacing
+    /// any existing checkpoint with the same name.
+    pub fn set_checkpoint(&mut self, name: String) {
+        let _ = self.checkpoints.insert(name, Checkpoint {
+            position: self.cursor,
+            discarded: false,
+        });
+    }
+
+    /// Returns the checkpoint with the given name.
+    pub fn checkpoint(&self, name: &str) -> Option<&Checkpoint> {
+        self.checkpoints.get(name)
+    }
+
+    /// Returns an iterator over the names of the checkpoints and their
+    /// checkpoints.
+    pub fn checkpoints(&self)
+        -> impl Iterator<Item=(&str, &Checkpoint)> + '_
+    {
+        self.checkpoints
+            .iter()
+            .map(|(name, checkpoint)| (name.as_str(), checkpoint))
+    }
+
+    /// Returns the info to record for new entries.
+    pub fn next_entry_info(&self) -> &EntryInfo {
+        &self.next_info
+    }
+
+    /// Sets the command and label to record for new entries.
+    pub fn set_next_entry_info(
+        &mut self,
+        command: Option<String>,
+        label: Option<String>)
+    {
+        self.next_info = EntryInfo { command, timestamp: None, label };
     }
 
     /// Returns the number of undo operations currently available.
     pub fn undo_count(&self) -> usize {
-        self.cursor
+        self.ancestors(self.cursor).count() - 1
     }
 
-    /// Returns the number of redo operations currently available.
+    /// Returns the number of redo operations currently available on the
+    /// current branch.
     pub fn redo_count(&self) -> usize {
-        self.ops.len() - self.cursor
+        std::iter::successors(Some(self.cursor), |id| self.redo_child(*id))
+            .count() - 1
     }
 
-    /// Pushes a new set of undo operations onto the history at the current
-    /// cursor position. This will truncate the history if there are any ops
-    /// beyond the cursor.
+    /// Pushes a new set of undo operations onto the history as a child of the
+    /// current entry. Any entries which were undone are kept on their own
+    /// branch.
     pub fn push_undo_ops(&mut self, ops: Vec<Operation>) {
+        let EntryInfo { command, label, .. } = self.next_info.clone();
+        self.push_entry(ops, command, label);
+    }
+
+    /// Pushes a new set of undo operations onto the history as a child of the
+    /// current entry, using the given command and label for its info.
+    pub fn push_entry(
+        &mut self,
+        ops: Vec<Operation>,
+        command: Option<String>,
+        label: Option<String>)
+    {
         assert_eq!(self.cursor_state, CursorState::Valid);
         tracing::trace!("History: cursor: {}, len: {}", self.cursor, self.ops.len());
 
-        if self.cursor >= self.ops.len() {
-            self.ops.push(ops);
-            self.cursor = self.ops.len();
-        } else {
-            self.ops[self.cursor] = ops;
-            self.ops.truncate(self.cursor);
-            self.cursor += 1;
+        self.normalize();
+        self.ops.push(ops);
+        self.info.push(EntryInfo {
+            command,
+            timestamp: current_timestamp(),
+            label,
+        });
+        self.parents.push(self.cursor);
+        let _ = self.selected.remove(&self.cursor);
+        self.cursor = self.ops.len();
+        if let Some(size) = self.size {
+            self.size = Some(size + self.entry_size(self.cursor));
         }
+        self.enforce_limits();
     }
 
     /// Performs a complete undo using the given operation transform function.
@@ -140,22 +736,25 @@ impl History {
     /// operation must be followed by `set_current_redo_ops` to ensure that the
     /// history remains in a valid state.
     pub fn pop_undo_ops(&mut self) -> Option<&[Operation]> {
-        if self.cursor == 0 {
-            return None;
-        }
-        self.cursor_state = CursorState::AwaitingSetRedo;
-        self.cursor -= 1;
+        let id = self.cursor;
+        let parent = self.parent(id)?;
+        self.select_child(parent, id);
+        self.cursor_state = CursorState::AwaitingSetRedo(id);
+        self.cursor = parent;
         self.ops
-            .get(self.cursor)
+            .get(id - 1)
             .map(|ops| &ops[..])
     }
 
     /// Sets the redo operations for the last returned undo operation. Must
     /// only be called after `pop_undo_ops`.
     pub fn set_current_redo_ops(&mut self, redo: Vec<Operation>) {
-        assert_eq!(self.cursor_state, CursorState::AwaitingSetRedo);
+        let id = match self.cursor_state {
+            CursorState::AwaitingSetRedo(id) => id,
+            state => panic!("invalid history cursor state: {:?}", state),
+        };
 
-        self.ops[self.cursor] = redo;
+        self.update_entry_size(id, |history| history.ops[id - 1] = redo);
         self.cursor_state = CursorState::Valid;
     }
 
@@ -163,22 +762,23 @@ impl History {
     /// operation must be followed by `set_current_undo_ops` to ensure that the
     /// history remains in a valid state.
     pub fn pop_redo_ops(&mut self) -> Option<&[Operation]> {
-        if self.cursor == self.ops.len() {
-            return None;
-        }
-        self.cursor_state = CursorState::AwaitingSetUndo;
-        self.cursor += 1;
+        let id = self.redo_child(self.cursor)?;
+        self.cursor_state = CursorState::AwaitingSetUndo(id);
+        self.cursor = id;
         self.ops
-            .get(self.cursor - 1)
+            .get(id - 1)
             .map(|ops| &ops[..])
     }
 
     /// Sets the undo operations for the last returned redo operation. Must
     /// only be called after `pop_repo_ops`.
     pub fn set_current_undo_ops(&mut self, undo: Vec<Operation>) {
-        assert_eq!(self.cursor_state, CursorState::AwaitingSetUndo);
+        let id = match self.cursor_state {
+            CursorState::AwaitingSetUndo(id) => id,
+            state => panic!("invalid history cursor state: {:?}", state),
+        };
 
-        self.ops[self.cursor - 1] = undo;
+        self.update_entry_size(id, |history| history.ops[id - 1] = undo);
         self.cursor_state = CursorState::Valid;
     }
 }
@@ -189,3 +789,84 @@ impl Default for History {
         History::new()
     }
 }
+
+/// A history entry, as serialized to measure the size of the history.
+#[derive(Serialize)]
+struct MeasuredEntry<'a> {
+    /// The undo or redo ops of the entry.
+    ops: &'a [Operation],
+    /// The info for the entry.
+    info: &'a EntryInfo,
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Transaction
+////////////////////////////////////////////////////////////////////////////////
+/// A pending set of changes which will be recorded as a single history entry
+/// when committed.
+#[derive(Debug, Clone, Default)]
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Transaction {
+    /// The undo ops which revert the changes, in the order they are applied.
+    undo_ops: Vec<Operation>,
+    /// The commands which made the changes.
+    commands: Vec<String>,
+    /// The label for the history entry.
+    label: Option<String>,
+}
+
+impl Transaction {
+    /// Constructs a new `Transaction` with the given label.
+    pub fn new(label: Option<String>) -> Self {
+        Transaction {
+            undo_ops: Vec::new(),
+            commands: Vec::new(),
+            label,
+        }
+    }
+
+    /// Returns true if the transaction has no changes.
+    pub fn is_empty(&self) -> bool {
+        self.undo_ops.is_empty()
+    }
+
+    /// Returns the commands which made the changes.
+    pub fn commands(&self) -> &[String] {
+        &self.commands[..]
+    }
+
+    /// Returns the label for the history entry.
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
+    /// Records the undo ops of a change made by the given command.
+    pub fn record(&mut self, undo_ops: Vec<Operation>, command: Option<&str>) {
+        // Later changes must be undone first.
+        let _ = self.undo_ops.splice(0..0, undo_ops);
+        if let Some(command) = command {
+            if self.commands.last().map(String::as_str) != Some(command) {
+                self.commands.push(command.to_owned());
+            }
+        }
+    }
+
+    /// Returns a savepoint marking the changes recorded so far.
+    pub fn savepoint(&self) -> usize {
+        self.undo_ops.len()
+    }
+
+    /// Removes and returns the undo ops of the changes recorded after the
+    /// given savepoint.
+    pub fn take_since(&mut self, savepoint: usize) -> Vec<Operation> {
+        let count = self.undo_ops.len().saturating_sub(savepoint);
+        self.undo_ops.drain(..count).collect()
+    }
+
+    /// Returns the undo ops, commands, and label of the transaction.
+    pub fn into_parts(self) -> (Vec<Operation>, Vec<String>, Option<String>) {
+        (self.undo_ops, self.commands, self.label)
+    }
+}
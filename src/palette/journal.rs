@@ -0,0 +1,313 @@
+////////////////////////////////////////////////////////////////////////////////
+// Atma structured color palette
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Palette change journal definitions.
+////////////////////////////////////////////////////////////////////////////////
+
+// Local imports.
+use crate::cell::Position;
+use crate::command::HistorySetOption;
+use crate::error::FileError;
+use crate::error::FileErrorContext as _;
+use crate::palette::Operation;
+
+// External library imports.
+use serde::Serialize;
+use serde::Deserialize;
+
+// Standard library imports.
+use std::ffi::OsString;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// JournalRecord
+////////////////////////////////////////////////////////////////////////////////
+/// A change to a palette recorded in its journal.
+#[derive(Debug, Clone)]
+#[derive(Serialize, Deserialize)]
+pub enum JournalRecord {
+    /// Operations were applied to the palette.
+    Apply {
+        /// The applied operations.
+        ops: Vec<Operation>,
+        /// The command which applied the operations.
+        command: Option<String>,
+        /// The history entry label.
+        label: Option<String>,
+        /// The time the operations were applied, in seconds since the unix
+        /// epoch.
+        timestamp: Option<u64>,
+    },
+
+    /// History entries were undone.
+    Undo {
+        /// The number of entries undone.
+        count: usize,
+    },
+
+    /// History entries were redone.
+    Redo {
+        /// The number of entries redone.
+        count: usize,
+    },
+
+    /// A palette metadata field was set or cleared.
+    SetMetadata {
+        /// The metadata field name.
+        key: String,
+        /// The metadata field values.
+        values: Vec<String>,
+    },
+
+    /// The palette's history setting was changed.
+    SetHistoryOption {
+        /// The history setting.
+        setting: HistorySetOption,
+    },
+
+    /// A history checkpoint was set at the current history position.
+    SetCheckpoint {
+        /// The name of the checkpoint.
+        name: String,
+    },
+
+    /// History entries were undone and redone to move to a history entry.
+    MoveHistory {
+        /// The id of the history entry.
+        id: usize,
+    },
+
+    /// The entries of a history branch were removed.
+    PruneBranch {
+        /// The tip entry of the branch.
+        branch: usize,
+    },
+
+    /// The history entries which are not on the current branch were removed.
+    PruneInactiveBranches,
+
+    /// A range of history entries was merged into a single entry.
+    SquashHistory {
+        /// The first entry of the range.
+        from: usize,
+        /// The last entry of the range.
+        to: usize,
+    },
+
+    /// All history entries were removed.
+    PruneHistory,
+
+    /// A transaction was begun.
+    BeginTransaction {
+        /// The label for the transaction's history entry.
+        label: Option<String>,
+    },
+
+    /// The pending transaction was committed.
+    CommitTransaction {
+        /// The time the transaction was committed, in seconds since the unix
+        /// epoch.
+        timestamp: Option<u64>,
+    },
+
+    /// The pending transaction was rolled back.
+    RollbackTransaction,
+
+    /// The pending transaction was rolled back to a savepoint.
+    RollbackToSavepoint {
+        /// The savepoint.
+        savepoint: usize,
+    },
+
+    /// The palette state which is not changed by operations was saved.
+    State {
+        /// The position cursor.
+        position_cursor: Position,
+        /// The metadata modification time.
+        modified: Option<u64>,
+    },
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// JournalEntry
+////////////////////////////////////////////////////////////////////////////////
+/// A numbered journal record, as stored in a journal file.
+#[derive(Debug, Clone)]
+#[derive(Serialize, Deserialize)]
+pub struct JournalEntry {
+    /// The sequence number of the record.
+    pub seq: u64,
+    /// The journal record.
+    pub record: JournalRecord,
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Journal
+////////////////////////////////////////////////////////////////////////////////
+/// Tracks the palette changes to append to a journal file.
+///
+/// A journal file stores one `JournalEntry` per line, next to the palette file.
+/// Entries are replayed when the palette is loaded, and are folded back into
+/// the palette file when the journal is compacted.
+#[derive(Debug, Clone, Default)]
+pub struct Journal {
+    /// The number of records to allow in the journal file before compacting
+    /// it, or `None` if journaling is disabled.
+    compact_threshold: Option<usize>,
+    /// The records which have not been written.
+    records: Vec<JournalRecord>,
+    /// The number of records in the journal file.
+    len: usize,
+    /// Whether the palette was changed in a way which can't be recorded.
+    incomplete: bool,
+}
+
+impl Journal {
+    /// Returns the path of the journal file for the palette file at the given
+    /// path.
+    pub fn path_for(palette_path: &Path) -> PathBuf {
+        let mut file_name = palette_path
+            .file_name()
+            .map(OsString::from)
+            .unwrap_or_default();
+        file_name.push(".journal");
+        palette_path.with_file_name(file_name)
+    }
+
+    /// Reads the journal file at the given path, returning a `Journal`
+    /// tracking it along with the entries it contains. A missing file is
+    /// treated as an empty journal.
+    ///
+    /// An unparsable final line is assumed to be a partially written record
+    /// and is ignored. The returned journal is then marked incomplete so that
+    /// it is compacted on the next write.
+    pub fn read_from_path(path: &Path)
+        -> Result<(Self, Vec<JournalEntry>), FileError>
+    {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Ok((Journal::default(), Vec::new()));
+            },
+            Err(e) => return Err(e).with_context(|| format!(
+                "Failed to read journal file {:?}",
+                path)),
+        };
+
+        let mut journal = Journal::default();
+        let mut entries = Vec::new();
+        let lines: Vec<&str> = text
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .collect();
+        for (i, line) in lines.iter().enumerate() {
+            match ron::de::from_str::<JournalEntry>(line) {
+                Ok(entry) => entries.push(entry),
+                Err(_) if i + 1 == lines.len() => {
+                    tracing::warn!("Ignoring incomplete record in journal \
+                        file {:?}", path);
+                    journal.incomplete = true;
+                },
+                Err(e) => return Err(e).with_context(|| format!(
+                    "Failed parsing journal file {:?}",
+                    path)),
+            }
+        }
+        journal.len = entries.len();
+        Ok((journal, entries))
+    }
+
+    /// Enables or disables journaling. If enabled, the journal file is
+    /// compacted once it would hold more than the given number of records.
+    pub fn set_compact_threshold(&mut self, compact_threshold: Option<usize>) {
+        self.compact_threshold = compact_threshold;
+    }
+
+    /// Returns true if journaling is enabled.
+    pub fn is_enabled(&self) -> bool {
+        self.compact_threshold.is_some()
+    }
+
+    /// Records a change to append to the journal file.
+    pub fn record(&mut self, record: JournalRecord) {
+        if self.is_enabled() { self.records.push(record); }
+    }
+
+    /// Marks the journal as incomplete, indicating that the palette was
+    /// changed in a way which can't be recorded.
+    pub fn invalidate(&mut self) {
+        self.incomplete = true;
+    }
+
+    /// Discards the unwritten records, to be used after the palette file is
+    /// rewritten.
+    pub fn reset(&mut self) {
+        self.records.clear();
+        self.len = 0;
+        self.incomplete = false;
+    }
+
+    /// Appends the unwritten records to the journal file at the given path,
+    /// numbering them after the given sequence number. Returns the sequence
+    /// number of the last record written, or `None` if the records were not
+    /// written because the journal must be compacted instead.
+    pub fn append_to_path(&mut self, path: &Path, seq: u64)
+        -> Result<Option<u64>, FileError>
+    {
+        let compact_threshold = match self.compact_threshold {
+            Some(compact_threshold) => compact_threshold,
+            None => return Ok(None),
+        };
+        if self.incomplete
+            || self.len + self.records.len() > compact_threshold
+        {
+            return Ok(None);
+        }
+
+        let mut text = String::new();
+        let mut last_seq = seq;
+        for record in self.records.drain(..) {
+            last_seq += 1;
+            let entry = JournalEntry { seq: last_seq, record };
+            text.push_str(&ron::ser::to_string(&entry)?);
+            text.push('\n');
+            self.len += 1;
+        }
+
+        let mut file = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(path)
+            .with_context(|| format!(
+                "Failed to create/open journal file {:?} for writing",
+                path))?;
+        file.write_all(text.as_bytes())
+            .and_then(|_| file.sync_data())
+            .with_context(|| format!(
+                "Failed to write journal file {:?}",
+                path))?;
+        Ok(Some(last_seq))
+    }
+
+    /// Removes the journal file at the given path, if it exists.
+    pub fn remove_path(path: &Path) -> Result<(), FileError> {
+        match std::fs::remove_file(path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                Err(e).with_context(|| format!(
+                    "Failed to remove journal file {:?}",
+                    path))
+            },
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,161 @@
+////////////////////////////////////////////////////////////////////////////////
+// Atma structured color palette
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Palette libraries for cross-palette references.
+////////////////////////////////////////////////////////////////////////////////
+
+// Local imports.
+use crate::cell::CellRef;
+use crate::color::Color;
+use crate::error::PaletteError;
+use crate::palette::BasicPalette;
+use crate::palette::Palette;
+use crate::utility::normalize_path;
+
+// Standard library imports.
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::path::Path;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Library
+////////////////////////////////////////////////////////////////////////////////
+/// Resolves palette library aliases and caches the loaded library palettes.
+///
+/// Library palettes are loaded read-only on first use. A `Library` shares its
+/// cache with the libraries of every palette it loads, so each file is loaded
+/// at most once and reference cycles can be detected across files.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(test, derive(PartialEq))]
+pub struct Library {
+    /// The directory used to resolve relative library paths.
+    base_dir: Option<PathBuf>,
+    /// The state shared by all palettes loaded through this library.
+    shared: Rc<RefCell<LibraryState>>,
+}
+
+/// The shared state of a `Library`.
+#[derive(Debug, Default)]
+#[cfg_attr(test, derive(PartialEq))]
+struct LibraryState {
+    /// Fallback library aliases, usually provided by the `Config`.
+    aliases: BTreeMap<String, PathBuf>,
+    /// The loaded library palettes.
+    palettes: HashMap<PathBuf, Rc<BasicPalette>>,
+    /// The library cells whose colors are currently being evaluated.
+    active: HashSet<(PathBuf, u32)>,
+}
+
+impl Library {
+    /// Constructs a new `Library` with the given fallback aliases.
+    pub fn new(aliases: BTreeMap<String, PathBuf>) -> Self {
+        Library {
+            base_dir: None,
+            shared: Rc::new(RefCell::new(LibraryState {
+                aliases,
+                .. Default::default()
+            })),
+        }
+    }
+
+    /// Returns the given `Library` with the given base directory for resolving
+    /// relative library paths.
+    pub fn with_base_dir<P>(mut self, base_dir: Option<P>) -> Self
+        where P: AsRef<Path>
+    {
+        self.base_dir = base_dir.map(|p| p.as_ref().to_owned());
+        self
+    }
+
+    /// Retreives a copy of the color associated with the given `CellRef`
+    /// within the library palette with the given alias. Aliases are looked up
+    /// in the given palette aliases before the fallback aliases.
+    pub(in crate) fn color<'name>(
+        &self,
+        library: &Cow<'name, str>,
+        cell_ref: &CellRef<'name>,
+        aliases: &BTreeMap<Cow<'static, str>, PathBuf>)
+        -> Result<Option<Color>, PaletteError>
+    {
+        let external = || CellRef::External {
+            library: library.clone(),
+            cell_ref: Box::new(cell_ref.clone()),
+        }.into_static();
+
+        let (path, palette) = self.load(library, aliases)?;
+        let idx = palette
+            .resolve_ref_to_index(cell_ref)
+            .map_err(|_| PaletteError::UndefinedCellReference {
+                cell_ref: external(),
+            })?;
+
+        let key = (path, idx);
+        if !self.shared.borrow_mut().active.insert(key.clone()) {
+            return Err(PaletteError::UndefinedColor {
+                cell_ref: external(),
+                circular: true,
+            });
+        }
+        let res = palette.color(cell_ref);
+        let _ = self.shared.borrow_mut().active.remove(&key);
+        res
+    }
+
+    /// Loads the library palette with the given alias, returning its path and
+    /// contents.
+    fn load<'name>(
+        &self,
+        library: &Cow<'name, str>,
+        aliases: &BTreeMap<Cow<'static, str>, PathBuf>)
+        -> Result<(PathBuf, Rc<BasicPalette>), PaletteError>
+    {
+        let path = match aliases.get(library.as_ref()) {
+            Some(path) => match &self.base_dir {
+                Some(base_dir) => normalize_path(base_dir, path),
+                None           => path.clone(),
+            },
+            None => self.shared
+                .borrow()
+                .aliases
+                .get(library.as_ref())
+                .cloned()
+                .ok_or_else(|| PaletteError::UndefinedLibrary {
+                    library: Cow::from(library.clone().into_owned()),
+                })?,
+        };
+        let path = path.canonicalize().unwrap_or(path);
+
+        if let Some(palette) = self.shared.borrow().palettes.get(&path) {
+            return Ok((path, palette.clone()));
+        }
+
+        let mut palette = Palette::read_from_path(&path)
+            .map_err(|e| PaletteError::LibraryLoadFailed {
+                library: Cow::from(library.clone().into_owned()),
+                path: path.clone(),
+                source: Box::new(e),
+            })?
+            .into_inner();
+        palette.set_library(Library {
+            base_dir: path.parent().map(Path::to_owned),
+            shared: self.shared.clone(),
+        });
+
+        let palette = Rc::new(palette);
+        let _ = self.shared
+            .borrow_mut()
+            .palettes
+            .insert(path.clone(), palette.clone());
+        Ok((path, palette))
+    }
+}
@@ -0,0 +1,154 @@
+////////////////////////////////////////////////////////////////////////////////
+// Atma structured color palette
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Palette-level metadata.
+////////////////////////////////////////////////////////////////////////////////
+
+// Local imports.
+use crate::error::PaletteError;
+use crate::utility::current_timestamp;
+use crate::utility::format_timestamp;
+
+// External library imports.
+use serde::Deserialize;
+use serde::Serialize;
+
+// Standard library imports.
+use std::collections::BTreeMap;
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Metadata
+////////////////////////////////////////////////////////////////////////////////
+/// Descriptive information about a palette.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Metadata {
+    /// The title of the palette.
+    pub title: Option<String>,
+    /// The authors of the palette.
+    pub authors: Vec<String>,
+    /// The license of the palette.
+    pub license: Option<String>,
+    /// A description of the palette.
+    pub description: Option<String>,
+    /// Tags for categorizing the palette.
+    pub tags: Vec<String>,
+    /// The creation time of the palette, in seconds since the unix epoch.
+    pub created: Option<u64>,
+    /// The last modification time of the palette, in seconds since the unix
+    /// epoch.
+    pub modified: Option<u64>,
+    /// Free-form metadata entries.
+    pub extra: BTreeMap<String, String>,
+}
+
+impl Metadata {
+    /// The names of the standard metadata fields.
+    pub const FIELDS: [&'static str; 7] = [
+        "title",
+        "authors",
+        "license",
+        "description",
+        "tags",
+        "created",
+        "modified",
+    ];
+
+    /// Constructs a new `Metadata` with the creation time set to the current
+    /// time.
+    pub fn new() -> Self {
+        let now = current_timestamp();
+        Metadata {
+            created: now,
+            modified: now,
+            .. Default::default()
+        }
+    }
+
+    /// Sets the modification time to the current time.
+    pub fn touch(&mut self) {
+        self.modified = current_timestamp();
+    }
+
+    /// Returns the value of the metadata field with the given name, or `None`
+    /// if it is not set. List fields are joined with commas.
+    pub fn get(&self, key: &str) -> Option<String> {
+        match key {
+            "title"       => self.title.clone(),
+            "authors"     => join_list(&self.authors[..]),
+            "license"     => self.license.clone(),
+            "description" => self.description.clone(),
+            "tags"        => join_list(&self.tags[..]),
+            "created"     => self.created.map(format_timestamp),
+            "modified"    => self.modified.map(format_timestamp),
+            _             => self.extra.get(key).cloned(),
+        }
+    }
+
+    /// Sets the metadata field with the given name. If no values are provided,
+    /// the field is cleared.
+    ///
+    /// ### Errors
+    ///
+    /// Returns a `PaletteError` if multiple values are provided for a
+    /// single-valued field, or if the field is read-only.
+    pub fn set(&mut self, key: &str, mut values: Vec<String>)
+        -> Result<(), PaletteError>
+    {
+        match key {
+            "authors" => { self.authors = values; return Ok(()); },
+            "tags"    => { self.tags = values; return Ok(()); },
+
+            "created" |
+            "modified" => return Err(PaletteError::InvalidInputValue {
+                msg: format!("metadata field '{}' is read-only", key).into(),
+            }),
+            _ => (),
+        }
+
+        if values.len() > 1 {
+            return Err(PaletteError::InvalidInputValue {
+                msg: format!("metadata field '{}' takes a single value", key)
+                    .into(),
+            });
+        }
+        let value = values.pop();
+
+        match key {
+            "title"       => self.title = value,
+            "license"     => self.license = value,
+            "description" => self.description = value,
+            _             => match value {
+                Some(value) => { let _ = self.extra.insert(key.into(), value); },
+                None        => { let _ = self.extra.remove(key); },
+            },
+        }
+        Ok(())
+    }
+
+    /// Returns an iterator over the names and values of all set metadata
+    /// fields.
+    pub fn entries(&self) -> impl Iterator<Item=(String, String)> + '_ {
+        Metadata::FIELDS
+            .iter()
+            .filter_map(move |k| self.get(k).map(|v| ((*k).into(), v)))
+            .chain(self.extra
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone())))
+    }
+}
+
+/// Joins a list of metadata values, returning `None` if the list is empty.
+fn join_list(values: &[String]) -> Option<String> {
+    if values.is_empty() {
+        None
+    } else {
+        Some(values.join(", "))
+    }
+}
@@ -13,6 +13,7 @@ use crate::cell::Cell;
 use crate::cell::CellRef;
 use crate::cell::Position;
 use crate::cell::PositionSelector;
+use crate::palette::Constraints;
 use crate::palette::Expr;
 
 // External library imports.
@@ -21,6 +22,7 @@ use serde::Deserialize;
 
 // Standard library imports.
 use std::borrow::Cow;
+use std::path::PathBuf;
 
 
 ////////////////////////////////////////////////////////////////////////////////
@@ -112,6 +114,72 @@ pub enum Operation {
         cell_ref: CellRef<'static>,
     },
 
+    ////////////////////////////////////////////////////////////////////////////
+    // Metadata operations
+    ////////////////////////////////////////////////////////////////////////////
+
+    /// Assigns a metadata entry to a cell.
+    AssignMetadata {
+        /// A reference to the `Cell` to assign the metadata to.
+        cell_ref: CellRef<'static>,
+        /// The metadata key.
+        key: Cow<'static, str>,
+        /// The metadata value.
+        value: Cow<'static, str>,
+    },
+
+    /// Unassigns a metadata entry for a cell.
+    UnassignMetadata {
+        /// A reference to the `Cell` to unassign the metadata for.
+        cell_ref: CellRef<'static>,
+        /// The metadata key to unassign.
+        key: Cow<'static, str>,
+    },
+
+    ////////////////////////////////////////////////////////////////////////////
+    // Lock operations
+    ////////////////////////////////////////////////////////////////////////////
+
+    /// Locks a cell, preventing changes to it.
+    Lock {
+        /// A reference to the `Cell` to lock.
+        cell_ref: CellRef<'static>,
+    },
+
+    /// Unlocks a cell.
+    Unlock {
+        /// A reference to the `Cell` to unlock.
+        cell_ref: CellRef<'static>,
+    },
+
+    ////////////////////////////////////////////////////////////////////////////
+    // Constraint operations
+    ////////////////////////////////////////////////////////////////////////////
+
+    /// Sets the palette's constraints.
+    SetConstraints {
+        /// The constraints to set.
+        constraints: Constraints,
+    },
+
+    ////////////////////////////////////////////////////////////////////////////
+    // Library operations
+    ////////////////////////////////////////////////////////////////////////////
+
+    /// Assigns the path of a palette library alias.
+    SetLibraryPath {
+        /// The library alias.
+        library: Cow<'static, str>,
+        /// The path of the library palette.
+        path: PathBuf,
+    },
+
+    /// Unassigns the path of a palette library alias.
+    UnsetLibraryPath {
+        /// The library alias.
+        library: Cow<'static, str>,
+    },
+
     ////////////////////////////////////////////////////////////////////////////
     // Expr operations
     ////////////////////////////////////////////////////////////////////////////
@@ -125,6 +193,25 @@ pub enum Operation {
     },
 
 
+    /// Sets the color expression override for a cell in a variant.
+    SetVariantExpr {
+        /// The name of the variant.
+        variant: Cow<'static, str>,
+        /// A reference to the `Cell` to set the `Expr` for.
+        cell_ref: CellRef<'static>,
+        /// The expression to set.
+        expr: Expr,
+    },
+
+    /// Removes the color expression override for a cell in a variant.
+    UnsetVariantExpr {
+        /// The name of the variant.
+        variant: Cow<'static, str>,
+        /// A reference to the `Cell` to remove the `Expr` for.
+        cell_ref: CellRef<'static>,
+    },
+
+
     ////////////////////////////////////////////////////////////////////////////
     // Positioning operations
     ////////////////////////////////////////////////////////////////////////////
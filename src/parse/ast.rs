@@ -39,6 +39,7 @@ use tephra::combinator::end_of_text;
 use tephra::combinator::repeat_collect;
 use tephra::combinator::right;
 use tephra::combinator::section;
+use tephra::combinator::seq;
 use tephra::combinator::spanned;
 use tephra::combinator::text;
 use tephra::lexer::Lexer;
@@ -489,6 +490,13 @@ pub fn primary_expr<'text, Cm>(lexer: Lexer<'text, AtmaScanner, Cm>)
 
     use AtmaToken::*;
     match lexer.peek() {
+        // External cell references must come before Ident.
+        Some(Ident) if seq(&[Ident, Colon, Colon])
+            (lexer.clone())
+            .is_ok() => cell_ref
+            (lexer)
+            .map_value(PrimaryExpr::CellRef),
+
         Some(Ident) => text(one(Ident))
             (lexer)
             .map_value(PrimaryExpr::Ident),
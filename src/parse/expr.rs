@@ -745,6 +745,9 @@ impl AstExprMatch for ColorSpace {
         let ast_span = ast_expr.span();
         match Ident::match_expr(ast_expr, metrics) {
             Ok(Ident(ident)) if ident == "rgb" => Ok(ColorSpace::Rgb),
+            Ok(Ident(ident)) if ident == "linear_rgb" => Ok(
+                ColorSpace::LinearRgb
+            ),
 
             _ => Err(ParseError::new("expected color space")
             .with_span("unrecognized color space", ast_span, metrics))
@@ -114,6 +114,13 @@ pub enum AtmaToken {
     Plus,
     /// A minus or hyphen character '-'.
     Minus,
+    /// An at character '@'.
+    At,
+    /// An equals character '='.
+    Equal,
+    /// A tilde character '~'. Can only be parsed imediately following a Colon
+    /// token.
+    Tilde,
 
     /// A floating point number.
     Float,
@@ -176,6 +183,9 @@ impl std::fmt::Display for AtmaToken {
             Mult              => write!(f, "'*'"),
             Plus              => write!(f, "'+'"),
             Minus             => write!(f, "'-'"),
+            At                => write!(f, "'@'"),
+            Equal             => write!(f, "'='"),
+            Tilde             => write!(f, "'~'"),
             Float             => write!(f, "float"),
             Decimal           => write!(f, "'.'"),
             Uint              => write!(f, "integer"),
@@ -650,6 +660,9 @@ impl AtmaScanner {
                 return_if_some!(self
                     .parse_str(source, base, metrics, "-", Minus));
 
+                return_if_some!(self
+                    .parse_str(source, base, metrics, "~", Tilde));
+
 
                 self.open = None;
                 self.scan(source, base, metrics)
@@ -738,6 +751,10 @@ impl AtmaScanner {
                     .parse_str(source, base, metrics, "+", Plus));
                 return_if_some!(self
                     .parse_str(source, base, metrics, "-", Minus));
+                return_if_some!(self
+                    .parse_str(source, base, metrics, "@", At));
+                return_if_some!(self
+                    .parse_str(source, base, metrics, "=", Equal));
                 
                 // Float must be parsed before Uint and Decimal.
                 return_if_some!(self.parse_float(source, base, metrics));
@@ -17,7 +17,9 @@ use crate::cell::CellRef;
 use crate::cell::CellSelection;
 use crate::cell::CellSelector;
 use crate::cell::Position;
+use crate::cell::PositionOffset;
 use crate::cell::PositionSelector;
+use crate::cell::REF_GROUP_TREE_TOKEN;
 use crate::parse::AtmaScanner;
 use crate::parse::AtmaToken;
 use crate::parse::PositionOrIndex;
@@ -71,6 +73,12 @@ pub fn cell_ref<'text, Cm>(mut lexer: Lexer<'text, AtmaScanner, Cm>)
 
     use AtmaToken::*;
     match lexer.peek() {
+        Some(Colon) if seq(&[Colon, Tilde])
+            (lexer.clone())
+            .is_ok() => position_offset
+            (lexer)
+            .map_value(CellRef::Relative),
+
         Some(Colon) => position_or_index
             (lexer)
             .map_value(CellRef::from),
@@ -84,12 +92,46 @@ pub fn cell_ref<'text, Cm>(mut lexer: Lexer<'text, AtmaScanner, Cm>)
                 None      => CellRef::Name(name),
             }),
 
+        Some(Ident) => external_cell_ref
+            (lexer),
+
         _ => fail
             (lexer)
             .map_value(|_| unreachable!())
     }
 }
 
+pub fn external_cell_ref<'text, Cm>(mut lexer: Lexer<'text, AtmaScanner, Cm>)
+    -> ParseResult<'text, AtmaScanner, Cm, CellRef<'text>>
+    where Cm: ColumnMetrics,
+{
+    let span = span!(Level::DEBUG, "external_cell_ref");
+    let _enter = span.enter();
+
+    use AtmaToken::*;
+    let (library, succ) = exact(
+        left(
+            text(one(Ident)),
+            seq(&[Colon, Colon])))
+        (lexer)?
+        .take_value();
+
+    // Names within the library palette may be given as identifiers.
+    let mut lexer = succ.lexer;
+    let res = match lexer.peek() {
+        Some(Ident) => text(one(Ident))
+            (lexer)
+            .map_value(|name| CellRef::Name(Cow::from(name))),
+        _ => cell_ref
+            (lexer),
+    };
+
+    res.map_value(|cell_ref| CellRef::External {
+        library: Cow::from(library),
+        cell_ref: Box::new(cell_ref),
+    })
+}
+
 pub fn position_or_index<'text, Cm>(mut lexer: Lexer<'text, AtmaScanner, Cm>)
     -> ParseResult<'text, AtmaScanner, Cm, PositionOrIndex>
     where Cm: ColumnMetrics,
@@ -175,6 +217,67 @@ pub fn position<'text, Cm>(mut lexer: Lexer<'text, AtmaScanner, Cm>)
         .map_value(|(page, (line, column))| Position { page, line, column })
 }
 
+pub fn position_offset<'text, Cm>(mut lexer: Lexer<'text, AtmaScanner, Cm>)
+    -> ParseResult<'text, AtmaScanner, Cm, PositionOffset>
+    where Cm: ColumnMetrics,
+{
+    let span = span!(Level::DEBUG, "position_offset");
+    let _enter = span.enter();
+
+    use AtmaToken::*;
+    let (first, succ) = exact(
+        right(seq(&[Colon, Tilde]),
+            signed_offset))
+        (lexer)?
+        .take_value();
+
+    let (second, succ) = exact(
+        atomic(
+            right(one(Decimal), signed_offset)))
+        (succ.lexer)?
+        .take_value();
+
+    let (third, succ) = match second {
+        Some(_) => exact(
+            atomic(
+                right(one(Decimal), signed_offset)))
+            (succ.lexer)?
+            .take_value(),
+        None => (None, succ),
+    };
+
+    // A single offset is a column offset, and two offsets are line and column
+    // offsets.
+    let offset = match (second, third) {
+        (None, _) => PositionOffset { page: 0, line: 0, column: first },
+        (Some(second), None) => PositionOffset {
+            page: 0,
+            line: first,
+            column: second,
+        },
+        (Some(second), Some(third)) => PositionOffset {
+            page: first,
+            line: second,
+            column: third,
+        },
+    };
+    Ok(succ.map_value(|_| offset))
+}
+
+fn signed_offset<'text, Cm>(mut lexer: Lexer<'text, AtmaScanner, Cm>)
+    -> ParseResult<'text, AtmaScanner, Cm, i32>
+    where Cm: ColumnMetrics,
+{
+    let span = span!(Level::DEBUG, "signed_offset");
+    let _enter = span.enter();
+
+    both(
+        maybe(one(AtmaToken::Minus)),
+        uint::<_, i32>)
+        (lexer)
+        .map_value(|(neg, val)| if neg.is_some() { -val } else { val })
+}
+
 pub fn group_or_name<'text, Cm>(mut lexer: Lexer<'text, AtmaScanner, Cm>)
     -> ParseResult<'text, AtmaScanner, Cm, (Cow<'text, str>, Option<u32>)>
     where Cm: ColumnMetrics,
@@ -316,7 +419,21 @@ pub fn cell_selector<'text, Cm>(mut lexer: Lexer<'text, AtmaScanner, Cm>)
                 Err(None)       => (),
             }
 
-            // Group and Name must come after GroupAll.
+            // GroupTree must come after GroupAll.
+            match exact(string)
+                (lexer.clone())
+                .filter_lexer_error()
+            {
+                Ok(succ) if succ.value.ends_with(REF_GROUP_TREE_TOKEN) => {
+                    return Ok(succ)
+                        .map_value(|path| GroupTree(
+                            strip_group_tree_suffix(path)))
+                },
+                Err(Some(fail)) => return Err(fail),
+                _               => (),
+            }
+
+            // Group and Name must come after GroupAll and GroupTree.
             let (val, succ) = range(group_or_name)
                 (lexer.clone())?
                 .take_value();
@@ -367,6 +484,33 @@ pub fn cell_selector<'text, Cm>(mut lexer: Lexer<'text, AtmaScanner, Cm>)
             }
         },
 
+        Some(At) => metadata_selector
+            (lexer)
+            .map_value(|(key, value)| Metadata { key, value }),
+
+        // External references can't select cells, but are parsed so that they
+        // produce a useful error.
+        Some(Ident) => {
+            let (cell_ref, succ) = external_cell_ref
+                (lexer)?
+                .take_value();
+            match CellSelector::try_from(cell_ref) {
+                Ok(selector) => Ok(Success {
+                    lexer: succ.lexer,
+                    value: selector,
+                }),
+                Err(e) => Err(Failure {
+                    parse_error: ParseError::new("invalid cell selector")
+                        .with_span(
+                            format!("{}", e),
+                            succ.lexer.token_span(),
+                            succ.lexer.column_metrics()),
+                    lexer: succ.lexer,
+                    source: None,
+                }),
+            }
+        },
+
         // TODO: Give a better error message.
         _ => fail
             (lexer)
@@ -374,6 +518,53 @@ pub fn cell_selector<'text, Cm>(mut lexer: Lexer<'text, AtmaScanner, Cm>)
     }
 }
 
+/// Removes the group subtree suffix from a group path.
+fn strip_group_tree_suffix<'text>(path: Cow<'text, str>) -> Cow<'text, str> {
+    let len = path.len() - REF_GROUP_TREE_TOKEN.len();
+    match path {
+        Cow::Borrowed(path) => Cow::Borrowed(&path[..len]),
+        Cow::Owned(mut path) => {
+            path.truncate(len);
+            Cow::Owned(path)
+        },
+    }
+}
+
+pub fn metadata_selector<'text, Cm>(mut lexer: Lexer<'text, AtmaScanner, Cm>)
+    -> ParseResult<'text, AtmaScanner, Cm,
+        (Cow<'text, str>, Option<Cow<'text, str>>)>
+    where Cm: ColumnMetrics,
+{
+    let span = span!(Level::DEBUG, "metadata_selector");
+    let _enter = span.enter();
+
+    exact(
+        right(one(AtmaToken::At),
+            both(
+                ident_or_string,
+                atomic(
+                    right(one(AtmaToken::Equal),
+                        ident_or_string)))))
+        (lexer)
+}
+
+fn ident_or_string<'text, Cm>(mut lexer: Lexer<'text, AtmaScanner, Cm>)
+    -> ParseResult<'text, AtmaScanner, Cm, Cow<'text, str>>
+    where Cm: ColumnMetrics,
+{
+    let span = span!(Level::DEBUG, "ident_or_string");
+    let _enter = span.enter();
+
+    match lexer.peek() {
+        Some(AtmaToken::Ident) => text(one(AtmaToken::Ident))
+            (lexer)
+            .map_value(Cow::from),
+
+        _ => string
+            (lexer),
+    }
+}
+
 pub fn position_selector<'text, Cm>(mut lexer: Lexer<'text, AtmaScanner, Cm>)
     -> ParseResult<'text, AtmaScanner, Cm, PositionSelector>
     where Cm: ColumnMetrics,
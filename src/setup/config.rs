@@ -12,6 +12,7 @@
 // Local imports.
 use crate::command::ColorDisplay;
 use crate::command::ColorStyle;
+use crate::command::check::Severity;
 use crate::command::CursorBehavior;
 use crate::command::GutterStyle;
 use crate::command::LineStyle;
@@ -21,19 +22,19 @@ use crate::command::RuleStyle;
 use crate::command::TextStyle;
 use crate::error::FileError;
 use crate::error::FileErrorContext as _;
+use crate::format::Format;
 use crate::setup::LoadStatus;
 use crate::setup::TraceConfig;
+use crate::utility::normalize_path;
 
 // External library imports.
 use serde::Deserialize;
 use serde::Serialize;
 
 // Standard library imports.
+use std::collections::BTreeMap;
 use std::fs::File;
 use std::fs::OpenOptions;
-use std::io::BufWriter;
-use std::io::Read as _;
-use std::io::Write as _;
 use std::path::Path;
 use std::path::PathBuf;
 
@@ -92,6 +93,21 @@ pub const DEFAULT_DEFAULT_LIST_LINE_STYLE: LineStyle = LineStyle::Auto;
 /// Default value for default_list_gutter_style.
 pub const DEFAULT_DEFAULT_LIST_GUTTER_STYLE: GutterStyle = GutterStyle::Auto;
 
+/// Default value for check_near_duplicate_delta_e.
+pub const DEFAULT_CHECK_NEAR_DUPLICATE_DELTA_E: f32 = 2.3;
+
+/// Default value for history_max_entries.
+pub const DEFAULT_HISTORY_MAX_ENTRIES: Option<usize> = None;
+
+/// Default value for history_max_bytes.
+pub const DEFAULT_HISTORY_MAX_BYTES: Option<usize> = None;
+
+/// Default value for journal.
+pub const DEFAULT_JOURNAL: bool = false;
+
+/// Default value for journal_compact_threshold.
+pub const DEFAULT_JOURNAL_COMPACT_THRESHOLD: usize = 64;
+
 /// The default value for  invalid_color_display_fallback.
 pub const DEFAULT_INVALID_COLOR_DISPLAY_FALLBACK: ColorDisplay = ColorDisplay {
     color_style: ColorStyle::None,
@@ -182,6 +198,42 @@ pub struct Config {
     /// The fallback ColorDisplay for when the provided combination is invalid.
     #[serde(default = "Config::default_invalid_color_display_fallback")]
     pub invalid_color_display_fallback: ColorDisplay,
+
+    /// Palette library aliases for cross-palette references. These are used
+    /// when an alias is not assigned in the palette itself.
+    #[serde(default)]
+    pub palette_libraries: BTreeMap<String, PathBuf>,
+
+    /// Severity overrides for the check command rules, keyed by rule code or
+    /// name. Rules are disabled by setting their severity to `Allow`.
+    #[serde(default)]
+    pub check_rules: BTreeMap<String, Severity>,
+
+    /// The color difference below which the check command reports colors as
+    /// near-duplicates.
+    #[serde(default = "Config::default_check_near_duplicate_delta_e")]
+    pub check_near_duplicate_delta_e: f32,
+
+    /// The maximum number of palette history entries. The oldest entries are
+    /// dropped when the limit is exceeded.
+    #[serde(default = "Config::default_history_max_entries")]
+    pub history_max_entries: Option<usize>,
+
+    /// The maximum serialized size of the palette history's entries, in bytes,
+    /// as measured in the palette's file format. The oldest entries are
+    /// dropped when the limit is exceeded.
+    #[serde(default = "Config::default_history_max_bytes")]
+    pub history_max_bytes: Option<usize>,
+
+    /// Whether to append palette changes to a journal file next to the
+    /// palette instead of rewriting the palette file.
+    #[serde(default = "Config::default_journal")]
+    pub journal: bool,
+
+    /// The number of records to allow in a palette journal file before it is
+    /// compacted into the palette file.
+    #[serde(default = "Config::default_journal_compact_threshold")]
+    pub journal_compact_threshold: usize,
 }
 
 
@@ -210,6 +262,13 @@ impl Config {
             default_list_gutter_style: DEFAULT_DEFAULT_LIST_GUTTER_STYLE,
             invalid_color_display_fallback:
                 DEFAULT_INVALID_COLOR_DISPLAY_FALLBACK,
+            palette_libraries: BTreeMap::new(),
+            check_rules: BTreeMap::new(),
+            check_near_duplicate_delta_e: DEFAULT_CHECK_NEAR_DUPLICATE_DELTA_E,
+            history_max_entries: DEFAULT_HISTORY_MAX_ENTRIES,
+            history_max_bytes: DEFAULT_HISTORY_MAX_BYTES,
+            journal: DEFAULT_JOURNAL,
+            journal_compact_threshold: DEFAULT_JOURNAL_COMPACT_THRESHOLD,
         }
     }
 
@@ -252,7 +311,9 @@ impl Config {
             .with_context(|| format!(
                 "Failed to open config file for reading: {}",
                 path.display()))?;
-        let mut config = Config::read_from_file(file)?;
+        let mut config = Config::read_from_file(
+            file,
+            Format::for_path(path))?;
         config.set_load_path(path);
         Ok(config)
     }
@@ -271,7 +332,7 @@ impl Config {
             .with_context(|| format!(
                 "Failed to create/open config file for writing: {}",
                 path.display()))?;
-        self.write_to_file(file)
+        self.write_to_file(file, Format::for_path(path))
             .context("Failed to write config file")?;
         Ok(())
     }
@@ -290,7 +351,7 @@ impl Config {
             .with_context(|| format!(
                 "Failed to create config file: {}",
                 path.display()))?;
-        self.write_to_file(file)
+        self.write_to_file(file, Format::for_path(path))
             .context("Failed to write config file")?;
         Ok(())
     }
@@ -323,57 +384,33 @@ impl Config {
         }
     }
 
-    /// Constructs a new `Config` with options parsed from the given file.
-    pub fn read_from_file(mut file: File) -> Result<Self, FileError>  {
-        Config::parse_ron_from_file(&mut file)
-    }
-
-    /// Parses a `Config` from a file using the RON format.
-    fn parse_ron_from_file(file: &mut File) -> Result<Self, FileError> {
-        let len = file.metadata()
-            .context("Failed to recover file metadata.")?
-            .len();
-        let mut buf = Vec::with_capacity(len as usize);
-        let _ = file.read_to_end(&mut buf)
-            .context("Failed to read config file")?;
-
-        use ron::de::Deserializer;
-        let mut d = Deserializer::from_bytes(&buf)
-            .context("Failed deserializing RON file")?;
-        let config = Config::deserialize(&mut d)
-            .context("Failed parsing RON file")?;
-        d.end()
-            .context("Failed parsing RON file")?;
-
-        Ok(config) 
-    }
-
-    /// Write the `Config` into the given file.
-    pub fn write_to_file(&self, mut file: File) -> Result<(), FileError> {
-        self.generate_ron_into_file(&mut file)
+    /// Constructs a new `Config` with options parsed from the given file in the
+    /// given format.
+    pub fn read_from_file(mut file: File, format: Format)
+        -> Result<Self, FileError>
+    {
+        format.read_from_file(&mut file)
     }
 
-    /// Parses a `Config` from a file using the RON format.
-    fn generate_ron_into_file(&self, file: &mut File) -> Result<(), FileError> {
+    /// Write the `Config` into the given file in the given format.
+    pub fn write_to_file(&self, mut file: File, format: Format)
+        -> Result<(), FileError>
+    {
         tracing::debug!("Serializing & writing Config file.");
         let pretty = ron::ser::PrettyConfig::new()
             .with_depth_limit(2)
             .with_separate_tuple_members(true)
             .with_enumerate_arrays(true)
             .with_extensions(ron::extensions::Extensions::IMPLICIT_SOME);
-        let s = ron::ser::to_string_pretty(&self, pretty)
-            .context("Failed to serialize RON file")?;
-        let mut writer = BufWriter::new(file);
-        writer.write_all(s.as_bytes())
-            .context("Failed to write RON file")?;
-        writer.flush()
-            .context("Failed to flush file buffer")
+        format.write_to_file(self, pretty, &mut file)
     }
 
     /// Normalizes paths in the config by expanding them relative to the given
     /// root path.
-    pub fn normalize_paths(&mut self, _base: &PathBuf) {
-        // NOTE: No paths currently in config, nothing to do.
+    pub fn normalize_paths(&mut self, base: &PathBuf) {
+        for path in self.palette_libraries.values_mut() {
+            *path = normalize_path(base, &path);
+        }
 
         // TODO: Normalize trace path?
     }
@@ -479,6 +516,36 @@ impl Config {
     fn default_invalid_color_display_fallback() -> ColorDisplay {
         DEFAULT_INVALID_COLOR_DISPLAY_FALLBACK
     }
+
+    /// Returns the default value for check_near_duplicate_delta_e.
+    #[inline]
+    fn default_check_near_duplicate_delta_e() -> f32 {
+        DEFAULT_CHECK_NEAR_DUPLICATE_DELTA_E
+    }
+
+    /// Returns the default value for history_max_entries.
+    #[inline]
+    fn default_history_max_entries() -> Option<usize> {
+        DEFAULT_HISTORY_MAX_ENTRIES
+    }
+
+    /// Returns the default value for history_max_bytes.
+    #[inline]
+    fn default_history_max_bytes() -> Option<usize> {
+        DEFAULT_HISTORY_MAX_BYTES
+    }
+
+    /// Returns the default value for journal.
+    #[inline]
+    fn default_journal() -> bool {
+        DEFAULT_JOURNAL
+    }
+
+    /// Returns the default value for journal_compact_threshold.
+    #[inline]
+    fn default_journal_compact_threshold() -> usize {
+        DEFAULT_JOURNAL_COMPACT_THRESHOLD
+    }
 }
 
 impl Default for Config {
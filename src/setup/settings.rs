@@ -14,6 +14,7 @@ use crate::utility::normalize_path;
 use crate::setup::LoadStatus;
 use crate::error::FileError;
 use crate::error::FileErrorContext as _;
+use crate::format::Format;
 use crate::command::CursorBehavior;
 
 // External library imports.
@@ -23,9 +24,6 @@ use serde::Serialize;
 // Standard library imports.
 use std::fs::File;
 use std::fs::OpenOptions;
-use std::io::BufWriter;
-use std::io::Read as _;
-use std::io::Write as _;
 use std::path::Path;
 use std::path::PathBuf;
 
@@ -112,7 +110,9 @@ impl Settings {
             .with_context(|| format!(
                 "Failed to open settings file for reading: {}",
                 path.display()))?;
-        let mut settings = Settings::read_from_file(file)?;
+        let mut settings = Settings::read_from_file(
+            file,
+            Format::for_path(path))?;
         settings.load_status.set_load_path(path);
         Ok(settings)
     }
@@ -131,7 +131,7 @@ impl Settings {
             .with_context(|| format!(
                 "Failed to create/open settings file for writing: {}",
                 path.display()))?;
-        self.write_to_file(file)
+        self.write_to_file(file, Format::for_path(path))
             .context("Failed to write settings file")?;
         Ok(())
     }
@@ -150,7 +150,7 @@ impl Settings {
             .with_context(|| format!(
                 "Failed to create settings file: {}",
                 path.display()))?;
-        self.write_to_file(file)
+        self.write_to_file(file, Format::for_path(path))
             .context("Failed to write settings file")?;
         Ok(())
     }
@@ -183,51 +183,25 @@ impl Settings {
         }
     }
 
-    /// Constructs a new `Settings` with options parsed from the given file.
-    pub fn read_from_file(mut file: File) -> Result<Self, FileError>  {
-        Settings::parse_ron_from_file(&mut file)
-    }
-
-    /// Parses a `Settings` from a file using the RON format.
-    fn parse_ron_from_file(file: &mut File) -> Result<Self, FileError> {
-        let len = file.metadata()
-            .context("Failed to recover file metadata.")?
-            .len();
-        let mut buf = Vec::with_capacity(len as usize);
-        let _ = file.read_to_end(&mut buf)
-            .context("Failed to read settings file")?;
-
-        use ron::de::Deserializer;
-        let mut d = Deserializer::from_bytes(&buf)
-            .context("Failed deserializing RON file")?;
-        let settings = Settings::deserialize(&mut d)
-            .context("Failed parsing Ron file")?;
-        d.end()
-            .context("Failed parsing Ron file")?;
-
-        Ok(settings) 
-    }
-    
-    /// Write the `Settings` into the given file.
-    pub fn write_to_file(&self, mut file: File) -> Result<(), FileError> {
-        self.generate_ron_into_file(&mut file)
+    /// Constructs a new `Settings` with options parsed from the given file in
+    /// the given format.
+    pub fn read_from_file(mut file: File, format: Format)
+        -> Result<Self, FileError>
+    {
+        format.read_from_file(&mut file)
     }
 
-    /// Parses a `Settings` from a file using the RON format.
-    fn generate_ron_into_file(&self, file: &mut File) -> Result<(), FileError> {
+    /// Write the `Settings` into the given file in the given format.
+    pub fn write_to_file(&self, mut file: File, format: Format)
+        -> Result<(), FileError>
+    {
         tracing::debug!("Serializing & writing Settings file.");
         let pretty = ron::ser::PrettyConfig::new()
             .with_depth_limit(2)
             .with_separate_tuple_members(true)
             .with_enumerate_arrays(true)
             .with_extensions(ron::extensions::Extensions::IMPLICIT_SOME);
-        let s = ron::ser::to_string_pretty(&self, pretty)
-            .context("Failed to serialize RON file")?;
-        let mut writer = BufWriter::new(file);
-        writer.write_all(s.as_bytes())
-            .context("Failed to write RON file")?;
-        writer.flush()
-            .context("Failed to flush file buffer")
+        format.write_to_file(self, pretty, &mut file)
     }
 
     /// Normalizes paths in the settings by expanding them relative to the given
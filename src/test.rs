@@ -0,0 +1,18 @@
+////////////////////////////////////////////////////////////////////////////////
+// Atma structured color palette
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Test modules.
+////////////////////////////////////////////////////////////////////////////////
+
+mod basic;
+mod check;
+mod convert;
+mod expr;
+mod history;
+mod import_png;
+mod journal;
+mod refs;
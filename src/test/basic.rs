@@ -0,0 +1,102 @@
+////////////////////////////////////////////////////////////////////////////////
+// Atma structured color palette
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Basic palette operation tests.
+////////////////////////////////////////////////////////////////////////////////
+
+// Local imports.
+use crate::cell::Cell;
+use crate::cell::CellRef;
+use crate::error::PaletteError;
+use crate::palette::BasicPalette;
+use crate::palette::Expr;
+use crate::palette::Operation;
+
+
+/// Returns a palette with an empty cell at index 0.
+fn palette_with_cell() -> BasicPalette {
+    let mut basic = BasicPalette::new();
+    let _ = basic.insert_cell(0, Cell::new_with_expr(Expr::Empty)).unwrap();
+    basic
+}
+
+/// Returns an operation removing the cell at index 0.
+fn remove_cell() -> Operation {
+    Operation::RemoveCell { cell_ref: CellRef::Index(0) }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Cell removal
+////////////////////////////////////////////////////////////////////////////////
+
+/// Tests that removing a cell removes its metadata and undo restores it.
+#[test]
+fn remove_cell_metadata() {
+    let mut basic = palette_with_cell();
+    let _ = basic.assign_metadata(CellRef::Index(0), "role", "accent")
+        .unwrap();
+
+    let undo_ops = basic.apply_operations_reversibly(&[remove_cell()])
+        .unwrap();
+    assert!(basic.assigned_metadata(&CellRef::Index(0)).is_none());
+    assert!(basic.resolve_metadata_if_occupied("role", None).is_empty());
+
+    basic.revert_operations(&undo_ops[..]);
+    assert_eq!(
+        basic.assigned_metadata(&CellRef::Index(0))
+            .and_then(|entries| entries.get("role"))
+            .map(|value| value.as_ref()),
+        Some("accent"));
+}
+
+/// Tests that removing a cell removes its variant overrides and undo restores
+/// them.
+#[test]
+fn remove_cell_variant_exprs() {
+    let mut basic = palette_with_cell();
+    let expr = Expr::Reference(CellRef::Index(1));
+    let _ = basic.set_variant_expr("dark", CellRef::Index(0), expr.clone())
+        .unwrap();
+
+    let undo_ops = basic.apply_operations_reversibly(&[remove_cell()])
+        .unwrap();
+    assert_eq!(basic.variant_exprs().count(), 0);
+    assert_eq!(basic.variants().count(), 0);
+
+    basic.revert_operations(&undo_ops[..]);
+    assert_eq!(basic.variant_expr("dark", &CellRef::Index(0)), Some(&expr));
+}
+
+/// Tests that removing a locked cell fails unless locks are ignored.
+#[test]
+fn remove_cell_locked() {
+    let mut basic = palette_with_cell();
+    let _ = basic.lock(CellRef::Index(0)).unwrap();
+
+    let res = basic.apply_operations_reversibly(&[remove_cell()]);
+    assert!(matches!(res, Err(PaletteError::LockedCell { .. })));
+    assert!(basic.is_occupied_index(&0));
+}
+
+/// Tests that removing a locked cell while ignoring locks removes its lock and
+/// undo restores it.
+#[test]
+fn remove_cell_locked_ignoring_locks() {
+    let mut basic = palette_with_cell();
+    let _ = basic.lock(CellRef::Index(0)).unwrap();
+    basic.set_ignore_locks(true);
+
+    let undo_ops = basic.apply_operations_reversibly(&[remove_cell()])
+        .unwrap();
+    assert!(!basic.is_occupied_index(&0));
+    assert!(!basic.is_locked(&CellRef::Index(0)));
+
+    basic.revert_operations(&undo_ops[..]);
+    assert!(basic.is_occupied_index(&0));
+    assert!(basic.is_locked(&CellRef::Index(0)));
+}
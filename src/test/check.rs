@@ -0,0 +1,320 @@
+////////////////////////////////////////////////////////////////////////////////
+// Atma structured color palette
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Palette check tests.
+////////////////////////////////////////////////////////////////////////////////
+
+// Local imports.
+use crate::cell::Cell;
+use crate::cell::CellRef;
+use crate::cell::Position;
+use crate::cell::PositionSelector;
+use crate::color::Color;
+use crate::color::Rgb;
+use crate::command::check::check;
+use crate::command::check::in_gamut;
+use crate::command::check::Diagnostic;
+use crate::command::check::Rule;
+use crate::command::check::Severity;
+use crate::command::check::DANGLING_REFERENCE;
+use crate::command::check::DUPLICATE_COLOR;
+use crate::command::check::EMPTY_CELL;
+use crate::command::check::GROUP_HOLE;
+use crate::command::check::NEAR_DUPLICATE_COLOR;
+use crate::command::check::OUT_OF_GAMUT;
+use crate::command::check::REFERENCE_CYCLE;
+use crate::command::check::RULES;
+use crate::command::check::UNOCCUPIED_NAME;
+use crate::command::check::UNUSED_CELL;
+use crate::palette::Expr;
+use crate::palette::Operation;
+use crate::palette::Palette;
+use crate::setup::Config;
+
+
+/// Returns the position in column `column` of the first line.
+fn column(column: u16) -> Position {
+    Position { page: 0, line: 0, column }
+}
+
+/// Returns an expression referencing the cell with the given index.
+fn index_ref(idx: u32) -> Expr {
+    Expr::Reference(CellRef::Index(idx))
+}
+
+/// Returns an expression with the given RGB color.
+fn color_expr(octets: [u8; 3]) -> Expr {
+    Expr::Color(Color::from(Rgb::from(octets)))
+}
+
+/// Returns a palette holding the given cell indices and expressions. Cells
+/// given a column are assigned the position in that column.
+fn palette_with_cells(cells: Vec<(u32, Option<u16>, Expr)>) -> Palette {
+    use Operation::*;
+    let mut ops = Vec::new();
+    for (idx, col, expr) in cells {
+        ops.push(InsertCell { idx, cell: Cell::new_with_expr(expr) });
+        if let Some(col) = col {
+            ops.push(AssignPosition {
+                cell_ref: CellRef::Index(idx),
+                position: column(col),
+            });
+        }
+    }
+
+    let mut palette = Palette::new();
+    palette.apply_operations(&ops[..]).expect("build palette");
+    palette
+}
+
+/// Applies the given operations to the palette.
+fn apply(palette: &mut Palette, ops: &[Operation]) {
+    palette.apply_operations(ops).expect("apply operations");
+}
+
+/// Returns the diagnostics for the given rule when checking the palette with
+/// the given config.
+fn diagnostics(palette: &Palette, config: &Config, rule: Rule)
+    -> Vec<Diagnostic>
+{
+    check(palette.inner(), config)
+        .into_iter()
+        .filter(|diagnostic| diagnostic.rule == rule)
+        .collect()
+}
+
+/// Returns the messages of the diagnostics for the given rule when checking
+/// the palette with the default config.
+fn messages(palette: &Palette, rule: Rule) -> Vec<String> {
+    diagnostics(palette, &Config::new(), rule)
+        .into_iter()
+        .map(|diagnostic| diagnostic.message)
+        .collect()
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Rules
+////////////////////////////////////////////////////////////////////////////////
+
+/// Tests that the rules have distinct, stable codes.
+#[test]
+fn check_rule_codes() {
+    let codes: Vec<&str> = RULES.iter().map(|rule| rule.code).collect();
+    assert_eq!(codes, vec![
+        "A001", "A002", "A003", "A004", "A005", "A006", "A007", "A008", "A009",
+    ]);
+}
+
+/// Tests that references to undefined cells are reported (A001).
+#[test]
+fn check_dangling_reference() {
+    let palette = palette_with_cells(vec![
+        (0, Some(0), index_ref(7)),
+        (1, Some(1), index_ref(0)),
+    ]);
+
+    assert_eq!(messages(&palette, DANGLING_REFERENCE), vec![format!(
+        "cell :0 refers to undefined cell {}",
+        CellRef::Index(7))]);
+}
+
+/// Tests that each reference cycle is reported once, listing its members
+/// (A002).
+#[test]
+fn check_reference_cycle() {
+    let palette = palette_with_cells(vec![
+        (0, Some(0), index_ref(1)),
+        (1, Some(1), index_ref(2)),
+        (2, Some(2), index_ref(0)),
+        (3, Some(3), index_ref(3)),
+        (4, Some(4), index_ref(0)),
+    ]);
+
+    assert_eq!(messages(&palette, REFERENCE_CYCLE), vec![
+        "cells :0, :1, :2 form a reference cycle".to_owned(),
+        "cell :3 refers to itself".to_owned(),
+    ]);
+}
+
+/// Tests that cells with empty expressions are reported (A003).
+#[test]
+fn check_empty_cell() {
+    let palette = palette_with_cells(vec![
+        (0, Some(0), Expr::Empty),
+        (1, Some(1), color_expr([0x00, 0x00, 0x00])),
+    ]);
+
+    assert_eq!(messages(&palette, EMPTY_CELL), vec![
+        "cell :0 is empty".to_owned(),
+    ]);
+}
+
+/// Tests that names assigned to unoccupied positions are reported (A004).
+#[test]
+fn check_unoccupied_name() {
+    let mut palette = palette_with_cells(vec![
+        (0, Some(0), color_expr([0x00, 0x00, 0x00])),
+    ]);
+    apply(&mut palette, &[
+        Operation::AssignName {
+            selector: PositionSelector::from(column(0)),
+            name: "first".into(),
+        },
+        Operation::AssignName {
+            selector: PositionSelector::from(column(5)),
+            name: "sixth".into(),
+        },
+    ]);
+
+    assert_eq!(messages(&palette, UNOCCUPIED_NAME), vec![format!(
+        "name \"sixth\" is assigned to unoccupied position {}",
+        PositionSelector::from(column(5)))]);
+}
+
+/// Tests that group members missing from the palette are reported (A005).
+#[test]
+fn check_group_hole() {
+    let mut palette = palette_with_cells(vec![
+        (0, Some(0), color_expr([0x00, 0x00, 0x00])),
+        (1, Some(1), color_expr([0xFF, 0xFF, 0xFF])),
+    ]);
+    let assign_group = |idx| Operation::AssignGroup {
+        cell_ref: CellRef::Index(idx),
+        group: "g".into(),
+        idx: None,
+    };
+    apply(&mut palette, &[assign_group(0), assign_group(1)]);
+    apply(&mut palette, &[
+        Operation::RemoveCell { cell_ref: CellRef::Index(1) },
+    ]);
+
+    assert_eq!(messages(&palette, GROUP_HOLE), vec![
+        "group \"g\" member 1 refers to missing cell :1".to_owned(),
+    ]);
+}
+
+/// Tests that colors outside of the RGB gamut are detected, and that colors
+/// within it are not reported (A006).
+#[test]
+fn check_out_of_gamut() {
+    assert!(in_gamut([0.0, 0.5, 1.0]));
+    assert!(!in_gamut([1.5, 0.5, 0.5]));
+    assert!(!in_gamut([0.5, -0.25, 0.5]));
+    assert!(!in_gamut([0.5, 0.5, f32::NAN]));
+
+    let palette = palette_with_cells(vec![
+        (0, Some(0), color_expr([0x00, 0x00, 0x00])),
+        (1, Some(1), color_expr([0xFF, 0xFF, 0xFF])),
+    ]);
+    assert!(messages(&palette, OUT_OF_GAMUT).is_empty());
+}
+
+/// Tests that cells with identical colors are reported once per color
+/// (A007).
+#[test]
+fn check_duplicate_color() {
+    let palette = palette_with_cells(vec![
+        (0, Some(0), color_expr([0x80, 0x80, 0x80])),
+        (1, Some(1), color_expr([0x80, 0x80, 0x80])),
+        (2, Some(2), color_expr([0xFF, 0xFF, 0xFF])),
+        (3, Some(3), index_ref(0)),
+    ]);
+
+    assert_eq!(messages(&palette, DUPLICATE_COLOR), vec![
+        "cells :0, :1, :3 have the same color".to_owned(),
+    ]);
+    assert!(messages(&palette, NEAR_DUPLICATE_COLOR).is_empty());
+}
+
+/// Tests that cells with nearly identical colors are reported, using the
+/// threshold from the config (A008).
+#[test]
+fn check_near_duplicate_color() {
+    let palette = palette_with_cells(vec![
+        (0, Some(0), color_expr([0x80, 0x80, 0x80])),
+        (1, Some(1), color_expr([0xFF, 0xFF, 0xFF])),
+        (2, Some(2), color_expr([0x82, 0x80, 0x80])),
+        (3, Some(3), color_expr([0xC8, 0x80, 0x80])),
+    ]);
+
+    let near = diagnostics(&palette, &Config::new(), NEAR_DUPLICATE_COLOR);
+    assert_eq!(near.len(), 1);
+    assert!(near[0].message.starts_with(
+        "cells :0 and :2 have nearly the same color"));
+    assert!(messages(&palette, DUPLICATE_COLOR).is_empty());
+
+    let mut config = Config::new();
+    config.check_near_duplicate_delta_e = 0.1;
+    assert!(diagnostics(&palette, &config, NEAR_DUPLICATE_COLOR).is_empty());
+
+    config.check_near_duplicate_delta_e = 40.0;
+    assert_eq!(diagnostics(&palette, &config, NEAR_DUPLICATE_COLOR).len(), 3);
+}
+
+/// Tests that cells which are neither referenced nor positioned are reported
+/// (A009).
+#[test]
+fn check_unused_cell() {
+    let palette = palette_with_cells(vec![
+        (0, Some(0), index_ref(1)),
+        (1, None, color_expr([0x00, 0x00, 0x00])),
+        (2, None, color_expr([0xFF, 0xFF, 0xFF])),
+    ]);
+
+    assert_eq!(messages(&palette, UNUSED_CELL), vec![
+        "cell :2 is neither referenced nor positioned".to_owned(),
+    ]);
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Severity
+////////////////////////////////////////////////////////////////////////////////
+
+/// Tests that diagnostics use the default severities of their rules.
+#[test]
+fn check_default_severity() {
+    let palette = palette_with_cells(vec![
+        (0, Some(0), index_ref(7)),
+        (1, Some(1), Expr::Empty),
+    ]);
+    let config = Config::new();
+
+    let dangling = diagnostics(&palette, &config, DANGLING_REFERENCE);
+    assert_eq!(dangling[0].severity, Severity::Error);
+    let empty = diagnostics(&palette, &config, EMPTY_CELL);
+    assert_eq!(empty[0].severity, Severity::Warning);
+}
+
+/// Tests that severities are overridden from the config by rule code or
+/// name, with the code taking precedence, and that allowed rules are not
+/// reported.
+#[test]
+fn check_severity_overrides() {
+    let palette = palette_with_cells(vec![
+        (0, Some(0), index_ref(7)),
+        (1, Some(1), Expr::Empty),
+        (2, None, color_expr([0x00, 0x00, 0x00])),
+    ]);
+    let mut config = Config::new();
+    let _ = config.check_rules.insert("A003".into(), Severity::Error);
+    let _ = config.check_rules.insert("empty_cell".into(), Severity::Allow);
+    let _ = config.check_rules
+        .insert("dangling_reference".into(), Severity::Warning);
+    let _ = config.check_rules.insert("A009".into(), Severity::Allow);
+
+    let empty = diagnostics(&palette, &config, EMPTY_CELL);
+    assert_eq!(empty.len(), 1);
+    assert_eq!(empty[0].severity, Severity::Error);
+
+    let dangling = diagnostics(&palette, &config, DANGLING_REFERENCE);
+    assert_eq!(dangling.len(), 1);
+    assert_eq!(dangling[0].severity, Severity::Warning);
+
+    assert!(diagnostics(&palette, &config, UNUSED_CELL).is_empty());
+}
@@ -0,0 +1,167 @@
+////////////////////////////////////////////////////////////////////////////////
+// Atma structured color palette
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Palette format conversion tests.
+////////////////////////////////////////////////////////////////////////////////
+
+// Local imports.
+use crate::cell::Cell;
+use crate::cell::CellRef;
+use crate::cell::Position;
+use crate::cell::PositionSelector;
+use crate::color::Color;
+use crate::color::Rgb;
+use crate::command::convert::convert_palette;
+use crate::format::Format;
+use crate::palette::Expr;
+use crate::palette::Operation;
+use crate::palette::Palette;
+
+// Standard library imports.
+use std::path::Path;
+use std::path::PathBuf;
+
+
+/// Returns the position in column `column` of the first line.
+fn column(column: u16) -> Position {
+    Position { page: 0, line: 0, column }
+}
+
+/// Returns a palette with positions and names, built with the given
+/// operations so that they are recorded in its history if it has one.
+fn build_palette(mut palette: Palette) -> Palette {
+    use Operation::*;
+    let color = Color::from(Rgb::from([0x33, 0x66, 0x99]));
+    palette.apply_operations(&[
+            InsertCell {
+                idx: 0,
+                cell: Cell::new_with_expr(Expr::Color(color)),
+            },
+            AssignPosition {
+                cell_ref: CellRef::Index(0),
+                position: column(0),
+            },
+            InsertCell {
+                idx: 1,
+                cell: Cell::new_with_expr(Expr::Reference(
+                    CellRef::Position(column(0)))),
+            },
+            AssignPosition {
+                cell_ref: CellRef::Index(1),
+                position: column(1),
+            },
+            AssignName {
+                selector: PositionSelector::from(column(1)),
+                name: "second".into(),
+            },
+        ])
+        .expect("build palette");
+    palette
+}
+
+/// Creates an empty directory for the named test, returning its path.
+fn test_dir(test: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "atma-{}-{}",
+        test,
+        std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).expect("create test directory");
+    dir
+}
+
+/// Writes the palette to the given path in RON format.
+fn write_ron(palette: &Palette, path: &Path) {
+    let data = palette.generate(Format::Ron).expect("generate palette");
+    std::fs::write(path, &data[..]).expect("write palette");
+}
+
+/// Returns the palette file at the given path, parsed in the given format.
+fn read(path: &Path, format: Format) -> Palette {
+    let data = std::fs::read(path).expect("read palette file");
+    Palette::parse(&data[..], format).expect("parse palette")
+}
+
+/// Returns the palette serialized in RON format, for comparison.
+fn ron(palette: &Palette) -> Vec<u8> {
+    palette.generate(Format::Ron).expect("generate palette")
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Round trips
+////////////////////////////////////////////////////////////////////////////////
+
+/// Tests that converting a palette with history and positions through each
+/// format and back to RON preserves it, including its undo history.
+#[test]
+fn convert_round_trip_with_history() {
+    let dir = test_dir("convert-round-trip");
+    let palette = build_palette(Palette::new().with_history());
+    let input = dir.join("palette.ron");
+    write_ron(&palette, &input);
+
+    let steps = [
+        (Format::Ron, "palette.json", Format::Json),
+        (Format::Json, "palette.bin", Format::Binary),
+        (Format::Binary, "palette-binary.ron", Format::Ron),
+        (Format::Ron, "palette-ron.json", Format::Json),
+        (Format::Json, "palette-json.ron", Format::Ron),
+    ];
+    let mut from_path = input;
+    for (from, name, to) in steps.iter() {
+        let to_path = dir.join(name);
+        convert_palette(&from_path, *from, &to_path, *to, false)
+            .expect("convert palette");
+        assert_eq!(ron(&read(&to_path, *to)), ron(&palette));
+        from_path = to_path;
+    }
+
+    let mut converted = read(&from_path, Format::Ron);
+    assert_eq!(converted.undo(1), 1);
+    assert_eq!(converted.inner().assigned_position(&CellRef::Index(1)), None);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+/// Tests that converting a palette with positions but no history to TOML and
+/// back to RON preserves it.
+#[test]
+fn convert_round_trip_toml() {
+    let dir = test_dir("convert-round-trip-toml");
+    let palette = build_palette(Palette::new());
+    let input = dir.join("palette.ron");
+    let toml = dir.join("palette.toml");
+    let output = dir.join("palette-toml.ron");
+    write_ron(&palette, &input);
+
+    convert_palette(&input, Format::Ron, &toml, Format::Toml, false)
+        .expect("convert palette to TOML");
+    assert_eq!(ron(&read(&toml, Format::Toml)), ron(&palette));
+    convert_palette(&toml, Format::Toml, &output, Format::Ron, false)
+        .expect("convert palette from TOML");
+    assert_eq!(ron(&read(&output, Format::Ron)), ron(&palette));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+/// Tests that converting a palette with history to TOML fails without
+/// writing the output file.
+#[test]
+fn convert_toml_with_history_rejected() {
+    let dir = test_dir("convert-toml-history");
+    let palette = build_palette(Palette::new().with_history());
+    let input = dir.join("palette.ron");
+    let output = dir.join("palette.toml");
+    write_ron(&palette, &input);
+
+    assert!(convert_palette(&input, Format::Ron, &output, Format::Toml, false)
+        .is_err());
+    assert!(!output.exists());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,29 @@
+////////////////////////////////////////////////////////////////////////////////
+// Atma structured color palette
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Expression display and parse tests.
+////////////////////////////////////////////////////////////////////////////////
+
+// Local imports.
+use crate::palette::ColorSpace;
+
+
+/// Tests that each color space displays as a token the parser accepts.
+#[test]
+fn color_space_display_round_trip() {
+    for color_space in &[ColorSpace::Rgb, ColorSpace::LinearRgb] {
+        let text = color_space.to_string();
+        assert_eq!(text.parse::<ColorSpace>().ok(), Some(*color_space));
+    }
+}
+
+/// Tests the tokens displayed for each color space.
+#[test]
+fn color_space_display() {
+    assert_eq!(ColorSpace::Rgb.to_string(), "rgb");
+    assert_eq!(ColorSpace::LinearRgb.to_string(), "linear_rgb");
+}
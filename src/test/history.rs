@@ -0,0 +1,111 @@
+////////////////////////////////////////////////////////////////////////////////
+// Atma structured color palette
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Undo history tests.
+////////////////////////////////////////////////////////////////////////////////
+
+// Local imports.
+use crate::cell::Position;
+use crate::format::Format;
+use crate::palette::History;
+use crate::palette::Operation;
+use crate::palette::Palette;
+
+
+/// Returns the position in column `column` of the first line.
+fn column(column: u16) -> Position {
+    Position { page: 0, line: 0, column }
+}
+
+/// Returns an operation setting the position cursor to the given column.
+fn set_cursor(col: u16) -> Operation {
+    Operation::SetPositionCursor { position: column(col) }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Linear history
+////////////////////////////////////////////////////////////////////////////////
+
+/// Tests loading a linear history recorded before branching was introduced.
+#[test]
+fn history_load_linear() {
+    // Each entry sets the cursor to its own column. Entries 1 and 2 are
+    // applied and hold undo ops, and entry 3 is undone and holds redo ops.
+    let text = "(\
+        ops: [\
+            [SetPositionCursor(position: (0, 0, 0))],\
+            [SetPositionCursor(position: (0, 0, 1))],\
+            [SetPositionCursor(position: (0, 0, 3))],\
+        ],\
+        cursor: 2,\
+    )";
+    let history: History = ron::de::from_str(text)
+        .expect("parse linear history");
+
+    assert_eq!(history.len(), 3);
+    assert_eq!(history.cursor(), 2);
+    assert_eq!(history.parent(1), Some(0));
+    assert_eq!(history.parent(2), Some(1));
+    assert_eq!(history.parent(3), Some(2));
+    assert_eq!(history.branches(), vec![3]);
+    assert_eq!(history.undo_count(), 2);
+    assert_eq!(history.redo_count(), 1);
+    assert_eq!(history.entry_info(1).command, None);
+
+    let mut palette = Palette::new();
+    let _ = palette.set_position_cursor(column(2));
+    let _ = palette.set_history(Some(history));
+
+    assert_eq!(palette.undo(5), 2);
+    assert_eq!(palette.position_cursor(), column(0));
+    assert_eq!(palette.redo(5), 3);
+    assert_eq!(palette.position_cursor(), column(3));
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Branching history
+////////////////////////////////////////////////////////////////////////////////
+
+/// Tests that undo history branches survive a round trip through a palette
+/// file.
+#[test]
+fn history_branch_round_trip() {
+    let mut palette = Palette::new().with_history();
+    palette.apply_operations(&[set_cursor(1)]).expect("apply entry 1");
+    palette.apply_operations(&[set_cursor(2)]).expect("apply entry 2");
+    assert_eq!(palette.undo(1), 1);
+    palette.apply_operations(&[set_cursor(5)]).expect("apply entry 3");
+
+    let history = palette.history().expect("history enabled");
+    assert_eq!(history.branches(), vec![2, 3]);
+    assert_eq!(history.parent(3), Some(1));
+    assert_eq!(history.cursor(), 3);
+
+    let data = palette.generate(Format::Ron).expect("generate palette");
+    let mut palette = Palette::parse(&data[..], Format::Ron)
+        .expect("parse palette");
+
+    let history = palette.history().expect("history loaded");
+    assert_eq!(history.branches(), vec![2, 3]);
+    assert_eq!(history.parent(3), Some(1));
+    assert_eq!(history.cursor(), 3);
+
+    palette.switch_branch(2).expect("switch to branch 2");
+    assert_eq!(palette.position_cursor(), column(2));
+    assert_eq!(palette.history().map(History::cursor), Some(2));
+
+    palette.switch_branch(3).expect("switch to branch 3");
+    assert_eq!(palette.position_cursor(), column(5));
+
+    assert_eq!(palette.undo(5), 2);
+    assert_eq!(palette.position_cursor(), column(0));
+    assert_eq!(palette.redo(5), 2);
+    assert_eq!(palette.position_cursor(), column(5));
+    assert_eq!(palette.history().map(History::cursor), Some(3));
+}
@@ -0,0 +1,124 @@
+////////////////////////////////////////////////////////////////////////////////
+// Atma structured color palette
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! PNG import and quantization tests.
+////////////////////////////////////////////////////////////////////////////////
+
+// Local imports.
+use crate::command::import_png::k_means;
+use crate::command::import_png::median_cut;
+
+
+/// Black.
+const BLACK: [f32; 3] = [0.0, 0.0, 0.0];
+/// White.
+const WHITE: [f32; 3] = [1.0, 1.0, 1.0];
+/// Mid gray.
+const GRAY: [f32; 3] = [0.5, 0.5, 0.5];
+
+/// Returns the given points sorted by their first channel.
+fn sorted(mut points: Vec<[f32; 3]>) -> Vec<[f32; 3]> {
+    points.sort_by(|a, b| a[0].partial_cmp(&b[0]).unwrap());
+    points
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Median cut
+////////////////////////////////////////////////////////////////////////////////
+
+/// Tests that median cut of an empty image yields no colors.
+#[test]
+fn median_cut_empty() {
+    assert_eq!(median_cut(&[], 4), Vec::<[f32; 3]>::new());
+}
+
+/// Tests that median cut of a single color image yields that color once.
+#[test]
+fn median_cut_single_color() {
+    assert_eq!(median_cut(&[GRAY; 10], 4), vec![GRAY]);
+}
+
+/// Tests that median cut yields each color once when there are fewer unique
+/// colors than requested.
+#[test]
+fn median_cut_fewer_unique_colors() {
+    let points = [BLACK, BLACK, BLACK, BLACK, WHITE];
+    assert_eq!(sorted(median_cut(&points, 8)), vec![BLACK, WHITE]);
+}
+
+/// Tests that median cut reduces the colors to the requested count.
+#[test]
+fn median_cut_reduces_colors() {
+    let points = [BLACK, [0.25, 0.25, 0.25], [0.75, 0.75, 0.75], WHITE];
+    let centers = sorted(median_cut(&points, 2));
+    assert_eq!(centers, vec![[0.125, 0.125, 0.125], [0.875, 0.875, 0.875]]);
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// K-means
+////////////////////////////////////////////////////////////////////////////////
+
+/// Tests that k-means of an empty image yields no colors.
+#[test]
+fn k_means_empty() {
+    assert_eq!(k_means(&[], 4), Vec::<[f32; 3]>::new());
+}
+
+/// Tests that k-means of a single color image yields that color once.
+#[test]
+fn k_means_single_color() {
+    assert_eq!(k_means(&[GRAY; 10], 4), vec![GRAY]);
+}
+
+/// Tests that k-means yields each color once when there are fewer unique
+/// colors than requested.
+#[test]
+fn k_means_fewer_unique_colors() {
+    let points = [BLACK, BLACK, BLACK, BLACK, WHITE];
+    assert_eq!(sorted(k_means(&points, 8)), vec![BLACK, WHITE]);
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Import
+////////////////////////////////////////////////////////////////////////////////
+
+/// Tests that importing an indexed PNG returns its PLTE colors in order
+/// without quantizing them.
+#[cfg(feature = "png")]
+#[test]
+fn import_png_indexed() {
+    use crate::command::import_png::read_png;
+    use crate::command::QuantizeMethod;
+    use crate::palette::ColorSpace;
+
+    let mut data: Vec<u8> = Vec::new();
+    {
+        let mut encoder = png::Encoder::new(&mut data, 2, 1);
+        encoder.set_color(png::ColorType::Indexed);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_palette(vec![255, 0, 0, 0, 255, 0, 0, 0, 255]);
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(&[0, 2]).unwrap();
+    }
+
+    let path = std::env::temp_dir().join(format!(
+        "atma-import-png-indexed-{}.png",
+        std::process::id()));
+    std::fs::write(&path, &data[..]).unwrap();
+    let colors = read_png(&path, 1, QuantizeMethod::MedianCut, ColorSpace::Rgb);
+    let _ = std::fs::remove_file(&path);
+
+    let octets: Vec<[u8; 3]> = colors
+        .unwrap()
+        .iter()
+        .map(|c| c.rgb_octets())
+        .collect();
+    assert_eq!(octets, vec![[255, 0, 0], [0, 255, 0], [0, 0, 255]]);
+}
@@ -0,0 +1,167 @@
+////////////////////////////////////////////////////////////////////////////////
+// Atma structured color palette
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Palette journal tests.
+////////////////////////////////////////////////////////////////////////////////
+
+// Local imports.
+use crate::cell::Position;
+use crate::format::Format;
+use crate::palette::Journal;
+use crate::palette::Operation;
+use crate::palette::Palette;
+
+// Standard library imports.
+use std::io::Write as _;
+use std::path::Path;
+use std::path::PathBuf;
+
+
+/// Returns the position in column `column` of the first line.
+fn column(column: u16) -> Position {
+    Position { page: 0, line: 0, column }
+}
+
+/// Returns an operation setting the position cursor to the given column.
+fn set_cursor(col: u16) -> Operation {
+    Operation::SetPositionCursor { position: column(col) }
+}
+
+/// Creates an empty directory for the named test, returning the path of a
+/// palette file within it.
+fn palette_path(test: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "atma-{}-{}",
+        test,
+        std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).expect("create test directory");
+    dir.join("palette.ron")
+}
+
+/// Writes a new palette with history to the given path, and returns it loaded
+/// from the path with journaling enabled.
+fn journaled_palette(path: &Path, compact_threshold: usize) -> Palette {
+    Palette::new()
+        .with_history()
+        .write_to_path(path)
+        .expect("write palette");
+    let mut palette = Palette::read_from_path(path).expect("read palette");
+    palette.set_journal(Some(compact_threshold));
+    palette
+}
+
+/// Applies an operation setting the position cursor and writes the changes.
+fn apply_and_write(palette: &mut Palette, col: u16) {
+    palette.apply_operations(&[set_cursor(col)]).expect("apply operation");
+    assert!(palette.write_changes_to_load_path().expect("write changes"));
+}
+
+/// Returns the position cursor stored in the palette file, without replaying
+/// its journal.
+fn stored_cursor(path: &Path) -> Position {
+    let data = std::fs::read(path).expect("read palette file");
+    Palette::parse(&data[..], Format::Ron)
+        .expect("parse palette")
+        .position_cursor()
+}
+
+/// Removes the directory of the given palette file.
+fn clean_up(path: &Path) {
+    if let Some(dir) = path.parent() {
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Replay
+////////////////////////////////////////////////////////////////////////////////
+
+/// Tests that changes appended to the journal are replayed when the palette
+/// is loaded, including their history.
+#[test]
+fn journal_replay() {
+    let path = palette_path("journal-replay");
+    let mut palette = journaled_palette(&path, 16);
+    apply_and_write(&mut palette, 1);
+    apply_and_write(&mut palette, 2);
+
+    assert!(Journal::path_for(&path).exists());
+    assert_eq!(stored_cursor(&path), column(0));
+
+    let mut palette = Palette::read_from_path(&path).expect("read palette");
+    assert_eq!(palette.position_cursor(), column(2));
+    assert_eq!(palette.undo(1), 1);
+    assert_eq!(palette.position_cursor(), column(1));
+
+    clean_up(&path);
+}
+
+/// Tests that an unparsable final journal line is ignored, and that the
+/// journal is compacted on the next write.
+#[test]
+fn journal_ignore_partial_last_line() {
+    let path = palette_path("journal-partial");
+    let mut palette = journaled_palette(&path, 16);
+    apply_and_write(&mut palette, 1);
+
+    let journal_path = Journal::path_for(&path);
+    std::fs::OpenOptions::new()
+        .append(true)
+        .open(&journal_path)
+        .expect("open journal file")
+        .write_all(b"(seq: 3, record: Apply(ops: [SetPos")
+        .expect("write partial record");
+
+    let mut palette = Palette::read_from_path(&path).expect("read palette");
+    assert_eq!(palette.position_cursor(), column(1));
+
+    palette.set_journal(Some(16));
+    apply_and_write(&mut palette, 2);
+    assert!(!journal_path.exists());
+    assert_eq!(stored_cursor(&path), column(2));
+
+    clean_up(&path);
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Compaction
+////////////////////////////////////////////////////////////////////////////////
+
+/// Tests that the journal is compacted into the palette file once it would
+/// exceed its threshold, leaving no journal or temporary file behind.
+#[test]
+fn journal_compact_at_threshold() {
+    let path = palette_path("journal-compact");
+    // Each write records the applied operations and the palette state.
+    let mut palette = journaled_palette(&path, 4);
+    let journal_path = Journal::path_for(&path);
+
+    apply_and_write(&mut palette, 1);
+    apply_and_write(&mut palette, 2);
+    assert!(journal_path.exists());
+    assert_eq!(stored_cursor(&path), column(0));
+
+    apply_and_write(&mut palette, 3);
+    assert!(!journal_path.exists());
+    assert_eq!(stored_cursor(&path), column(3));
+    let dir = path.parent().expect("palette directory");
+    assert_eq!(std::fs::read_dir(dir).expect("read directory").count(), 1);
+
+    // Changes after compaction are journaled again.
+    apply_and_write(&mut palette, 4);
+    assert!(journal_path.exists());
+
+    let mut palette = Palette::read_from_path(&path).expect("read palette");
+    assert_eq!(palette.position_cursor(), column(4));
+    assert_eq!(palette.undo(4), 4);
+    assert_eq!(palette.position_cursor(), column(0));
+
+    clean_up(&path);
+}
@@ -0,0 +1,699 @@
+////////////////////////////////////////////////////////////////////////////////
+// Atma structured color palette
+////////////////////////////////////////////////////////////////////////////////
+// Copyright 2020 Skylor R. Schermer
+// This code is dual licenced using the MIT or Apache 2 license.
+// See licence-mit.md and licence-apache.md for details.
+////////////////////////////////////////////////////////////////////////////////
+//! Reference and name rewriting tests.
+////////////////////////////////////////////////////////////////////////////////
+
+// Local imports.
+use crate::cell::Cell;
+use crate::cell::CellRef;
+use crate::cell::CellSelection;
+use crate::cell::CellSelector;
+use crate::cell::Position;
+use crate::cell::PositionOffset;
+use crate::cell::PositionSelector;
+use crate::color::Color;
+use crate::color::Rgb;
+use crate::command::ConflictPolicy;
+use crate::command::CursorBehavior;
+use crate::command::Positioning;
+use crate::command::ShiftMode;
+use crate::command::SortKey;
+use crate::error::PaletteError;
+use crate::palette::Expr;
+use crate::palette::InsertExpr;
+use crate::palette::Operation;
+use crate::palette::Palette;
+
+
+/// Returns the position in column `column` of the first line.
+fn column(column: u16) -> Position {
+    Position { page: 0, line: 0, column }
+}
+
+/// Returns an expression referencing the cell in column `col`.
+fn column_ref(col: u16) -> Expr {
+    Expr::Reference(CellRef::Position(column(col)))
+}
+
+/// Returns an expression with the given RGB color.
+fn color_expr(octets: [u8; 3]) -> Expr {
+    Expr::Color(Color::from(Rgb::from(octets)))
+}
+
+/// Returns a palette with history holding the given cell indices, columns,
+/// and expressions, and with the given names assigned to columns.
+fn palette_with_cells(
+    cells: Vec<(u32, u16, Expr)>,
+    names: &[(&'static str, u16)])
+    -> Palette
+{
+    use Operation::*;
+    let mut ops = Vec::new();
+    for (idx, col, expr) in cells {
+        ops.push(InsertCell { idx, cell: Cell::new_with_expr(expr) });
+        ops.push(AssignPosition {
+            cell_ref: CellRef::Index(idx),
+            position: column(col),
+        });
+    }
+    for (name, col) in names {
+        ops.push(AssignName {
+            selector: PositionSelector::from(column(*col)),
+            name: (*name).into(),
+        });
+    }
+
+    let mut palette = Palette::new().with_history();
+    palette.apply_operations(&ops[..]).expect("build palette");
+    palette
+}
+
+/// Returns a palette with history holding the given expressions in the
+/// columns matching their indices, and with the name "second" assigned to
+/// column 1.
+fn palette_with_exprs(exprs: Vec<Expr>) -> Palette {
+    palette_with_cells(exprs
+            .into_iter()
+            .enumerate()
+            .map(|(idx, expr)| (idx as u32, idx as u16, expr))
+            .collect(),
+        &[("second", 1)])
+}
+
+/// Locks the cell with the given index.
+fn lock(palette: &mut Palette, idx: u32) {
+    palette.apply_operations(&[Operation::Lock {
+            cell_ref: CellRef::Index(idx),
+        }])
+        .expect("lock cell");
+}
+
+/// Returns the expression of the cell with the given index.
+fn expr(palette: &Palette, idx: u32) -> Option<Expr> {
+    palette.inner()
+        .cell(&CellRef::Index(idx))
+        .ok()
+        .map(|cell| cell.expr().clone())
+}
+
+/// Returns the position selector the name "second" is assigned to.
+fn second(palette: &Palette) -> Option<PositionSelector> {
+    palette.inner().get_name_selector("second").copied()
+}
+
+/// Returns the position of the cell with the given index.
+fn position(palette: &Palette, idx: u32) -> Option<Position> {
+    palette.inner().assigned_position(&CellRef::Index(idx)).copied()
+}
+
+/// Returns the positions of the cells with the given indices.
+fn positions(palette: &Palette, indices: &[u32]) -> Vec<Option<Position>> {
+    indices.iter().map(|idx| position(palette, *idx)).collect()
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Shifting
+////////////////////////////////////////////////////////////////////////////////
+
+/// Tests that inserting with a shift moves the names and position references
+/// of the shifted cells, and that undo restores them.
+#[test]
+fn insert_shift_moves_names_and_refs() {
+    let mut palette = palette_with_exprs(vec![
+        column_ref(2),
+        Expr::Empty,
+        column_ref(1),
+    ]);
+    // References held by locked cells are rewritten too.
+    lock(&mut palette, 0);
+
+    palette.insert_exprs(
+            &[InsertExpr::Reference(CellRef::Index(0))],
+            None::<&str>,
+            Positioning::Position(column(1)),
+            CursorBehavior::RemainInPlace,
+            Some(ShiftMode::Line))
+        .expect("insert with shift");
+
+    assert_eq!(position(&palette, 1), Some(column(2)));
+    assert_eq!(position(&palette, 2), Some(column(3)));
+    assert_eq!(position(&palette, 3), Some(column(1)));
+    assert_eq!(second(&palette), Some(PositionSelector::from(column(2))));
+    assert_eq!(palette.inner().resolve_name_if_occupied("second"), Some(1));
+    assert_eq!(expr(&palette, 0), Some(column_ref(3)));
+    assert_eq!(expr(&palette, 2), Some(column_ref(2)));
+
+    assert_eq!(palette.undo(1), 1);
+    assert_eq!(expr(&palette, 3), None);
+    assert_eq!(position(&palette, 1), Some(column(1)));
+    assert_eq!(position(&palette, 2), Some(column(2)));
+    assert_eq!(second(&palette), Some(PositionSelector::from(column(1))));
+    assert_eq!(expr(&palette, 0), Some(column_ref(2)));
+    assert_eq!(expr(&palette, 2), Some(column_ref(1)));
+}
+
+/// Tests that inserting with a shift fails if a locked cell would be moved.
+#[test]
+fn insert_shift_locked() {
+    let mut palette = palette_with_exprs(vec![
+        Expr::Empty,
+        Expr::Empty,
+        Expr::Empty,
+    ]);
+    lock(&mut palette, 2);
+
+    let res = palette.insert_exprs(
+        &[InsertExpr::Reference(CellRef::Index(0))],
+        None::<&str>,
+        Positioning::Position(column(1)),
+        CursorBehavior::RemainInPlace,
+        Some(ShiftMode::Line));
+
+    assert!(matches!(res, Err(PaletteError::LockedCell { .. })));
+    assert_eq!(expr(&palette, 3), None);
+    assert_eq!(position(&palette, 2), Some(column(2)));
+}
+
+/// Tests that moving with a shift moves the names and position references of
+/// the shifted cells, and that undo restores them.
+#[test]
+fn move_shift_moves_names_and_refs() {
+    let mut palette = palette_with_exprs(vec![
+        column_ref(2),
+        Expr::Empty,
+        column_ref(1),
+    ]);
+
+    palette.move_selection(
+            CellSelection::from(CellSelector::Index(0)),
+            Positioning::Position(column(2)),
+            CursorBehavior::RemainInPlace,
+            Some(ShiftMode::Line))
+        .expect("move with shift");
+
+    assert_eq!(position(&palette, 0), Some(column(2)));
+    assert_eq!(position(&palette, 1), Some(column(0)));
+    assert_eq!(position(&palette, 2), Some(column(1)));
+    assert_eq!(second(&palette), Some(PositionSelector::from(column(0))));
+    assert_eq!(palette.inner().resolve_name_if_occupied("second"), Some(1));
+    assert_eq!(expr(&palette, 0), Some(column_ref(1)));
+    assert_eq!(expr(&palette, 2), Some(column_ref(0)));
+
+    assert_eq!(palette.undo(1), 1);
+    assert_eq!(position(&palette, 0), Some(column(0)));
+    assert_eq!(position(&palette, 1), Some(column(1)));
+    assert_eq!(position(&palette, 2), Some(column(2)));
+    assert_eq!(second(&palette), Some(PositionSelector::from(column(1))));
+    assert_eq!(expr(&palette, 0), Some(column_ref(2)));
+    assert_eq!(expr(&palette, 2), Some(column_ref(1)));
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Sorting
+////////////////////////////////////////////////////////////////////////////////
+
+/// Tests that sorting moves the names and position references of the sorted
+/// cells, and that undo restores them.
+#[test]
+fn sort_moves_names_and_refs() {
+    let mut palette = palette_with_exprs(vec![
+        color_expr([0xFF, 0xFF, 0xFF]),
+        color_expr([0x00, 0x00, 0x00]),
+        column_ref(0),
+    ]);
+
+    palette.sort_selection(
+            CellSelection::from(CellSelector::IndexRange { low: 0, high: 1 }),
+            SortKey::Lightness,
+            false)
+        .expect("sort");
+
+    assert_eq!(positions(&palette, &[0, 1, 2]),
+        vec![Some(column(1)), Some(column(0)), Some(column(2))]);
+    assert_eq!(second(&palette), Some(PositionSelector::from(column(0))));
+    assert_eq!(palette.inner().resolve_name_if_occupied("second"), Some(1));
+    assert_eq!(expr(&palette, 2), Some(column_ref(1)));
+
+    assert_eq!(palette.undo(1), 1);
+    assert_eq!(positions(&palette, &[0, 1, 2]),
+        vec![Some(column(0)), Some(column(1)), Some(column(2))]);
+    assert_eq!(second(&palette), Some(PositionSelector::from(column(1))));
+    assert_eq!(expr(&palette, 2), Some(column_ref(0)));
+
+    assert_eq!(palette.redo(1), 1);
+    assert_eq!(positions(&palette, &[0, 1, 2]),
+        vec![Some(column(1)), Some(column(0)), Some(column(2))]);
+    assert_eq!(second(&palette), Some(PositionSelector::from(column(0))));
+}
+
+/// Tests that sorting fails if a locked cell would be moved.
+#[test]
+fn sort_locked() {
+    let mut palette = palette_with_exprs(vec![
+        color_expr([0xFF, 0xFF, 0xFF]),
+        color_expr([0x00, 0x00, 0x00]),
+    ]);
+    lock(&mut palette, 0);
+
+    let res = palette.sort_selection(
+        CellSelection::from(CellSelector::All),
+        SortKey::Lightness,
+        false);
+
+    assert!(matches!(res, Err(PaletteError::LockedCell { .. })));
+    assert_eq!(positions(&palette, &[0, 1]),
+        vec![Some(column(0)), Some(column(1))]);
+}
+
+/// Tests that undo reverts the operations of an entry in reverse order, so
+/// that operations which displace each other are restored.
+#[test]
+fn undo_reverse_order() {
+    let mut palette = palette_with_exprs(vec![Expr::Empty, Expr::Empty]);
+    palette.apply_operations(&[
+            Operation::AssignPosition {
+                cell_ref: CellRef::Index(0),
+                position: column(1),
+            },
+            Operation::AssignPosition {
+                cell_ref: CellRef::Index(1),
+                position: column(0),
+            },
+        ])
+        .expect("swap positions");
+    assert_eq!(positions(&palette, &[0, 1]),
+        vec![Some(column(1)), Some(column(0))]);
+
+    assert_eq!(palette.undo(1), 1);
+    assert_eq!(positions(&palette, &[0, 1]),
+        vec![Some(column(0)), Some(column(1))]);
+
+    assert_eq!(palette.redo(1), 1);
+    assert_eq!(positions(&palette, &[0, 1]),
+        vec![Some(column(1)), Some(column(0))]);
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Compacting
+////////////////////////////////////////////////////////////////////////////////
+
+/// Tests that compacting rewrites the index and position references and names
+/// of the compacted cells, and that undo restores them.
+#[test]
+fn compact_moves_names_and_refs() {
+    let mut palette = palette_with_cells(vec![
+            (0, 0, column_ref(4)),
+            (2, 2, Expr::Reference(CellRef::Index(4))),
+            (4, 4, Expr::Empty),
+        ],
+        &[("third", 4)]);
+
+    palette.compact_selection(
+            CellSelection::from(CellSelector::All),
+            true,
+            true)
+        .expect("compact");
+
+    assert_eq!(positions(&palette, &[0, 1, 2, 4]),
+        vec![Some(column(0)), Some(column(1)), Some(column(2)), None]);
+    assert_eq!(expr(&palette, 0), Some(column_ref(2)));
+    assert_eq!(expr(&palette, 1), Some(Expr::Reference(CellRef::Index(2))));
+    assert_eq!(expr(&palette, 2), Some(Expr::Empty));
+    assert_eq!(expr(&palette, 4), None);
+    assert_eq!(palette.inner().resolve_name_if_occupied("third"), Some(2));
+
+    assert_eq!(palette.undo(1), 1);
+    assert_eq!(positions(&palette, &[0, 1, 2, 4]),
+        vec![Some(column(0)), None, Some(column(2)), Some(column(4))]);
+    assert_eq!(expr(&palette, 0), Some(column_ref(4)));
+    assert_eq!(expr(&palette, 1), None);
+    assert_eq!(expr(&palette, 2), Some(Expr::Reference(CellRef::Index(4))));
+    assert_eq!(palette.inner().resolve_name_if_occupied("third"), Some(4));
+}
+
+/// Tests that compacting renumbers locked cells, keeping them locked, and that
+/// undo restores the lock on the original index.
+#[test]
+fn compact_renumbers_locked_cells() {
+    let mut palette = palette_with_cells(vec![
+            (0, 0, Expr::Empty),
+            (2, 1, Expr::Empty),
+            (4, 2, Expr::Empty),
+        ],
+        &[]);
+    lock(&mut palette, 4);
+
+    palette.compact_selection(
+            CellSelection::from(CellSelector::All),
+            true,
+            true)
+        .expect("compact");
+
+    assert_eq!(positions(&palette, &[0, 1, 2]),
+        vec![Some(column(0)), Some(column(1)), Some(column(2))]);
+    assert!(palette.inner().is_locked(&CellRef::Index(2)));
+    assert!(!palette.inner().is_locked(&CellRef::Index(4)));
+
+    assert_eq!(palette.undo(1), 1);
+    assert_eq!(positions(&palette, &[0, 2, 4]),
+        vec![Some(column(0)), Some(column(1)), Some(column(2))]);
+    assert!(palette.inner().is_locked(&CellRef::Index(4)));
+    assert!(!palette.inner().is_locked(&CellRef::Index(2)));
+}
+
+/// Tests that compacting fails if the position of a locked cell would be
+/// moved.
+#[test]
+fn compact_locked_position() {
+    let mut palette = palette_with_cells(vec![
+            (0, 0, Expr::Empty),
+            (1, 2, Expr::Empty),
+        ],
+        &[]);
+    lock(&mut palette, 1);
+
+    let res = palette.compact_selection(
+        CellSelection::from(CellSelector::All),
+        true,
+        false);
+
+    assert!(matches!(res, Err(PaletteError::LockedCell { .. })));
+    assert_eq!(position(&palette, 1), Some(column(2)));
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Duplicating
+////////////////////////////////////////////////////////////////////////////////
+
+/// Returns an expression referencing the cell with the given name.
+fn name_ref(name: &'static str) -> Expr {
+    Expr::Reference(CellRef::Name(name.into()))
+}
+
+/// Tests that duplicating remaps the references between the selected cells to
+/// the copies and names the copies, and that undo removes them.
+#[test]
+fn duplicate_remaps_names_and_refs() {
+    let mut palette = palette_with_exprs(vec![
+        Expr::Empty,
+        column_ref(0),
+        name_ref("second"),
+    ]);
+
+    palette.duplicate_selection(
+            CellSelection::from(CellSelector::All),
+            Positioning::Position(column(4)),
+            "_copy",
+            CursorBehavior::RemainInPlace)
+        .expect("duplicate");
+
+    assert_eq!(positions(&palette, &[3, 4, 5]),
+        vec![Some(column(4)), Some(column(5)), Some(column(6))]);
+    assert_eq!(expr(&palette, 3), Some(Expr::Empty));
+    assert_eq!(expr(&palette, 4), Some(column_ref(4)));
+    assert_eq!(expr(&palette, 5), Some(name_ref("second_copy")));
+    assert_eq!(palette.inner().resolve_name_if_occupied("second_copy"),
+        Some(4));
+    assert_eq!(expr(&palette, 1), Some(column_ref(0)));
+    assert_eq!(expr(&palette, 2), Some(name_ref("second")));
+
+    assert_eq!(palette.undo(1), 1);
+    assert_eq!(positions(&palette, &[3, 4, 5]), vec![None, None, None]);
+    assert_eq!(expr(&palette, 3), None);
+    assert!(!palette.inner().is_assigned_name("second_copy"));
+    assert_eq!(palette.inner().resolve_name_if_occupied("second"), Some(1));
+}
+
+/// Tests that duplicating leaves references to unselected cells unchanged.
+#[test]
+fn duplicate_keeps_unselected_refs() {
+    let mut palette = palette_with_exprs(vec![
+        Expr::Empty,
+        column_ref(0),
+        name_ref("second"),
+    ]);
+
+    palette.duplicate_selection(
+            CellSelection::from(CellSelector::IndexRange { low: 1, high: 2 }),
+            Positioning::Position(column(4)),
+            "_copy",
+            CursorBehavior::RemainInPlace)
+        .expect("duplicate");
+
+    assert_eq!(positions(&palette, &[3, 4]),
+        vec![Some(column(4)), Some(column(5))]);
+    assert_eq!(expr(&palette, 3), Some(column_ref(0)));
+    assert_eq!(expr(&palette, 4), Some(name_ref("second_copy")));
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Relative references
+////////////////////////////////////////////////////////////////////////////////
+
+/// Returns an expression referencing the cell at the given column offset.
+fn relative_ref(column: i32) -> Expr {
+    Expr::Reference(CellRef::Relative(PositionOffset {
+        page: 0,
+        line: 0,
+        column,
+    }))
+}
+
+/// Returns the RGB octets of the color of the cell with the given index.
+fn octets(palette: &Palette, idx: u32) -> Option<[u8; 3]> {
+    palette.inner()
+        .color(&CellRef::Index(idx))
+        .ok()
+        .flatten()
+        .map(|color| color.rgb_octets())
+}
+
+/// Tests that duplicated relative references are unchanged and refer to the
+/// cells near the copies, and that undo removes the copies.
+#[test]
+fn relative_refs_duplicate() {
+    let mut palette = palette_with_cells(vec![
+            (0, 0, color_expr([0xFF, 0xFF, 0xFF])),
+            (1, 1, relative_ref(-1)),
+            (2, 4, color_expr([0x00, 0x00, 0x00])),
+        ],
+        &[]);
+    assert_eq!(octets(&palette, 1), Some([0xFF, 0xFF, 0xFF]));
+
+    palette.duplicate_selection(
+            CellSelection::from(CellSelector::Index(1)),
+            Positioning::Position(column(5)),
+            "_copy",
+            CursorBehavior::RemainInPlace)
+        .expect("duplicate");
+
+    assert_eq!(position(&palette, 3), Some(column(5)));
+    assert_eq!(expr(&palette, 3), Some(relative_ref(-1)));
+    assert_eq!(octets(&palette, 3), Some([0x00, 0x00, 0x00]));
+    assert_eq!(octets(&palette, 1), Some([0xFF, 0xFF, 0xFF]));
+
+    assert_eq!(palette.undo(1), 1);
+    assert_eq!(expr(&palette, 3), None);
+    assert_eq!(octets(&palette, 1), Some([0xFF, 0xFF, 0xFF]));
+}
+
+/// Tests that relative references are unchanged when their cells are shifted
+/// together, and still refer to the same cells.
+#[test]
+fn relative_refs_shift() {
+    let mut palette = palette_with_cells(vec![
+            (0, 0, color_expr([0xFF, 0xFF, 0xFF])),
+            (1, 1, relative_ref(-1)),
+        ],
+        &[]);
+
+    palette.insert_exprs(
+            &[InsertExpr::Reference(CellRef::Index(0))],
+            None::<&str>,
+            Positioning::Position(column(0)),
+            CursorBehavior::RemainInPlace,
+            Some(ShiftMode::Line))
+        .expect("insert with shift");
+
+    assert_eq!(positions(&palette, &[0, 1]),
+        vec![Some(column(1)), Some(column(2))]);
+    assert_eq!(expr(&palette, 1), Some(relative_ref(-1)));
+    assert_eq!(octets(&palette, 1), Some([0xFF, 0xFF, 0xFF]));
+
+    assert_eq!(palette.undo(1), 1);
+    assert_eq!(positions(&palette, &[0, 1]),
+        vec![Some(column(0)), Some(column(1))]);
+    assert_eq!(octets(&palette, 1), Some([0xFF, 0xFF, 0xFF]));
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Renaming
+////////////////////////////////////////////////////////////////////////////////
+
+/// Returns an expression referencing the cell in the given group.
+fn group_ref(group: &'static str, idx: u32) -> Expr {
+    Expr::Reference(CellRef::Group { group: group.into(), idx })
+}
+
+/// Returns the palette's groups and their members.
+fn groups(palette: &Palette) -> Vec<(String, Vec<u32>)> {
+    palette.inner()
+        .groups()
+        .map(|(group, members)| (group.to_owned(), members.to_vec()))
+        .collect()
+}
+
+/// Tests that renaming a name rewrites the references to it, and that undo
+/// restores them.
+#[test]
+fn rename_name_rewrites_refs() {
+    let mut palette = palette_with_exprs(vec![
+        Expr::Empty,
+        Expr::Empty,
+        name_ref("second"),
+    ]);
+
+    palette.rename_name("second", "middle").expect("rename name");
+
+    assert!(!palette.inner().is_assigned_name("second"));
+    assert_eq!(palette.inner().resolve_name_if_occupied("middle"), Some(1));
+    assert_eq!(expr(&palette, 2), Some(name_ref("middle")));
+
+    assert_eq!(palette.undo(1), 1);
+    assert!(!palette.inner().is_assigned_name("middle"));
+    assert_eq!(palette.inner().resolve_name_if_occupied("second"), Some(1));
+    assert_eq!(expr(&palette, 2), Some(name_ref("second")));
+}
+
+/// Tests that renaming a name to an assigned name fails.
+#[test]
+fn rename_name_assigned() {
+    let mut palette = palette_with_cells(vec![
+            (0, 0, Expr::Empty),
+            (1, 1, Expr::Empty),
+        ],
+        &[("first", 0), ("second", 1)]);
+
+    assert!(palette.rename_name("second", "first").is_err());
+    assert_eq!(palette.inner().resolve_name_if_occupied("first"), Some(0));
+    assert_eq!(palette.inner().resolve_name_if_occupied("second"), Some(1));
+}
+
+/// Tests that renaming a group renames its nested groups and rewrites the
+/// references to them, and that undo restores them.
+#[test]
+fn rename_group_rewrites_refs() {
+    let mut palette = palette_with_exprs(vec![
+        Expr::Empty,
+        Expr::Empty,
+        group_ref("ramp", 1),
+        group_ref("ramp/dark", 0),
+    ]);
+    let assign = |idx, group: &'static str| Operation::AssignGroup {
+        cell_ref: CellRef::Index(idx),
+        group: group.into(),
+        idx: None,
+    };
+    palette.apply_operations(&[
+            assign(0, "ramp"),
+            assign(1, "ramp"),
+            assign(1, "ramp/dark"),
+        ])
+        .expect("assign groups");
+
+    palette.rename_group("ramp", "shades").expect("rename group");
+
+    assert_eq!(groups(&palette), vec![
+        ("shades".to_owned(), vec![0, 1]),
+        ("shades/dark".to_owned(), vec![1]),
+    ]);
+    assert_eq!(expr(&palette, 2), Some(group_ref("shades", 1)));
+    assert_eq!(expr(&palette, 3), Some(group_ref("shades/dark", 0)));
+
+    assert_eq!(palette.undo(1), 1);
+    assert_eq!(groups(&palette), vec![
+        ("ramp".to_owned(), vec![0, 1]),
+        ("ramp/dark".to_owned(), vec![1]),
+    ]);
+    assert_eq!(expr(&palette, 2), Some(group_ref("ramp", 1)));
+    assert_eq!(expr(&palette, 3), Some(group_ref("ramp/dark", 0)));
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+// Merging
+////////////////////////////////////////////////////////////////////////////////
+
+/// Returns a palette to merge holding an empty cell, a position reference to
+/// it, and a reference to the name "second".
+fn merge_source() -> Palette {
+    palette_with_exprs(vec![
+        Expr::Empty,
+        column_ref(0),
+        name_ref("second"),
+    ])
+}
+
+/// Tests that merging rewrites the references between the merged cells and
+/// prefixes their names, and that undo removes them.
+#[test]
+fn merge_remaps_names_and_refs() {
+    let other = merge_source();
+    let mut palette = palette_with_cells(vec![(0, 0, Expr::Empty)], &[]);
+
+    palette.merge_palette(
+            other.inner(),
+            Some(column(4)),
+            Some("m_"),
+            ConflictPolicy::Rename)
+        .expect("merge");
+
+    assert_eq!(positions(&palette, &[1, 2, 3]),
+        vec![Some(column(4)), Some(column(5)), Some(column(6))]);
+    assert_eq!(expr(&palette, 2), Some(column_ref(4)));
+    assert_eq!(expr(&palette, 3), Some(name_ref("m_second")));
+    assert_eq!(palette.inner().resolve_name_if_occupied("m_second"), Some(2));
+
+    assert_eq!(palette.undo(1), 1);
+    assert_eq!(expr(&palette, 1), None);
+    assert_eq!(expr(&palette, 2), None);
+    assert_eq!(expr(&palette, 3), None);
+    assert!(!palette.inner().is_assigned_name("m_second"));
+}
+
+/// Tests that merging with the rename policy relocates and renames the
+/// conflicting cells, rewriting the references to them.
+#[test]
+fn merge_rename_conflicts() {
+    let other = merge_source();
+    let mut palette = palette_with_exprs(vec![
+        Expr::Empty,
+        Expr::Empty,
+        Expr::Empty,
+    ]);
+
+    palette.merge_palette(other.inner(), None, None, ConflictPolicy::Rename)
+        .expect("merge");
+
+    assert_eq!(positions(&palette, &[3, 4, 5]),
+        vec![Some(column(3)), Some(column(4)), Some(column(5))]);
+    assert_eq!(expr(&palette, 4), Some(column_ref(3)));
+    assert_eq!(expr(&palette, 5), Some(name_ref("second_2")));
+    assert_eq!(palette.inner().resolve_name_if_occupied("second_2"), Some(4));
+    assert_eq!(palette.inner().resolve_name_if_occupied("second"), Some(1));
+
+    assert_eq!(palette.undo(1), 1);
+    assert_eq!(positions(&palette, &[3, 4, 5]), vec![None, None, None]);
+    assert!(!palette.inner().is_assigned_name("second_2"));
+}